@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fs::{File, OpenOptions},
     path::Path,
 };
@@ -6,7 +7,7 @@ use std::{
 use globed_shared::{
     anyhow::{self, anyhow},
     esp::{self, Decodable, Encodable},
-    generate_alphanum_string, Decodable, Encodable, ServerRole, ADMIN_KEY_LENGTH, DEFAULT_GAME_SERVER_PORT,
+    generate_alphanum_string, Decodable, Encodable, ServerRole, ADMIN_KEY_LENGTH, DEFAULT_GAME_SERVER_PORT, DEFAULT_MAX_PACKET_SIZE,
 };
 use json_comments::StripComments;
 use serde::{Deserialize, Serialize};
@@ -60,6 +61,10 @@ const fn default_status_print_interval() -> u64 {
     7200 // 2 hours
 }
 
+const fn default_config_refresh_interval() -> u64 {
+    300 // 5 minutes
+}
+
 const fn default_userlist_mode() -> UserlistMode {
     UserlistMode::None
 }
@@ -76,6 +81,86 @@ const fn default_chat_burst_interval() -> u32 {
     3000
 }
 
+const fn default_voice_throughput_limit() -> u32 {
+    8
+}
+
+const fn default_voice_proximity_radius() -> u32 {
+    0
+}
+
+const fn default_voice_silence_threshold() -> u32 {
+    0
+}
+
+const fn default_voice_min_bitrate() -> u32 {
+    0
+}
+
+const fn default_voice_max_bitrate() -> u32 {
+    0
+}
+
+const fn default_voice_max_frames() -> u32 {
+    10
+}
+
+const fn default_max_packet_size() -> u32 {
+    DEFAULT_MAX_PACKET_SIZE
+}
+
+const fn default_keepalive_interval() -> u32 {
+    30
+}
+
+const fn default_login_attempt_limit() -> u32 {
+    5
+}
+
+const fn default_login_attempt_ip_limit() -> u32 {
+    20
+}
+
+const fn default_login_attempt_interval() -> u32 {
+    60
+}
+
+const fn default_handshake_limit() -> u32 {
+    5
+}
+
+const fn default_user_data_cache_ttl() -> u32 {
+    30
+}
+
+const fn default_profile_request_limit() -> u32 {
+    10
+}
+
+const fn default_profile_request_interval() -> u32 {
+    5000
+}
+
+const fn default_profile_request_max_players() -> u32 {
+    100
+}
+
+const fn default_max_players() -> u32 {
+    0
+}
+
+const fn default_login_queue_size() -> u32 {
+    0
+}
+
+const fn default_soft_max_players() -> u32 {
+    0
+}
+
+const fn default_max_accounts_per_ip() -> u32 {
+    0
+}
+
 fn default_roles() -> Vec<ServerRole> {
     vec![
         ServerRole {
@@ -115,6 +200,14 @@ const fn default_token_expiry() -> u64 {
     60 * 60 * 24
 }
 
+fn default_banned_ips() -> Vec<String> {
+    Vec::new()
+}
+
+fn default_game_server_features() -> HashMap<String, GameServerFeatures> {
+    HashMap::new()
+}
+
 /* end stinky serde defaults */
 
 #[derive(PartialEq, Eq, Debug, Default, Clone, Serialize, Deserialize)]
@@ -136,6 +229,17 @@ pub struct GameServerEntry {
     pub region: String,
 }
 
+/// Overrides of `voice_enabled`, `chat_enabled` and `profiles_enabled` for a single game server, unset ones use the global setting.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct GameServerFeatures {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voice_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles_enabled: Option<bool>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct ServerConfig {
@@ -147,6 +251,8 @@ pub struct ServerConfig {
     pub maintenance: bool,
     #[serde(default = "default_status_print_interval")]
     pub status_print_interval: u64,
+    #[serde(default = "default_config_refresh_interval")]
+    pub config_refresh_interval: u64,
 
     // special users and "special" users
     #[serde(default = "default_userlist_mode")]
@@ -158,12 +264,77 @@ pub struct ServerConfig {
 
     #[serde(default = "default_string")]
     pub admin_webhook_url: String,
+    #[serde(default = "default_false")]
+    pub join_leave_webhook: bool,
+    #[serde(default = "default_string")]
+    pub server_name: String,
+    #[serde(default = "default_string")]
+    pub motd: String,
 
     // chat limits
     #[serde(default = "default_chat_burst_limit")]
     pub chat_burst_limit: u32,
     #[serde(default = "default_chat_burst_interval")]
     pub chat_burst_interval: u32,
+    #[serde(default = "default_true")]
+    pub chat_enabled: bool,
+
+    // voice limits
+    #[serde(default = "default_voice_throughput_limit")]
+    pub voice_throughput_limit: u32,
+    #[serde(default = "default_voice_proximity_radius")]
+    pub voice_proximity_radius: u32,
+    #[serde(default = "default_voice_silence_threshold")]
+    pub voice_silence_threshold: u32,
+    #[serde(default = "default_voice_min_bitrate")]
+    pub voice_min_bitrate: u32,
+    #[serde(default = "default_voice_max_bitrate")]
+    pub voice_max_bitrate: u32,
+    #[serde(default = "default_voice_max_frames")]
+    pub voice_max_frames: u32,
+    #[serde(default = "default_true")]
+    pub voice_enabled: bool,
+
+    // packet limits
+    #[serde(default = "default_max_packet_size")]
+    pub max_packet_size: u32,
+    #[serde(default = "default_keepalive_interval")]
+    pub keepalive_interval: u32,
+
+    // login rate limits
+    #[serde(default = "default_login_attempt_limit")]
+    pub login_attempt_limit: u32,
+    #[serde(default = "default_login_attempt_ip_limit")]
+    pub login_attempt_ip_limit: u32,
+    #[serde(default = "default_login_attempt_interval")]
+    pub login_attempt_interval: u32,
+    #[serde(default = "default_handshake_limit")]
+    pub handshake_limit: u32,
+    #[serde(default = "default_user_data_cache_ttl")]
+    pub user_data_cache_ttl: u32,
+
+    // profile request limits
+    #[serde(default = "default_profile_request_limit")]
+    pub profile_request_limit: u32,
+    #[serde(default = "default_profile_request_interval")]
+    pub profile_request_interval: u32,
+    #[serde(default = "default_profile_request_max_players")]
+    pub profile_request_max_players: u32,
+    #[serde(default = "default_true")]
+    pub profiles_enabled: bool,
+    /// keyed by the ID that the game server sends in the boot request
+    #[serde(default = "default_game_server_features")]
+    pub game_server_features: HashMap<String, GameServerFeatures>,
+
+    // player limits
+    #[serde(default = "default_max_players")]
+    pub max_players: u32,
+    #[serde(default = "default_login_queue_size")]
+    pub login_queue_size: u32,
+    #[serde(default = "default_soft_max_players")]
+    pub soft_max_players: u32,
+    #[serde(default = "default_max_accounts_per_ip")]
+    pub max_accounts_per_ip: u32,
 
     // roles
     #[serde(default = "default_roles")]
@@ -196,6 +367,8 @@ pub struct ServerConfig {
     pub challenge_expiry: u32,
     #[serde(default = "default_token_expiry")]
     pub token_expiry: u64,
+    #[serde(default = "default_banned_ips")]
+    pub banned_ips: Vec<String>,
 }
 
 impl ServerConfig {
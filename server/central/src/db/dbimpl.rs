@@ -1,6 +1,6 @@
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use globed_shared::UserEntry;
+use globed_shared::{BannedUser, UserEntry};
 use rocket_db_pools::sqlx::{query_as, Result};
 use serde::Serialize;
 use sqlx::{prelude::*, query, sqlite::SqliteRow};
@@ -71,6 +71,32 @@ impl GlobedDb {
         self.unwrap_user(res).await
     }
 
+    /// Fetch all users that are currently banned. Bans that have already expired are skipped.
+    #[allow(clippy::cast_possible_wrap)]
+    pub async fn get_banned_users(&self) -> Result<Vec<BannedUser>> {
+        let rows = query("SELECT account_id, violation_reason, violation_expiry FROM users WHERE is_banned = 1")
+            .fetch_all(&self.0)
+            .await?;
+
+        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).expect("clock went backwards").as_secs() as i64;
+
+        let mut users = Vec::with_capacity(rows.len());
+        for row in rows {
+            let expiry: Option<i64> = row.try_get("violation_expiry")?;
+            if expiry.is_some_and(|expiry| current_time > expiry) {
+                continue;
+            }
+
+            users.push(BannedUser {
+                account_id: row.try_get("account_id")?,
+                reason: row.try_get("violation_reason")?,
+                expiry,
+            });
+        }
+
+        Ok(users)
+    }
+
     pub async fn update_user(&self, account_id: i32, user: &UserEntry) -> Result<()> {
         query(
             "INSERT OR REPLACE INTO users (account_id, user_name, name_color, user_roles, is_banned, is_muted, is_whitelisted, admin_password, violation_reason, violation_expiry)
@@ -17,7 +17,7 @@ use globed_shared::{
     hmac::Hmac,
     rand::{self, distributions::Alphanumeric, rngs::OsRng, Rng, RngCore},
     sha2::Sha256,
-    TokenIssuer,
+    BannedUser, TokenIssuer,
 };
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
@@ -145,21 +145,19 @@ impl ServerStateData {
         orig_value.answer == answer
     }
 
-    pub async fn is_banned(&self, db: &GlobedDb, account_id: i32) -> anyhow::Result<Option<String>> {
+    /// Returns the ban of the given account, if they are banned. Expired bans are lifted by `get_user`.
+    pub async fn get_ban(&self, db: &GlobedDb, account_id: i32) -> anyhow::Result<Option<BannedUser>> {
         if self.config.userlist_mode == UserlistMode::Whitelist {
             return Ok(None);
         }
 
         let user = db.get_user(account_id).await?;
-        if let Some(user) = user {
-            if user.is_banned {
-                Ok(user.violation_reason.clone())
-            } else {
-                Ok(None)
-            }
-        } else {
-            Ok(None)
-        }
+
+        Ok(user.filter(|user| user.is_banned).map(|user| BannedUser {
+            account_id,
+            reason: user.violation_reason,
+            expiry: user.violation_expiry,
+        }))
     }
 }
 
@@ -7,12 +7,11 @@ use globed_shared::{
     anyhow::{self, anyhow},
     base64::{engine::general_purpose as b64e, Engine as _},
     logger::*,
-    MIN_CLIENT_VERSION, PROTOCOL_VERSION,
+    MIN_CLIENT_VERSION,
 };
 use rocket::{post, State};
 
 use crate::{
-    config::UserlistMode,
     db::GlobedDb,
     ip_blocker::IpBlocker,
     state::{ActiveChallenge, ServerState},
@@ -63,19 +62,15 @@ pub async fn totp_login(
     let state_ = state.state_read().await;
     get_user_ip!(state_, ip, cfip, _ip);
 
-    if state_.config.userlist_mode == UserlistMode::Whitelist {
-        if !db.get_user(aid).await?.map_or(false, |x| x.is_whitelisted) {
-            unauthorized!("This server has whitelist enabled and your account ID has not been approved.");
-        }
-    } else {
-        let ban_reason = state_.is_banned(db, aid).await;
-        if let Err(err) = ban_reason {
-            bad_request!(&format!("server error: {err}"));
-        }
+    // in whitelist mode the game servers check the whitelist when the player logs in,
+    // so that accounts in their local whitelist and admins can join as well
+    let ban = state_.get_ban(db, aid).await;
+    if let Err(err) = ban {
+        bad_request!(&format!("server error: {err}"));
+    }
 
-        if let Some(reason) = ban_reason.unwrap() {
-            unauthorized!(&format!("Banned from the server: {reason}"));
-        }
+    if let Some(ban) = ban.unwrap() {
+        unauthorized!(&ban.rejection_message());
     }
 
     let uak_decoded = b64e::URL_SAFE.decode(authkey)?;
@@ -115,19 +110,15 @@ pub async fn challenge_start(
     let mut state = state.state_write().await;
     get_user_ip!(state, ip, cfip, user_ip);
 
-    if state.config.userlist_mode == UserlistMode::Whitelist {
-        if !db.get_user(aid).await?.map_or(false, |x| x.is_whitelisted) {
-            unauthorized!("This server has whitelist enabled and your account ID has not been approved.");
-        }
-    } else {
-        let ban_reason = state.is_banned(db, aid).await;
-        if let Err(err) = ban_reason {
-            bad_request!(&format!("server error: {err}"));
-        }
+    // in whitelist mode the game servers check the whitelist when the player logs in,
+    // so that accounts in their local whitelist and admins can join as well
+    let ban = state.get_ban(db, aid).await;
+    if let Err(err) = ban {
+        bad_request!(&format!("server error: {err}"));
+    }
 
-        if let Some(reason) = ban_reason.unwrap() {
-            unauthorized!(&format!("Banned from the server: {reason}"));
-        }
+    if let Some(ban) = ban.unwrap() {
+        unauthorized!(&ban.rejection_message());
     }
 
     let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?;
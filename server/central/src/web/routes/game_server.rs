@@ -10,12 +10,14 @@ use rocket::{get, post, serde::json::Json, State};
 
 use crate::{config::UserlistMode, db::GlobedDb, state::ServerState, web::*};
 
-#[post("/gs/boot")]
+#[post("/gs/boot?<id>")]
 pub async fn boot(
     state: &State<ServerState>,
     password: GameServerPasswordGuard,
+    database: &GlobedDb,
     ip_address: IpAddr,
     user_agent: GameServerUserAgentGuard<'_>,
+    id: Option<&str>,
 ) -> WebResult<Vec<u8>> {
     let correct = state.state_read().await.config.game_server_password.clone();
 
@@ -23,8 +25,11 @@ pub async fn boot(
         unauthorized!("invalid gameserver credentials");
     }
 
+    let banned_users = database.get_banned_users().await?;
+
     let state = state.state_read().await;
     let config = &state.config;
+    let features = id.and_then(|id| config.game_server_features.get(id)).cloned().unwrap_or_default();
 
     let bdata = GameServerBootData {
         protocol: PROTOCOL_VERSION,
@@ -33,15 +38,49 @@ pub async fn boot(
         secret_key2: config.secret_key2.clone(),
         token_expiry: config.token_expiry,
         status_print_interval: config.status_print_interval,
+        config_refresh_interval: config.config_refresh_interval,
         admin_key: FastString::new(&config.admin_key),
         whitelist: config.userlist_mode == UserlistMode::Whitelist,
         admin_webhook_url: config.admin_webhook_url.clone(),
+        join_leave_webhook: config.join_leave_webhook,
+        server_name: config.server_name.clone(),
+        motd: config.motd.clone(),
         chat_burst_limit: config.chat_burst_limit,
         chat_burst_interval: config.chat_burst_interval,
+        chat_enabled: features.chat_enabled.unwrap_or(config.chat_enabled),
+        voice_throughput_limit: config.voice_throughput_limit,
+        voice_proximity_radius: config.voice_proximity_radius,
+        voice_silence_threshold: config.voice_silence_threshold,
+        voice_min_bitrate: config.voice_min_bitrate,
+        voice_max_bitrate: config.voice_max_bitrate,
+        voice_max_frames: config.voice_max_frames,
+        voice_enabled: features.voice_enabled.unwrap_or(config.voice_enabled),
+        max_packet_size: config.max_packet_size,
+        keepalive_interval: config.keepalive_interval,
+        login_attempt_limit: config.login_attempt_limit,
+        login_attempt_ip_limit: config.login_attempt_ip_limit,
+        login_attempt_interval: config.login_attempt_interval,
+        handshake_limit: config.handshake_limit,
+        user_data_cache_ttl: config.user_data_cache_ttl,
+        profile_request_limit: config.profile_request_limit,
+        profile_request_interval: config.profile_request_interval,
+        profile_request_max_players: config.profile_request_max_players,
+        profiles_enabled: features.profiles_enabled.unwrap_or(config.profiles_enabled),
+        max_players: config.max_players,
+        login_queue_size: config.login_queue_size,
+        soft_max_players: config.soft_max_players,
+        max_accounts_per_ip: config.max_accounts_per_ip,
         roles: config.roles.clone(),
+        banned_users,
+        banned_ips: config.banned_ips.clone(),
     };
 
-    debug!("boot data request from game server {} at {}", user_agent.0, ip_address);
+    debug!(
+        "boot data request from game server {} ({}) at {}",
+        user_agent.0,
+        id.unwrap_or("no ID"),
+        ip_address
+    );
 
     let mut bb = ByteBuffer::new();
     bb.write_bytes(SERVER_MAGIC);
@@ -3,6 +3,13 @@ use crate::*;
 #[derive(Copy, Clone, Default, Debug)]
 pub struct FiniteF32(f32);
 
+impl FiniteF32 {
+    #[inline]
+    pub const fn get(self) -> f32 {
+        self.0
+    }
+}
+
 impl Encodable for FiniteF32 {
     fn encode(&self, buf: &mut ByteBuffer) {
         buf.write_f32(self.0);
@@ -1,7 +1,7 @@
 #![allow(clippy::wildcard_imports, clippy::cast_possible_truncation)]
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use esp::{ByteBuffer, ByteReader};
-use globed_game_server::{data::*, make_uninit, managers::LevelManager, new_uninit};
+use globed_game_server::{data::*, make_uninit, managers::LevelManager, new_uninit, util::buffer_pool};
 use globed_shared::{
     generate_alphanum_string,
     rand::{self, Rng, RngCore},
@@ -254,6 +254,69 @@ fn strings(c: &mut Criterion) {
     });
 }
 
-// criterion_group!(benches, buffers, structs, managers, read_value_array, strings);
-criterion_group!(benches, strings);
+fn buffer_pool(c: &mut Criterion) {
+    // a voice packet copied out to 64 peers, like a broadcast where every send would block
+    const PEERS: usize = 64;
+    let packet = vec![0x42u8; 1200];
+
+    c.bench_function("broadcast-fresh-vec", |b| {
+        b.iter(black_box(|| {
+            for _ in 0..PEERS {
+                let buf = black_box(packet.clone());
+                drop(buf);
+            }
+        }));
+    });
+
+    c.bench_function("broadcast-buffer-pool", |b| {
+        b.iter(black_box(|| {
+            for _ in 0..PEERS {
+                let buf = black_box(buffer_pool::take_copy(&packet));
+                buffer_pool::put(buf);
+            }
+        }));
+    });
+}
+
+fn voice_broadcast(c: &mut Criterion) {
+    // a room of 20 players, so every voice packet goes out to 19 people
+    const RECIPIENTS: usize = 19;
+
+    let packet = VoiceBroadcastPacket {
+        player_id: 123_456,
+        data: FastEncodedAudioFrame {
+            data: vec![0x42u8; 1200].into(),
+        },
+    };
+
+    // both write into the same kind of buffer that gets encrypted afterwards, only the encoding differs
+    c.bench_function("voice-encode-per-recipient", |b| {
+        let mut out = [0u8; 1300];
+
+        b.iter(black_box(|| {
+            for _ in 0..RECIPIENTS {
+                let mut buf = FastByteBuffer::new(&mut out);
+                buf.write_value(black_box(&packet));
+                black_box(buf.len());
+            }
+        }));
+    });
+
+    c.bench_function("voice-encode-once", |b| {
+        let mut out = [0u8; 1300];
+
+        b.iter(black_box(|| {
+            let encoded = EncodedVoiceBroadcastPacket::new(black_box(&packet));
+
+            for _ in 0..RECIPIENTS {
+                let mut buf = FastByteBuffer::new(&mut out);
+                buf.write_bytes(&encoded.data);
+                black_box(buf.len());
+            }
+        }));
+    });
+}
+
+// criterion_group!(benches, buffers, structs, managers, read_value_array, strings, buffer_pool, voice_broadcast);
+criterion_group!(benches, strings, buffer_pool, voice_broadcast);
 criterion_main!(benches);
@@ -0,0 +1,72 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use globed_shared::{
+    time::{format_description::well_known::Rfc3339, OffsetDateTime},
+    warn, SyncMutex,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    admin_id: i32,
+    admin_name: &'a str,
+    action: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_id: Option<i32>,
+    details: &'a str,
+}
+
+/// Append-only record of every action taken by admins and moderators, one JSON object per line.
+pub struct AuditLog {
+    file: Option<SyncMutex<File>>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Some(SyncMutex::new(file)),
+        })
+    }
+
+    pub const fn disabled() -> Self {
+        Self { file: None }
+    }
+
+    /// Records an admin action. `target_id` is the account ID of the affected player, if there is a single one.
+    pub fn record(&self, admin_id: i32, admin_name: &str, action: &str, target_id: Option<i32>, details: &str) {
+        let Some(file) = self.file.as_ref() else {
+            return;
+        };
+
+        let entry = AuditEntry {
+            timestamp: OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default(),
+            admin_id,
+            admin_name,
+            action,
+            target_id,
+            details,
+        };
+
+        let mut line = match serde_json::to_string(&entry) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("failed to serialize an audit log entry: {e}");
+                return;
+            }
+        };
+
+        line.push('\n');
+
+        // a single write per entry, so that lines never get interleaved
+        if let Err(e) = file.lock().write_all(line.as_bytes()) {
+            warn!("failed to write to the audit log: {e}");
+        }
+    }
+}
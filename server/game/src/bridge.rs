@@ -1,17 +1,32 @@
 use std::{
     error::Error,
     fmt::Display,
-    sync::atomic::{AtomicBool, Ordering},
-    time::Duration,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
 };
 
 use esp::{size_of_types, ByteBuffer, ByteBufferExt, ByteBufferExtRead, ByteBufferExtWrite, ByteReader, DecodeError, DynamicSize, StaticSize};
 use globed_shared::{
-    reqwest::{self, StatusCode},
-    GameServerBootData, SyncMutex, TokenIssuer, UserEntry, PROTOCOL_VERSION, SERVER_MAGIC, SERVER_MAGIC_LEN,
+    debug,
+    reqwest::{self, Method, StatusCode},
+    token_issuer::TokenValidationFailure,
+    warn, GameServerBootData, SyncMutex, TokenIssuer, UserEntry, DEFAULT_MAX_PACKET_SIZE, PROTOCOL_VERSION, SERVER_MAGIC, SERVER_MAGIC_LEN,
 };
 
-use crate::webhook::{self, *};
+use crate::{
+    tokio::{
+        self,
+        sync::{
+            mpsc::{self, error::TrySendError},
+            Mutex,
+        },
+    },
+    webhook::{self, *},
+};
 
 #[derive(Debug)]
 pub enum CentralBridgeError {
@@ -52,47 +67,184 @@ impl From<DecodeError> for CentralBridgeError {
 
 impl Error for CentralBridgeError {}
 
+impl CentralBridgeError {
+    /// Whether the error is likely temporary (timeout, connection failure or a 5xx response), so the request is worth retrying.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RequestError(err) => err.is_timeout() || err.is_connect(),
+            Self::CentralError((status, _)) => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, CentralBridgeError>;
 
+pub struct CentralServer {
+    pub url: String,
+    pub password: String,
+}
+
+/// TLS settings for requests to the central server. Webhooks are not affected by these.
+#[derive(Default)]
+pub struct CentralTlsConfig {
+    /// root certificates to trust in addition to the built-in ones, for example of an internal CA
+    pub root_certificates: Vec<reqwest::Certificate>,
+    /// if enabled, only `root_certificates` are trusted and the built-in root certificates are not used
+    pub pin_certificates: bool,
+    /// disables certificate verification entirely. never enable this outside of development
+    pub insecure: bool,
+}
+
+/// how many webhook messages can wait to be sent, once full new ones are dropped
+const WEBHOOK_QUEUE_CAPACITY: usize = 256;
+/// discord allows at most 10 embeds in a single message
+const WEBHOOK_BATCH_SIZE: usize = 10;
+
 /// `CentralBridge` stores the configuration of the game server,
 /// and is used for making requests to the central server.
 pub struct CentralBridge {
+    /// used for webhooks, always verifies certificates against the built-in root certificates
     pub http_client: reqwest::Client,
-    pub central_url: String,
-    pub central_pw: String,
+    /// used for requests to the central server, configured with `CentralTlsConfig`
+    pub central_client: reqwest::Client,
+    /// all configured central servers, requests go to the last one that worked and fall back to the others on connection failures
+    pub central_servers: Vec<CentralServer>,
+    pub current_central: AtomicUsize,
+    /// sent in the boot request, so that the central server can give this game server its own settings
+    pub server_id: Option<String>,
     pub token_issuer: SyncMutex<TokenIssuer>,
     pub central_conf: SyncMutex<GameServerBootData>,
+    /// when the configuration was last re-fetched because of a token that failed to validate
+    last_token_refresh: Mutex<Option<Instant>>,
 
     // for performance reasons /shrug
     pub maintenance: AtomicBool,
+    /// maintenance mode enabled at runtime by an admin, independently of the central server
+    pub local_maintenance: AtomicBool,
     pub whitelist: AtomicBool,
     pub webhook_present: AtomicBool,
+    pub join_leave_webhook: AtomicBool,
+    /// messages waiting to be sent by the webhook worker, which is started on the first queued message
+    webhook_queue: OnceLock<mpsc::Sender<WebhookMessage>>,
+    pub max_packet_size: AtomicU32,
+    /// maximum packet size set with `GLOBED_GS_MAX_PACKET_SIZE`, overrides the one sent by the central server if nonzero
+    pub local_max_packet_size: AtomicU32,
+    pub voice_proximity_radius: AtomicU32,
+    pub voice_silence_threshold: AtomicU32,
+    /// in kbit/s, 0 if unlimited
+    pub voice_max_bitrate: AtomicU32,
+    /// 0 if unlimited
+    pub voice_max_frames: AtomicU32,
+    /// in KiB/s per player, 0 if unlimited
+    pub voice_throughput_limit: AtomicU32,
+    /// features that the central server can turn off at runtime, for every game server or just this one
+    pub voice_enabled: AtomicBool,
+    pub chat_enabled: AtomicBool,
+    pub profiles_enabled: AtomicBool,
+    /// incremented every time the admin key changes, only modified while `central_conf` is locked
+    pub admin_key_generation: AtomicU32,
+    pub tps: AtomicU32,
+    pub keepalive_interval: AtomicU32,
+    /// whether the last request to the central server got a response, from any of the configured servers
+    pub central_reachable: AtomicBool,
+}
+
+/// how many times a request is attempted before giving up, when the central server is temporarily unavailable
+const MAX_REQUEST_ATTEMPTS: u32 = 3;
+/// delay before the first retry, doubles with every next attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// lowest accepted value for `max_packet_size`, anything below would make it impossible to even log in
+const MIN_MAX_PACKET_SIZE: u32 = 2048;
+
+/// longer server names and MOTDs are cut off, so that the server info always fits in a single datagram
+const MAX_SERVER_NAME_LENGTH: usize = 64;
+const MAX_MOTD_LENGTH: usize = 512;
+
+/// a token failing to validate only triggers a refresh of the configuration this often, so that invalid tokens can't be used to spam the central server
+const MIN_TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// range of accepted values for `tps`, anything outside of it is ignored and the previous value is kept
+pub const MIN_TPS: u32 = 1;
+pub const MAX_TPS: u32 = 240;
+
+/// range of accepted values for `keepalive_interval` (in seconds), same handling as `tps`
+pub const MIN_KEEPALIVE_INTERVAL: u32 = 5;
+pub const MAX_KEEPALIVE_INTERVAL: u32 = 300;
+/// clients that don't send anything for this many keepalive intervals get disconnected
+pub const KEEPALIVE_TIMEOUT_INTERVALS: u32 = 3;
+
+/// how long a client that was told to use this keepalive interval can go without sending anything before getting disconnected
+pub const fn idle_timeout_for(keepalive_interval: u32) -> Duration {
+    Duration::from_secs(keepalive_interval as u64 * KEEPALIVE_TIMEOUT_INTERVALS as u64)
 }
 
 impl CentralBridge {
-    pub fn new(central_url: &str, central_pw: &str) -> Self {
-        let http_client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(5))
-            .user_agent(format!("globed-game-server/{}", env!("CARGO_PKG_VERSION")))
-            .build()
-            .unwrap();
+    pub fn new(central_servers: Vec<CentralServer>, server_id: Option<String>, tls: CentralTlsConfig) -> Self {
+        let http_client = Self::client_builder().build().unwrap();
+
+        let mut central_client = Self::client_builder()
+            .tls_built_in_root_certs(!tls.pin_certificates)
+            .danger_accept_invalid_certs(tls.insecure);
+
+        for cert in tls.root_certificates {
+            central_client = central_client.add_root_certificate(cert);
+        }
 
         Self {
             http_client,
+            central_client: central_client.build().unwrap(),
             token_issuer: SyncMutex::new(TokenIssuer::new("", Duration::from_secs(0))),
-            central_url: central_url.to_owned(),
-            central_pw: central_pw.to_owned(),
+            central_servers,
+            current_central: AtomicUsize::new(0),
+            server_id,
             central_conf: SyncMutex::new(GameServerBootData::default()),
+            last_token_refresh: Mutex::new(None),
             maintenance: AtomicBool::new(false),
+            local_maintenance: AtomicBool::new(false),
             whitelist: AtomicBool::new(false),
             webhook_present: AtomicBool::new(false),
+            join_leave_webhook: AtomicBool::new(false),
+            webhook_queue: OnceLock::new(),
+            max_packet_size: AtomicU32::new(DEFAULT_MAX_PACKET_SIZE),
+            local_max_packet_size: AtomicU32::new(0),
+            voice_proximity_radius: AtomicU32::new(0),
+            voice_silence_threshold: AtomicU32::new(0),
+            voice_max_bitrate: AtomicU32::new(0),
+            voice_max_frames: AtomicU32::new(GameServerBootData::default().voice_max_frames),
+            voice_throughput_limit: AtomicU32::new(GameServerBootData::default().voice_throughput_limit),
+            voice_enabled: AtomicBool::new(true),
+            chat_enabled: AtomicBool::new(true),
+            profiles_enabled: AtomicBool::new(true),
+            admin_key_generation: AtomicU32::new(0),
+            tps: AtomicU32::new(GameServerBootData::default().tps),
+            keepalive_interval: AtomicU32::new(GameServerBootData::default().keepalive_interval),
+            central_reachable: AtomicBool::new(true),
         }
     }
 
+    fn client_builder() -> reqwest::ClientBuilder {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .user_agent(format!("globed-game-server/{}", env!("CARGO_PKG_VERSION")))
+    }
+
     pub fn is_maintenance(&self) -> bool {
         self.maintenance.load(Ordering::Relaxed)
     }
 
+    /// Enables or disables maintenance mode on this game server. If the central server has maintenance enabled,
+    /// it cannot be disabled here. Returns whether maintenance mode is enabled after the change.
+    pub fn set_local_maintenance(&self, enabled: bool) -> bool {
+        self.local_maintenance.store(enabled, Ordering::Relaxed);
+
+        let maintenance = enabled || self.central_conf.lock().maintenance;
+        self.maintenance.store(maintenance, Ordering::Relaxed);
+
+        maintenance
+    }
+
     pub fn is_whitelist(&self) -> bool {
         self.whitelist.load(Ordering::Relaxed)
     }
@@ -101,13 +253,139 @@ impl CentralBridge {
         self.webhook_present.load(Ordering::Relaxed)
     }
 
+    /// whether player logins and disconnects should be sent to the webhook
+    pub fn has_join_leave_webhook(&self) -> bool {
+        self.join_leave_webhook.load(Ordering::Relaxed)
+    }
+
+    /// maximum size of a packet that a client is allowed to send, and that the server is willing to send to a client
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size.load(Ordering::Relaxed) as usize
+    }
+
+    /// Sets a maximum packet size that takes priority over the one sent by the central server.
+    /// Returns the size that is actually used, as values below the minimum are raised to it.
+    pub fn set_local_max_packet_size(&self, size: u32) -> u32 {
+        let size = size.max(MIN_MAX_PACKET_SIZE);
+        self.local_max_packet_size.store(size, Ordering::Relaxed);
+        self.max_packet_size.store(size, Ordering::Relaxed);
+
+        size
+    }
+
+    /// how many times per second clients send their player data while in a level
+    pub fn tps(&self) -> u32 {
+        self.tps.load(Ordering::Relaxed)
+    }
+
+    /// how often (in seconds) clients are told to send a keepalive if they have nothing else to send
+    pub fn keepalive_interval(&self) -> u32 {
+        self.keepalive_interval.load(Ordering::Relaxed)
+    }
+
+    /// how long a client that logs in now can go without sending anything before getting disconnected
+    pub fn idle_timeout(&self) -> Duration {
+        idle_timeout_for(self.keepalive_interval())
+    }
+
+    pub fn is_central_reachable(&self) -> bool {
+        self.central_reachable.load(Ordering::Relaxed)
+    }
+
+    /// Validates a session token. If the signature does not match, which could mean the secret key was changed on the central server,
+    /// calls `refresh` to fetch the new configuration, and validates the token again. Expired tokens are rejected right away.
+    /// `refresh` is called at most once every `MIN_TOKEN_REFRESH_INTERVAL`, and should return whether the refresh succeeded.
+    pub async fn validate_token<F, Fut>(
+        &self,
+        account_id: i32,
+        user_id: i32,
+        token: &str,
+        refresh: F,
+    ) -> std::result::Result<String, TokenValidationFailure>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let result = self.token_issuer.lock().validate(account_id, user_id, token);
+
+        match result {
+            Err(TokenValidationFailure::InvalidSignature) => {}
+            _ => return result,
+        }
+
+        {
+            // held during the refresh, so that logins failing at the same time wait for it instead of refreshing again
+            let mut last_refresh = self.last_token_refresh.lock().await;
+
+            if last_refresh.is_none_or(|last| last.elapsed() >= MIN_TOKEN_REFRESH_INTERVAL) {
+                debug!("token validation failed, refreshing the configuration in case the secret key has changed");
+
+                *last_refresh = Some(Instant::now());
+                if !refresh().await {
+                    return result;
+                }
+            }
+        }
+
+        self.token_issuer.lock().validate(account_id, user_id, token)
+    }
+
+    /// Makes a request to the central server. If it cannot be reached, the other central servers are tried in order,
+    /// and the first one that responds is used for all the next requests.
+    async fn central_request(&self, method: Method, path: &str, body: Option<Vec<u8>>) -> Result<reqwest::Response> {
+        let count = self.central_servers.len();
+        if count == 0 {
+            return Err(CentralBridgeError::Other("no central server is configured".to_owned()));
+        }
+
+        let start = self.current_central.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for offset in 0..count {
+            let idx = (start + offset) % count;
+            let server = &self.central_servers[idx];
+
+            let mut request = self
+                .central_client
+                .request(method.clone(), format!("{}{path}", server.url))
+                .header("Authorization", server.password.clone());
+
+            if let Some(body) = &body {
+                request = request.body(body.clone());
+            }
+
+            match request.send().await {
+                Ok(response) => {
+                    if idx != start {
+                        warn!(
+                            "central server {} is unreachable, switched to {}",
+                            self.central_servers[start].url, server.url
+                        );
+                        self.current_central.store(idx, Ordering::Relaxed);
+                    }
+
+                    self.central_reachable.store(true, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                Err(err) if err.is_connect() || err.is_timeout() => {
+                    debug!("failed to reach central server {}: {err}", server.url);
+                    last_error = Some(err);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        self.central_reachable.store(false, Ordering::Relaxed);
+        Err(last_error.unwrap().into())
+    }
+
     pub async fn request_boot_data(&self) -> Result<GameServerBootData> {
-        let response = self
-            .http_client
-            .post(format!("{}gs/boot", self.central_url))
-            .header("Authorization", self.central_pw.clone())
-            .send()
-            .await?;
+        let path = match &self.server_id {
+            Some(id) => format!("gs/boot?id={id}"),
+            None => "gs/boot".to_owned(),
+        };
+
+        let response = self.central_request(Method::POST, &path, None).await?;
 
         let status = response.status();
         if !status.is_success() {
@@ -129,17 +407,24 @@ impl CentralBridge {
             return Err(CentralBridgeError::InvalidMagic(txt));
         }
 
-        let boot_data = reader.read_value::<GameServerBootData>()?;
-
-        if boot_data.protocol != PROTOCOL_VERSION {
-            return Err(CentralBridgeError::ProtocolMismatch(boot_data.protocol));
+        // the protocol comes first, check it before anything else since the rest could be encoded differently
+        let protocol_pos = reader.get_rpos();
+        let protocol = reader.read_value::<u16>()?;
+        if protocol != PROTOCOL_VERSION {
+            return Err(CentralBridgeError::ProtocolMismatch(protocol));
         }
 
+        reader.set_rpos(protocol_pos);
+        let boot_data = reader.read_value::<GameServerBootData>()?;
+
         Ok(boot_data)
     }
 
-    pub async fn refresh_boot_data(&self) -> Result<()> {
+    /// Fetches the configuration from the central server and applies it. On failure, the current configuration is kept.
+    /// Returns the names of the fields that changed.
+    pub async fn refresh_boot_data(&self) -> Result<Vec<&'static str>> {
         let data = self.request_boot_data().await?;
+        let changed = self.central_conf.lock().changed_fields(&data);
 
         // update various values
         self.token_issuer.lock().set_expiration_period(Duration::from_secs(data.token_expiry));
@@ -147,31 +432,78 @@ impl CentralBridge {
         // set the data
         self.set_boot_data(data);
 
-        Ok(())
+        Ok(changed)
     }
 
     #[inline]
-    pub fn set_boot_data(&self, data: GameServerBootData) {
-        self.maintenance.store(data.maintenance, Ordering::Relaxed);
+    pub fn set_boot_data(&self, mut data: GameServerBootData) {
+        truncate_string(&mut data.server_name, MAX_SERVER_NAME_LENGTH);
+        truncate_string(&mut data.motd, MAX_MOTD_LENGTH);
+
+        if !(MIN_TPS..=MAX_TPS).contains(&data.tps) {
+            let previous = self.tps();
+            warn!(
+                "ignoring invalid tps value sent by the central server: {} (must be between {MIN_TPS} and {MAX_TPS}), keeping {previous}",
+                data.tps
+            );
+            data.tps = previous;
+        }
+
+        if !(MIN_KEEPALIVE_INTERVAL..=MAX_KEEPALIVE_INTERVAL).contains(&data.keepalive_interval) {
+            let previous = self.keepalive_interval();
+            warn!(
+                "ignoring invalid keepalive interval sent by the central server: {} (must be between {MIN_KEEPALIVE_INTERVAL} and {MAX_KEEPALIVE_INTERVAL}), keeping {previous}",
+                data.keepalive_interval
+            );
+            data.keepalive_interval = previous;
+        }
+
+        if data.voice_max_bitrate != 0 && data.voice_min_bitrate > data.voice_max_bitrate {
+            warn!(
+                "voice_min_bitrate ({}) is higher than voice_max_bitrate ({}), lowering it to the maximum",
+                data.voice_min_bitrate, data.voice_max_bitrate
+            );
+            data.voice_min_bitrate = data.voice_max_bitrate;
+        }
+
+        self.tps.store(data.tps, Ordering::Relaxed);
+        self.keepalive_interval.store(data.keepalive_interval, Ordering::Relaxed);
+        self.maintenance
+            .store(data.maintenance || self.local_maintenance.load(Ordering::Relaxed), Ordering::Relaxed);
         self.whitelist.store(data.whitelist, Ordering::Relaxed);
         self.webhook_present.store(!data.admin_webhook_url.is_empty(), Ordering::Relaxed);
+        self.join_leave_webhook
+            .store(data.join_leave_webhook && !data.admin_webhook_url.is_empty(), Ordering::Relaxed);
+        let local_max_packet_size = self.local_max_packet_size.load(Ordering::Relaxed);
+        if local_max_packet_size == 0 {
+            self.max_packet_size
+                .store(data.max_packet_size.max(MIN_MAX_PACKET_SIZE), Ordering::Relaxed);
+        }
+        self.voice_proximity_radius.store(data.voice_proximity_radius, Ordering::Relaxed);
+        self.voice_silence_threshold.store(data.voice_silence_threshold, Ordering::Relaxed);
+        self.voice_max_bitrate.store(data.voice_max_bitrate, Ordering::Relaxed);
+        self.voice_max_frames.store(data.voice_max_frames, Ordering::Relaxed);
+        self.voice_throughput_limit.store(data.voice_throughput_limit, Ordering::Relaxed);
+        self.voice_enabled.store(data.voice_enabled, Ordering::Relaxed);
+        self.chat_enabled.store(data.chat_enabled, Ordering::Relaxed);
+        self.profiles_enabled.store(data.profiles_enabled, Ordering::Relaxed);
 
         let mut issuer = self.token_issuer.lock();
 
         issuer.set_expiration_period(Duration::from_secs(data.token_expiry));
         issuer.set_secret_key(&data.secret_key2);
 
-        *self.central_conf.lock() = data;
+        let mut conf = self.central_conf.lock();
+        if conf.admin_key != data.admin_key {
+            self.admin_key_generation.fetch_add(1, Ordering::Relaxed);
+        }
+
+        *conf = data;
     }
 
     // other web requests
     pub async fn get_user_data(&self, player: &str) -> Result<UserEntry> {
-        let response = self
-            .http_client
-            .get(format!("{}gs/user/{}", self.central_url, player))
-            .header("Authorization", self.central_pw.clone())
-            .send()
-            .await?;
+        let response = self.central_request(Method::GET, &format!("gs/user/{player}"), None).await?;
 
         let status = response.status();
         if !status.is_success() {
@@ -187,6 +519,25 @@ impl CentralBridge {
         Ok(reader.read_value::<UserEntry>()?)
     }
 
+    /// Same as `get_user_data`, but retries with exponential backoff if the central server is temporarily unavailable.
+    pub async fn get_user_data_with_retry(&self, player: &str) -> Result<UserEntry> {
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+
+        loop {
+            match self.get_user_data(player).await {
+                Err(err) if err.is_transient() && attempt < MAX_REQUEST_ATTEMPTS => {
+                    debug!("failed to fetch user data (attempt {attempt}/{MAX_REQUEST_ATTEMPTS}), retrying: {err}");
+                    tokio::time::sleep(delay).await;
+
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
     pub async fn update_user_data(&self, user: &UserEntry) -> Result<()> {
         let mut buffer = ByteBuffer::with_capacity(user.encoded_size() + size_of_types!(u32));
 
@@ -195,13 +546,7 @@ impl CentralBridge {
 
         let body = buffer.into_vec();
 
-        let response = self
-            .http_client
-            .post(format!("{}gs/user/update", self.central_url))
-            .header("Authorization", self.central_pw.clone())
-            .body(body)
-            .send()
-            .await?;
+        let response = self.central_request(Method::POST, "gs/user/update", Some(body)).await?;
 
         let status = response.status();
         if !status.is_success() {
@@ -219,6 +564,33 @@ impl CentralBridge {
         self.send_webhook_messages(&messages).await
     }
 
+    /// Queues the message to be sent in the background, so that a slow webhook never holds up the caller.
+    /// If the webhook can't keep up and too many messages are waiting, the message is dropped. Errors are only logged.
+    pub fn queue_webhook_message(&'static self, message: WebhookMessage) {
+        let queue = self.webhook_queue.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(WEBHOOK_QUEUE_CAPACITY);
+            tokio::spawn(self.run_webhook_worker(receiver));
+            sender
+        });
+
+        if let Err(TrySendError::Full(_)) = queue.try_send(message) {
+            debug!("dropping a webhook message, too many are waiting to be sent");
+        }
+    }
+
+    /// Sends the queued messages one request at a time, putting the ones that piled up in the meantime into a single request.
+    async fn run_webhook_worker(&self, mut receiver: mpsc::Receiver<WebhookMessage>) {
+        let mut messages = Vec::with_capacity(WEBHOOK_BATCH_SIZE);
+
+        while receiver.recv_many(&mut messages, WEBHOOK_BATCH_SIZE).await != 0 {
+            if let Err(err) = self.send_webhook_messages(&messages).await {
+                warn!("webhook error: {err}");
+            }
+
+            messages.clear();
+        }
+    }
+
     // not really bridge but it was making web requests which is sorta related i guess
     pub async fn send_webhook_messages(&self, messages: &[WebhookMessage]) -> Result<()> {
         let url = self.central_conf.lock().admin_webhook_url.clone();
@@ -255,3 +627,15 @@ impl CentralBridge {
         Ok(())
     }
 }
+
+/// Cuts off the string after `max_len` bytes, without splitting a character in half.
+fn truncate_string(string: &mut String, max_len: usize) {
+    if string.len() > max_len {
+        let mut len = max_len;
+        while !string.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        string.truncate(len);
+    }
+}
@@ -3,6 +3,7 @@ use std::{fmt::Display, time::SystemTimeError};
 use crate::data::{types::ColorParseError, DecodeError};
 use globed_shared::reqwest;
 
+#[derive(Debug)]
 pub enum PacketHandlingError {
     Other(String),                         // unknown generic error
     WrongCryptoBoxState,                   // cryptobox was either Some or None when should've been the other one
@@ -25,12 +26,96 @@ pub enum PacketHandlingError {
     DangerousAllocation(usize),            // attempted to allocate a huge chunk of memory with alloca
     DebugOnlyPacket,                       // packet can only be handled in debug mode
     PacketTooLong(usize),                  // packet is too long
+    OversizedPacket(u16, usize),           // packet is larger than any packet with its ID can be
+    CorruptedPacket,                       // packet checksum did not match
+    SendTooLong(&'static str, usize),      // tried to send a packet above the maximum packet size
     UnableToSendUdp,                       // only tcp packets can be sent at the moment
     InvalidStreamMarker,                   // client did not send a control byte indicating whether this is an initial login or a recovery
+    MalformedVoiceData,                    // voice packet with missing or empty opus frames, or ones larger than opus can produce
+    TooManyVoiceFrames(usize),             // voice packet with more opus frames than the server allows
+    DecompressionError,                    // failed to decompress data, or compression was not negotiated
+    InvalidLevelStats,                     // LevelStatsPacket with a result that can't be achieved
 }
 
 pub type Result<T> = core::result::Result<T, PacketHandlingError>;
 
+/// Broad category of a `PacketHandlingError`, for counting errors by what went wrong rather than by every single variant.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ErrorClass {
+    /// the client sent something that doesn't follow the protocol
+    Malformed = 0,
+    /// encrypting or decrypting failed, or the crypto box was in the wrong state
+    Crypto = 1,
+    Ratelimited = 2,
+    /// packet with an unknown ID, or that can't be handled in this build
+    Unhandled = 3,
+    /// the connection to the client failed
+    Network = 4,
+    /// the central server request failed
+    Central = 5,
+    /// bug or misconfiguration on our side
+    Internal = 6,
+}
+
+impl ErrorClass {
+    pub const ALL: [Self; 7] = [
+        Self::Malformed,
+        Self::Crypto,
+        Self::Ratelimited,
+        Self::Unhandled,
+        Self::Network,
+        Self::Central,
+        Self::Internal,
+    ];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Malformed => "malformed",
+            Self::Crypto => "crypto",
+            Self::Ratelimited => "ratelimited",
+            Self::Unhandled => "unhandled",
+            Self::Network => "network",
+            Self::Central => "central",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl PacketHandlingError {
+    pub const fn class(&self) -> ErrorClass {
+        match self {
+            Self::MalformedMessage
+            | Self::MalformedLoginAttempt
+            | Self::MalformedCiphertext
+            | Self::MalformedPacketStructure(_)
+            | Self::MalformedVoiceData
+            | Self::TooManyVoiceFrames(_)
+            | Self::InvalidStreamMarker
+            | Self::PacketTooLong(_)
+            | Self::OversizedPacket(..)
+            | Self::CorruptedPacket
+            | Self::UnexpectedPlayerData
+            | Self::DecompressionError
+            | Self::InvalidLevelStats => ErrorClass::Malformed,
+            Self::WrongCryptoBoxState | Self::EncryptionError | Self::DecryptionError => ErrorClass::Crypto,
+            Self::Ratelimited => ErrorClass::Ratelimited,
+            Self::NoHandler(_) | Self::DebugOnlyPacket => ErrorClass::Unhandled,
+            Self::IOError(_) | Self::SocketSendFailed(_) | Self::SocketWouldBlock | Self::UnableToSendUdp => ErrorClass::Network,
+            Self::WebRequestError(_) | Self::UnexpectedCentralResponse => ErrorClass::Central,
+            Self::Other(_) | Self::SystemTimeError(_) | Self::ColorParseFailed(_) | Self::DangerousAllocation(_) | Self::SendTooLong(..) => {
+                ErrorClass::Internal
+            }
+        }
+    }
+
+    /// Whether the connection can't continue after this error. A packet that was too long was never read from the stream,
+    /// so everything after it would be read from the middle of it.
+    pub const fn is_fatal(&self) -> bool {
+        matches!(self, Self::PacketTooLong(_))
+    }
+}
+
 impl From<globed_shared::anyhow::Error> for PacketHandlingError {
     fn from(value: globed_shared::anyhow::Error) -> Self {
         Self::Other(value.to_string())
@@ -93,8 +178,28 @@ impl Display for PacketHandlingError {
             )),
             Self::DebugOnlyPacket => f.write_str("this packet can only be handled in debug mode"),
             Self::PacketTooLong(size) => f.write_fmt(format_args!("received packet is way too long - {size} bytes")),
+            Self::OversizedPacket(id, size) => f.write_fmt(format_args!("received packet with ID {id} is too long for its type - {size} bytes")),
+            Self::SendTooLong(name, size) => f.write_fmt(format_args!(
+                "refusing to send {name}, it is above the maximum packet size - {size} bytes"
+            )),
             Self::UnableToSendUdp => f.write_str("tried to send a udp packet on a thread that was not claimed by a udp connection"),
             Self::InvalidStreamMarker => f.write_str("invalid or missing stream marker at the start of the tcp stream"),
+            Self::MalformedVoiceData => f.write_str("received voice packet with malformed opus frames"),
+            Self::TooManyVoiceFrames(frames) => f.write_fmt(format_args!("received voice packet with too many opus frames - {frames}")),
+            Self::DecompressionError => f.write_str("Decompression failed"),
+            Self::CorruptedPacket => f.write_str("received packet with an invalid checksum"),
+            Self::InvalidLevelStats => f.write_str("received level stats with an impossible result"),
+        }
+    }
+}
+
+impl std::error::Error for PacketHandlingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IOError(e) | Self::SocketSendFailed(e) => Some(e),
+            Self::WebRequestError(e) => Some(e),
+            Self::SystemTimeError(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,37 @@
+// Every packet sent over TCP is prefixed with its length as a big endian u32,
+// since a stream socket can split a single packet across multiple reads, or coalesce several packets into one.
+
+use crate::tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Size of the length prefix in front of every TCP packet.
+pub const FRAME_HEADER_SIZE: usize = 4;
+
+/// Reads the length prefix of the next packet.
+pub async fn read_frame_header<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<usize> {
+    let mut length_buf = [0u8; FRAME_HEADER_SIZE];
+    reader.read_exact(&mut length_buf).await?;
+
+    Ok(u32::from_be_bytes(length_buf) as usize)
+}
+
+/// Reads exactly `bytes` bytes of packet data, waiting for more data if the packet arrives in multiple parts.
+/// If the data fits in `inline_buf`, it is used, otherwise `heap_buf` is resized to fit the packet.
+///
+/// Fails with `UnexpectedEof` if the connection is closed before the entire packet is received.
+pub async fn read_frame_body<'a, R: AsyncRead + Unpin>(
+    reader: &mut R,
+    bytes: usize,
+    inline_buf: &'a mut [u8],
+    heap_buf: &'a mut Vec<u8>,
+) -> std::io::Result<&'a mut [u8]> {
+    let data = if bytes <= inline_buf.len() {
+        &mut inline_buf[..bytes]
+    } else {
+        heap_buf.resize(bytes, 0);
+        &mut heap_buf[..]
+    };
+
+    reader.read_exact(data).await?;
+
+    Ok(data)
+}
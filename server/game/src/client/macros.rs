@@ -33,8 +33,8 @@ macro_rules! gs_handler_sync {
 
 /// call disconnect and return from the function
 macro_rules! gs_disconnect {
-    ($self:ident, $msg:expr) => {
-        $self.kick($msg).await?;
+    ($self:ident, $reason:expr, $msg:expr) => {
+        $self.kick($reason, $msg).await?;
         return Ok(());
     };
 }
@@ -43,7 +43,11 @@ macro_rules! gs_disconnect {
 /// send a `ServerNoticePacket` to the client with the given message
 macro_rules! gs_notice {
     ($self:expr, $msg:expr) => {
-        $self.send_packet_fast_dynamic(&ServerNoticePacket { message: $msg }).await?;
+        $self
+            .send_packet_dynamic(&ServerNoticePacket {
+                message: FastString::new($msg),
+            })
+            .await?;
     };
 }
 
@@ -53,7 +57,7 @@ macro_rules! gs_needauth {
         let account_id = $self.account_id.load(Ordering::Relaxed);
 
         if account_id == 0 {
-            gs_disconnect!($self, "unauthorized, please try connecting again");
+            gs_disconnect!($self, DisconnectReason::AuthFailed, "unauthorized, please try connecting again");
         }
 
         account_id
@@ -188,7 +192,7 @@ macro_rules! gs_inline_encode {
 
                 match res {
                     // if we cant send without blocking, accept our defeat and clone the data to a vec
-                    Err(PacketHandlingError::SocketWouldBlock) => Ok(Some(data.to_vec())),
+                    Err(PacketHandlingError::SocketWouldBlock) => Ok(Some($crate::util::buffer_pool::take_copy(data))),
                     // if another error occured, propagate it up
                     Err(e) => Err(e),
                     // if all good, do nothing
@@ -197,7 +201,7 @@ macro_rules! gs_inline_encode {
                             Ok(None)
                         } else {
                             // send leftover data
-                            Ok(Some(data[written..data.len()].to_vec()))
+                            Ok(Some($crate::util::buffer_pool::take_copy(&data[written..data.len()])))
                         }
                     },
                 }
@@ -205,11 +209,14 @@ macro_rules! gs_inline_encode {
         };
 
         if let Some(data) = retval? {
-            if $tcp {
-                $self.send_buffer_tcp(&data).await?;
+            let result = if $tcp {
+                $self.send_buffer_tcp(&data).await
             } else {
-                $self.send_buffer_udp(&data).await?;
-            }
+                $self.send_buffer_udp(&data).await
+            };
+
+            $crate::util::buffer_pool::put(data);
+            result?;
         }
     }
 }
@@ -1,11 +1,12 @@
 pub mod error;
+pub mod framing;
 pub mod macros;
 pub mod socket;
 pub mod state;
 pub mod thread;
 pub mod unauthorized;
 
-pub use error::{PacketHandlingError, Result};
+pub use error::{ErrorClass, PacketHandlingError, Result};
 pub use macros::*;
 pub use socket::ClientSocket;
 pub use state::{AtomicClientThreadState, ClientThreadState};
@@ -1,139 +1,221 @@
 use std::{
-    net::{SocketAddr, SocketAddrV4},
-    sync::OnceLock,
-    time::Duration,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
 };
 
-use crate::tokio::{
-    self,
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::TcpStream,
-};
+use crate::tokio::{self, io::AsyncWriteExt, net::TcpStream};
 
 #[allow(unused_imports)]
 use globed_shared::{
     crypto_box::{
         aead::{AeadCore, AeadInPlace, OsRng},
-        ChaChaBox,
+        ChaChaBox, SecretKey,
     },
     trace,
 };
 
 use super::{
     error::{PacketHandlingError, Result},
+    framing,
     macros::*,
 };
-use crate::{data::*, server::GameServer};
+use crate::{data::*, metrics::ConnectionStats, server::GameServer, util::buffer_pool};
 
 pub struct ClientSocket {
     pub socket: TcpStream,
 
-    pub tcp_peer: SocketAddrV4,
-    pub udp_peer: Option<SocketAddrV4>,
+    pub tcp_peer: SocketAddr,
+    pub udp_peer: Option<SocketAddr>,
     crypto_box: OnceLock<ChaChaBox>,
+    /// optional features agreed on in the handshake
+    pub capabilities: HandshakeCapabilities,
     game_server: &'static GameServer,
+    pub stats: Arc<ConnectionStats>,
+    /// total time spent waiting for sends that couldn't be done right away, in nanoseconds
+    send_wait: AtomicU64,
 }
 
 // do not touch those, encryption related
 const NONCE_SIZE: usize = 24;
 const MAC_SIZE: usize = 16;
 
-const MAX_PACKET_SIZE: usize = 65536;
+/// bytes an encrypted packet takes on top of its data and header
+pub const ENCRYPTION_OVERHEAD: usize = NONCE_SIZE + MAC_SIZE;
+
 pub const INLINE_BUFFER_SIZE: usize = 164;
 
+/// packets larger than this get compressed, if the client supports it
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Compresses the data of a packet with zstd, returns `None` if that would not make it any smaller.
+pub fn compress_payload(data: &[u8]) -> Option<Vec<u8>> {
+    zstd::bulk::compress(data, COMPRESSION_LEVEL)
+        .ok()
+        .filter(|compressed| compressed.len() < data.len())
+}
+
+/// Inflates the data of a compressed packet, failing if it would be larger than `limit` bytes.
+pub fn decompress_payload(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    zstd::bulk::decompress(data, limit).map_err(|_| PacketHandlingError::DecompressionError)
+}
+
+/// Appends an entire packet to `out`: the length prefix if sent over tcp, the header, and the data, encrypted if `cbox` is given.
+/// `flags` are added to the header, besides the encryption flag which is set automatically.
+pub fn encode_packet_into(out: &mut Vec<u8>, packet_id: u16, flags: u8, data: &[u8], cbox: Option<&ChaChaBox>, tcp: bool) -> Result<()> {
+    let prefix_start = out.len();
+    if tcp {
+        // reserve space for packet length
+        out.extend_from_slice(&[0u8; size_of_types!(u32)]);
+    }
+
+    let header_start = out.len();
+
+    let mut header = [0u8; PacketHeader::SIZE];
+    FastByteBuffer::new(&mut header).write_value(&PacketHeader {
+        packet_id,
+        flags: if cbox.is_some() { flags | PACKET_FLAG_ENCRYPTED } else { flags },
+    });
+    out.extend_from_slice(&header);
+
+    if let Some(cbox) = cbox {
+        let nonce = ChaChaBox::generate_nonce(&mut OsRng);
+        out.extend_from_slice(nonce.as_slice());
+
+        let mac_start = out.len();
+        out.extend_from_slice(&[0u8; MAC_SIZE]);
+
+        let data_start = out.len();
+        out.extend_from_slice(data);
+
+        let tag = cbox
+            .encrypt_in_place_detached(&nonce, b"", &mut out[data_start..])
+            .map_err(|_| PacketHandlingError::EncryptionError)?;
+
+        out[mac_start..data_start].copy_from_slice(&tag);
+    } else {
+        out.extend_from_slice(data);
+    }
+
+    if tcp {
+        let packet_len = (out.len() - header_start) as u32;
+        out[prefix_start..header_start].copy_from_slice(&packet_len.to_be_bytes());
+    }
+
+    Ok(())
+}
+
+/// Decrypts an encrypted packet in place (including its header), returning a reader over the decrypted data.
+pub fn decrypt_in_place<'a>(cbox: &ChaChaBox, message: &'a mut [u8]) -> Result<ByteReader<'a>> {
+    if message.len() < PacketHeader::SIZE + NONCE_SIZE + MAC_SIZE {
+        return Err(PacketHandlingError::MalformedCiphertext);
+    }
+
+    let nonce_start = PacketHeader::SIZE;
+    let mac_start = nonce_start + NONCE_SIZE;
+    let ciphertext_start = mac_start + MAC_SIZE;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.clone_from_slice(&message[nonce_start..mac_start]);
+    let nonce = nonce.into();
+
+    let mut mac = [0u8; MAC_SIZE];
+    mac.clone_from_slice(&message[mac_start..ciphertext_start]);
+    let mac = mac.into();
+
+    cbox.decrypt_in_place_detached(&nonce, b"", &mut message[ciphertext_start..], &mac)
+        .map_err(|_| PacketHandlingError::DecryptionError)?;
+
+    Ok(ByteReader::from_bytes(&message[ciphertext_start..]))
+}
+
 impl ClientSocket {
-    pub fn new(socket: TcpStream, tcp_peer: SocketAddrV4, game_server: &'static GameServer) -> Self {
+    pub fn new(socket: TcpStream, tcp_peer: SocketAddr, game_server: &'static GameServer) -> Self {
         Self {
             socket,
             tcp_peer,
             udp_peer: None,
             crypto_box: OnceLock::new(),
+            capabilities: HandshakeCapabilities::default(),
             game_server,
+            stats: Arc::new(ConnectionStats::default()),
+            send_wait: AtomicU64::new(0),
         }
     }
 
+    /// Returns the total time spent waiting for sends that couldn't be done without blocking,
+    /// so that the time a slow peer takes to receive data can be told apart from the time spent handling packets.
+    pub fn send_wait(&self) -> Duration {
+        Duration::from_nanos(self.send_wait.load(Ordering::Relaxed))
+    }
+
+    fn record_send_wait(&self, started: Instant) {
+        self.send_wait.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
     pub async fn shutdown(&mut self) -> std::io::Result<()> {
         self.socket.shutdown().await
     }
 
     pub async fn poll_for_tcp_data(&mut self) -> Result<usize> {
-        let mut length_buf = [0u8; 4];
-        self.socket.read_exact(&mut length_buf).await?;
-
-        Ok(u32::from_be_bytes(length_buf) as usize)
+        Ok(framing::read_frame_header(&mut self.socket).await?)
     }
 
     /// Receive `bytes` bytes from the TCP connection and invoke the given closure.
+    /// If `bytes` is above the configured maximum packet size, nothing is read and `PacketTooLong` is returned.
+    /// The stream cannot be recovered after that, so the caller is expected to terminate the connection.
     #[inline]
     pub async fn recv_and_handle<F>(&mut self, bytes: usize, f: F) -> Result<()>
     where
         F: async FnOnce(&mut [u8]) -> Result<()>,
     {
-        if bytes > MAX_PACKET_SIZE {
+        if bytes > self.game_server.bridge.max_packet_size() {
             return Err(PacketHandlingError::PacketTooLong(bytes));
         }
 
         let mut inline_buf = [0u8; INLINE_BUFFER_SIZE];
         let mut heap_buf = Vec::<u8>::new();
 
-        let use_inline = bytes <= INLINE_BUFFER_SIZE;
-
-        let data: &mut [u8] = if use_inline {
-            self.socket.read_exact(&mut inline_buf[..bytes]).await?;
-            &mut inline_buf[..bytes]
-        } else {
-            let sock = &mut self.socket;
-
-            let read_bytes = sock.take(bytes as u64).read_to_end(&mut heap_buf).await?;
-            &mut heap_buf[..read_bytes]
-        };
+        let data = framing::read_frame_body(&mut self.socket, bytes, &mut inline_buf, &mut heap_buf).await?;
+        self.game_server.state.metrics.add_received(framing::FRAME_HEADER_SIZE + bytes);
 
         f(data).await
     }
 
-    pub fn init_crypto_box(&self, key: &CryptoPublicKey) -> Result<()> {
+    pub fn has_crypto_box(&self) -> bool {
+        self.crypto_box.get().is_some()
+    }
+
+    pub fn init_crypto_box(&self, key: &CryptoPublicKey, secret_key: &SecretKey) -> Result<()> {
         if self.crypto_box.get().is_some() {
             return Err(PacketHandlingError::WrongCryptoBoxState);
         }
 
-        self.crypto_box.get_or_init(|| ChaChaBox::new(&key.0, &self.game_server.secret_key));
+        self.crypto_box.get_or_init(|| ChaChaBox::new(&key.0, secret_key));
 
         Ok(())
     }
 
-    pub fn set_udp_peer(&mut self, udp_peer: SocketAddrV4) {
+    pub fn set_udp_peer(&mut self, udp_peer: SocketAddr) {
         self.udp_peer.replace(udp_peer);
     }
 
     pub fn decrypt<'a>(&self, message: &'a mut [u8]) -> Result<ByteReader<'a>> {
-        if message.len() < PacketHeader::SIZE + NONCE_SIZE + MAC_SIZE {
-            return Err(PacketHandlingError::MalformedCiphertext);
-        }
-
-        let cbox = self.crypto_box.get();
+        let cbox = self.crypto_box.get().ok_or(PacketHandlingError::WrongCryptoBoxState)?;
+        decrypt_in_place(cbox, message)
+    }
 
-        if cbox.is_none() {
-            return Err(PacketHandlingError::WrongCryptoBoxState);
+    /// Inflates the rest of the data of a compressed packet, up to `limit` bytes. Fails if compression was not agreed on in the handshake.
+    pub fn decompress(&self, data: &ByteReader, limit: usize) -> Result<Vec<u8>> {
+        if !self.capabilities.compression() {
+            return Err(PacketHandlingError::DecompressionError);
         }
 
-        let cbox = cbox.unwrap();
-
-        let nonce_start = PacketHeader::SIZE;
-        let mac_start = nonce_start + NONCE_SIZE;
-        let ciphertext_start = mac_start + MAC_SIZE;
-
-        let mut nonce = [0u8; NONCE_SIZE];
-        nonce.clone_from_slice(&message[nonce_start..mac_start]);
-        let nonce = nonce.into();
-
-        let mut mac = [0u8; MAC_SIZE];
-        mac.clone_from_slice(&message[mac_start..ciphertext_start]);
-        let mac = mac.into();
-
-        cbox.decrypt_in_place_detached(&nonce, b"", &mut message[ciphertext_start..], &mac)
-            .map_err(|_| PacketHandlingError::DecryptionError)?;
-
-        Ok(ByteReader::from_bytes(&message[ciphertext_start..]))
+        decompress_payload(&data.as_bytes()[data.get_rpos()..], limit)
     }
 
     // packet encoding and sending functions
@@ -185,6 +267,13 @@ impl ClientSocket {
     where
         F: FnOnce(&mut FastByteBuffer),
     {
+        // voice is already compressed with opus, so it would only waste time
+        let compressible = P::PACKET_ID != VoiceBroadcastPacket::PACKET_ID && P::PACKET_ID != VoiceBroadcastBatchPacket::PACKET_ID;
+
+        if self.capabilities.compression() && compressible && packet_size > COMPRESSION_THRESHOLD {
+            return self.send_packet_compressed::<P, _>(packet_size, encode_fn).await;
+        }
+
         if cfg!(debug_assertions) && P::PACKET_ID != KeepaliveResponsePacket::PACKET_ID {
             self.print_packet::<P>(true, Some(if P::ENCRYPTED { "fast + encrypted" } else { "fast" }));
         }
@@ -217,6 +306,7 @@ impl ClientSocket {
 
                 // if the written size isn't equal to `packet_size`, we use buffer length instead
                 let raw_data_end = raw_data_start + buf.len();
+                self.check_outgoing_size::<P>(raw_data_end - header_start)?;
 
                 // this unwrap is safe, as an encrypted packet can only be sent downstream after the handshake is established.
                 let cbox = self.crypto_box.get().unwrap();
@@ -249,25 +339,28 @@ impl ClientSocket {
                 };
 
                 match res {
-                    Err(PacketHandlingError::SocketWouldBlock) => Ok(Some(send_data.to_vec())),
+                    Err(PacketHandlingError::SocketWouldBlock) => Ok(Some(buffer_pool::take_copy(send_data))),
                     Err(e) => Err(e),
                     Ok(written) => {
                         if written == raw_data_end {
                             Ok(None)
                         } else {
                             // send leftover data
-                            Ok(Some(data[written..raw_data_end].to_vec()))
+                            Ok(Some(buffer_pool::take_copy(&data[written..raw_data_end])))
                         }
                     }
                 }
             });
 
             if let Some(to_send) = to_send? {
-                if P::SHOULD_USE_TCP {
-                    self.send_buffer_tcp(&to_send).await?;
+                let result = if P::SHOULD_USE_TCP {
+                    self.send_buffer_tcp(&to_send).await
                 } else {
-                    self.send_buffer_udp(&to_send).await?;
-                }
+                    self.send_buffer_udp(&to_send).await
+                };
+
+                buffer_pool::put(to_send);
+                result?;
             }
         } else {
             let prefix_sz = if P::SHOULD_USE_TCP { size_of_types!(u32) } else { 0usize };
@@ -275,6 +368,7 @@ impl ClientSocket {
             gs_inline_encode!(self, prefix_sz + PacketHeader::SIZE + packet_size, buf, P::SHOULD_USE_TCP, {
                 buf.write_packet_header::<P>();
                 encode_fn(&mut buf);
+                self.check_outgoing_size::<P>(buf.len() - prefix_sz)?;
             });
         }
 
@@ -282,15 +376,95 @@ impl ClientSocket {
             self.socket.flush().await?;
         }
 
+        self.stats.packets_sent.fetch_add(1, Ordering::Relaxed);
+
         Ok(())
     }
 
+    /// slower version of `send_packet_alloca_with` for large packets, used once compression is enabled.
+    /// the packet is encoded on the heap and compressed, unless compressing does not make it any smaller.
+    async fn send_packet_compressed<P: Packet, F>(&mut self, packet_size: usize, encode_fn: F) -> Result<()>
+    where
+        F: FnOnce(&mut FastByteBuffer),
+    {
+        let mut raw = buffer_pool::take();
+        raw.resize(packet_size, 0);
+
+        let mut buf = FastByteBuffer::new(&mut raw);
+        encode_fn(&mut buf);
+        let len = buf.len();
+        raw.truncate(len);
+
+        let compressed = compress_payload(&raw);
+
+        if cfg!(debug_assertions) {
+            self.print_packet::<P>(true, Some(if compressed.is_some() { "compressed" } else { "uncompressed" }));
+        }
+
+        let (data, flags) = match &compressed {
+            Some(compressed) => (compressed.as_slice(), PACKET_FLAG_COMPRESSED),
+            None => (raw.as_slice(), 0),
+        };
+
+        let cbox = if P::ENCRYPTED {
+            Some(self.crypto_box.get().ok_or(PacketHandlingError::WrongCryptoBoxState)?)
+        } else {
+            None
+        };
+
+        let mut out = buffer_pool::take();
+        let result = encode_packet_into(&mut out, P::PACKET_ID, flags, data, cbox, P::SHOULD_USE_TCP).and_then(|()| {
+            let prefix_sz = if P::SHOULD_USE_TCP { size_of_types!(u32) } else { 0usize };
+            self.check_outgoing_size::<P>(out.len() - prefix_sz)
+        });
+        buffer_pool::put(raw);
+
+        let result = match result {
+            Ok(()) if P::SHOULD_USE_TCP => self.send_buffer_tcp(&out).await,
+            Ok(()) => self.send_buffer_udp(&out).await,
+            Err(e) => Err(e),
+        };
+
+        buffer_pool::put(out);
+        result?;
+
+        if P::SHOULD_USE_TCP {
+            self.socket.flush().await?;
+        }
+
+        self.stats.packets_sent.fetch_add(1, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// the client would drop a packet above the maximum packet size anyway, so sending one is always a bug on our side.
+    /// `size` excludes the length prefix of tcp packets, same as when receiving.
+    #[inline]
+    fn check_outgoing_size<P: PacketMetadata>(&self, size: usize) -> Result<()> {
+        if size > self.game_server.bridge.max_packet_size() {
+            Err(PacketHandlingError::SendTooLong(P::NAME, size))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn record_sent(&self, bytes: usize) {
+        self.game_server.state.metrics.add_sent(bytes);
+        self.stats.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
     /// sends a buffer to our peer via the tcp socket
     async fn send_buffer_tcp(&mut self, buffer: &[u8]) -> Result<()> {
+        let started = Instant::now();
         let result = tokio::time::timeout(Duration::from_secs(5), self.socket.write_all(buffer)).await;
+        self.record_send_wait(started);
 
         match result {
-            Ok(Ok(())) => Ok(()),
+            Ok(Ok(())) => {
+                self.record_sent(buffer.len());
+                Ok(())
+            }
             Ok(Err(err)) => Err(PacketHandlingError::SocketSendFailed(err)),
             Err(_) => {
                 // timed out
@@ -307,7 +481,10 @@ impl ClientSocket {
         let result = self.socket.try_write(buffer);
 
         match result {
-            Ok(x) => Ok(x),
+            Ok(x) => {
+                self.record_sent(x);
+                Ok(x)
+            }
             Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => Err(PacketHandlingError::SocketWouldBlock),
             Err(e) => Err(e.into()),
         }
@@ -315,29 +492,32 @@ impl ClientSocket {
 
     /// sends a buffer to our peer via the udp socket
     async fn send_buffer_udp(&self, buffer: &[u8]) -> Result<()> {
-        match self.udp_peer.as_ref() {
-            Some(udp_peer) => self
-                .game_server
-                .udp_socket
-                .send_to(buffer, udp_peer)
-                .await
-                .map(|_size| ())
-                .map_err(PacketHandlingError::SocketSendFailed),
+        let Some(udp_peer) = self.udp_peer.as_ref() else {
+            return Err(PacketHandlingError::UnableToSendUdp);
+        };
 
-            None => Err(PacketHandlingError::UnableToSendUdp),
-        }
+        let started = Instant::now();
+        let result = self.game_server.udp_socket.send_to(buffer, udp_peer).await;
+        self.record_send_wait(started);
+
+        result.map(|size| self.record_sent(size)).map_err(PacketHandlingError::SocketSendFailed)
     }
 
     /// non async version of `send_buffer_udp`
     fn send_buffer_udp_immediate(&self, buffer: &[u8]) -> Result<usize> {
         match self.udp_peer.as_ref() {
-            Some(udp_peer) => self.game_server.udp_socket.try_send_to(buffer, SocketAddr::V4(*udp_peer)).map_err(|e| {
-                if e.kind() == std::io::ErrorKind::WouldBlock {
-                    PacketHandlingError::SocketWouldBlock
-                } else {
-                    PacketHandlingError::SocketSendFailed(e)
-                }
-            }),
+            Some(udp_peer) => self
+                .game_server
+                .udp_socket
+                .try_send_to(buffer, *udp_peer)
+                .inspect(|size| self.record_sent(*size))
+                .map_err(|e| {
+                    if e.kind() == std::io::ErrorKind::WouldBlock {
+                        PacketHandlingError::SocketWouldBlock
+                    } else {
+                        PacketHandlingError::SocketSendFailed(e)
+                    }
+                }),
 
             None => Err(PacketHandlingError::UnableToSendUdp),
         }
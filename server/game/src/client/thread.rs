@@ -1,7 +1,7 @@
 use std::{
     collections::VecDeque,
     io::ErrorKind,
-    net::SocketAddrV4,
+    net::SocketAddr,
     sync::{
         atomic::{AtomicBool, AtomicI32, AtomicU16, AtomicU32, Ordering},
         Arc,
@@ -14,15 +14,16 @@ use crate::tokio::{
     sync::{Mutex, Notify},
 };
 use esp::ByteReader;
-use globed_shared::{logger::*, SyncMutex, UserEntry};
-use handlers::game::MAX_VOICE_PACKET_SIZE;
+use globed_shared::{logger::*, IntSet, SyncMutex, UserEntry};
 use tokio::time::Instant;
 
 use crate::{
+    bridge::idle_timeout_for,
     data::*,
     managers::ComputedRole,
+    metrics::{ConnectionPhaseTracker, ConnectionStats},
     server::GameServer,
-    util::{LockfreeMutCell, SimpleRateLimiter},
+    util::{buffer_pool, LockfreeMutCell, SimpleRateLimiter, ThroughputLimiter},
 };
 
 pub use super::*;
@@ -31,12 +32,29 @@ pub mod handlers;
 
 pub const INLINE_BUFFER_SIZE: usize = 164;
 pub const THREAD_MICRO_TIMEOUT: Duration = Duration::from_secs(30);
+/// voice throughput is averaged over this period of time
+const VOICE_THROUGHPUT_WINDOW: Duration = Duration::from_secs(2);
+/// icon updates arriving sooner than this after the previous accepted one are ignored
+const MIN_ICON_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+/// after this many sends in a row fail, the peer is considered dead and the thread is terminated
+const MAX_CONSECUTIVE_SEND_FAILURES: u32 = 5;
+/// max amount of voice packets coalesced into a single datagram when voice batching is enabled
+const MAX_VOICE_BATCH_PACKETS: usize = 8;
+/// when kicking someone, at most this many of the messages still queued for them are sent before the disconnect notice,
+/// for at most this long, so that a full queue can't hold up the disconnect
+const MAX_FLUSHED_MESSAGES: usize = 32;
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// how many packets a client can send in a ratelimiter period, on top of the player data it sends every tick
+const fn packet_rate_limit(tps: u32) -> usize {
+    tps as usize + 6
+}
 
 #[derive(Clone)]
 pub enum ServerThreadMessage {
     SmallPacket(([u8; INLINE_BUFFER_SIZE], usize)),
     Packet(Vec<u8>),
-    BroadcastVoice(Arc<VoiceBroadcastPacket>),
+    BroadcastVoice(Arc<EncodedVoiceBroadcastPacket>),
     BroadcastText(ChatMessageBroadcastPacket),
     BroadcastNotice(ServerNoticePacket),
     BroadcastInvite(RoomInvitePacket),
@@ -44,13 +62,18 @@ pub enum ServerThreadMessage {
     BroadcastBan(ServerBannedPacket),
     BroadcastMute(ServerMutedPacket),
     BroadcastRoleChange(RolesUpdatedPacket),
-    TerminationNotice(FastString),
+    BroadcastLevelJoin(LevelPlayerJoinedPacket),
+    BroadcastLevelLeave(LevelPlayerLeftPacket),
+    TerminationNotice(DisconnectReason, FastString),
+    /// wakes up a thread that was terminated from the outside, without sending anything to the client
+    ForceDisconnect,
 }
 
 pub struct ClientThread {
     pub game_server: &'static GameServer,
     pub socket: LockfreeMutCell<ClientSocket>,
     connection_state: AtomicClientThreadState,
+    pub connection_phase: ConnectionPhaseTracker,
 
     pub secret_key: u32,
 
@@ -63,14 +86,46 @@ pub struct ClientThread {
     pub user_role: SyncMutex<ComputedRole>,
 
     pub fragmentation_limit: AtomicU16,
+    /// protocol version negotiated in the handshake, packets that changed since then must be encoded the old way for this client
+    pub protocol: AtomicU16,
+    /// admins can log in as spectators, which lets them watch levels without being visible to anyone
+    pub spectator: bool,
 
     pub is_authorized_admin: AtomicBool,
+    /// whether the user logged into the admin panel with the global admin key
+    pub is_global_admin: AtomicBool,
+    /// `admin_key_generation` of the key the user logged in with, if it changed since then they lose their permissions
+    pub global_admin_generation: AtomicU32,
+    pub admin_auth_failures: AtomicU32,
+
+    /// players whose voice this user has muted
+    pub voice_muted_players: SyncMutex<IntSet<i32>>,
+    /// whether this user only wants to hear the voice of their friends
+    pub voice_friends_only: AtomicBool,
+    /// friend list sent by the client, kept until they disconnect. It is not verified in any way, a client can claim
+    /// that anyone is their friend, so this only filters what the user hears and is not a privacy control
+    pub voice_friends: SyncMutex<IntSet<i32>>,
+    /// traffic of this connection, shared with the socket
+    pub connection_stats: Arc<ConnectionStats>,
+    /// whether the client was told that voice or profiles are disabled, as it keeps using those without the player doing anything
+    voice_disabled_notified: AtomicBool,
+    profiles_disabled_notified: AtomicBool,
 
     message_queue: Mutex<VecDeque<ServerThreadMessage>>,
     message_notify: Notify,
+    /// keepalive interval (in seconds) the client was told to use when it logged in, the idle timeout is based on it
+    pub keepalive_interval: u32,
     rate_limiter: LockfreeMutCell<SimpleRateLimiter>,
+    /// when the last `LevelDataPacket` was sent to this client
+    last_level_data: LockfreeMutCell<Instant>,
     voice_rate_limiter: LockfreeMutCell<SimpleRateLimiter>,
+    voice_throughput_limiter: LockfreeMutCell<ThroughputLimiter>,
     chat_rate_limiter: Option<LockfreeMutCell<SimpleRateLimiter>>,
+    profile_rate_limiter: Option<LockfreeMutCell<SimpleRateLimiter>>,
+    last_icon_sync: LockfreeMutCell<Option<Instant>>,
+    send_failures: LockfreeMutCell<u32>,
+    /// malformed voice packets received, minus valid ones
+    voice_strikes: LockfreeMutCell<u32>,
 
     pub destruction_notify: Arc<Notify>,
 }
@@ -84,12 +139,13 @@ impl ClientThread {
     pub fn from_unauthorized(thread: UnauthorizedThread) -> Self {
         let game_server = thread.game_server;
 
-        let (rate_limiter, voice_rate_limiter, chat_rate_limiter) = {
+        let (rate_limiter, voice_rate_limiter, voice_throughput_limiter, chat_rate_limiter, profile_rate_limiter) = {
             let conf = game_server.bridge.central_conf.lock();
 
             (
-                SimpleRateLimiter::new(conf.tps as usize + 6, Duration::from_millis(900)),
+                SimpleRateLimiter::new(packet_rate_limit(conf.tps), Duration::from_millis(900)),
                 SimpleRateLimiter::new(5, Duration::from_millis(1000)),
+                ThroughputLimiter::new(conf.voice_throughput_limit as usize * 1024, VOICE_THROUGHPUT_WINDOW),
                 if conf.chat_burst_interval != 0 && conf.chat_burst_limit != 0 {
                     Some(SimpleRateLimiter::new(
                         conf.chat_burst_limit as usize,
@@ -98,10 +154,20 @@ impl ClientThread {
                 } else {
                     None
                 },
+                if conf.profile_request_interval != 0 && conf.profile_request_limit != 0 {
+                    Some(SimpleRateLimiter::new(
+                        conf.profile_request_limit as usize,
+                        Duration::from_millis(u64::from(conf.profile_request_interval)),
+                    ))
+                } else {
+                    None
+                },
             )
         };
 
         let account_data = std::mem::take(&mut *thread.account_data.lock());
+        let voice_muted_players = game_server.state.mute_cache.take(thread.account_id.load(Ordering::Relaxed));
+        let connection_stats = unsafe { thread.socket.get() }.stats.clone();
         let user_entry = std::mem::take(&mut *thread.user_entry.lock()).unwrap_or_default();
         let user_role = std::mem::take(&mut *thread.user_role.lock()).unwrap_or_else(|| game_server.state.role_manager.get_default().clone());
 
@@ -109,8 +175,9 @@ impl ClientThread {
             game_server,
             socket: thread.socket,
             connection_state: thread.connection_state,
+            connection_phase: thread.connection_phase,
 
-            secret_key: thread.secret_key,
+            secret_key: thread.secret_key.into_inner(),
 
             account_id: thread.account_id,
             level_id: thread.level_id,
@@ -121,23 +188,127 @@ impl ClientThread {
             user_role: SyncMutex::new(user_role),
 
             fragmentation_limit: thread.fragmentation_limit,
+            protocol: thread.protocol,
+            spectator: thread.spectator.into_inner(),
 
             is_authorized_admin: AtomicBool::new(false),
+            is_global_admin: AtomicBool::new(false),
+            global_admin_generation: AtomicU32::new(0),
+            admin_auth_failures: AtomicU32::new(0),
+
+            voice_muted_players: SyncMutex::new(voice_muted_players),
+            voice_friends_only: AtomicBool::new(false),
+            voice_friends: SyncMutex::new(IntSet::default()),
+            connection_stats,
 
             message_queue: Mutex::new(VecDeque::new()),
             message_notify: Notify::new(),
+            keepalive_interval: thread.keepalive_interval.load(Ordering::Relaxed),
             rate_limiter: LockfreeMutCell::new(rate_limiter),
+            last_level_data: LockfreeMutCell::new(Instant::now()),
             voice_rate_limiter: LockfreeMutCell::new(voice_rate_limiter),
+            voice_throughput_limiter: LockfreeMutCell::new(voice_throughput_limiter),
             chat_rate_limiter: chat_rate_limiter.map(LockfreeMutCell::new),
+            profile_rate_limiter: profile_rate_limiter.map(LockfreeMutCell::new),
+            last_icon_sync: LockfreeMutCell::new(None),
+            send_failures: LockfreeMutCell::new(0),
+            voice_strikes: LockfreeMutCell::new(0),
+            voice_disabled_notified: AtomicBool::new(false),
+            profiles_disabled_notified: AtomicBool::new(false),
 
             destruction_notify: thread.destruction_notify,
         }
     }
 
     pub fn into_unauthorized(self) -> UnauthorizedThread {
+        // if the connection gets recovered, the new thread picks them up again
+        self.save_voice_mutes();
         UnauthorizedThread::downgrade(self)
     }
 
+    /// Returns `true` if enough time has passed since the last `LevelDataPacket` to send another one.
+    /// This way level data goes out at most once per server tick, no matter how often the client sends its own data.
+    /// The tps is read every time, so that changing it on the central server applies to connected clients right away.
+    fn should_send_level_data(&self) -> bool {
+        // allow some jitter, so that a client sending exactly at the tickrate doesn't randomly miss updates
+        let interval = Duration::from_secs(1) * 3 / (4 * self.game_server.bridge.tps());
+
+        // safety: only we can access this field
+        let last_sent = unsafe { self.last_level_data.get_mut() };
+        let now = Instant::now();
+
+        if now.duration_since(*last_sent) < interval {
+            return false;
+        }
+
+        *last_sent = now;
+        true
+    }
+
+    /// Returns `true` if an icon update can be accepted now, so that spamming them can't keep invalidating the profile cache.
+    fn try_icon_sync(&self) -> bool {
+        // safety: only we can access this field
+        let last_sync = unsafe { self.last_icon_sync.get_mut() };
+        let now = Instant::now();
+
+        if last_sync.is_some_and(|last| now.duration_since(last) < MIN_ICON_SYNC_INTERVAL) {
+            return false;
+        }
+
+        *last_sync = Some(now);
+        true
+    }
+
+    /// optional features agreed on in the handshake
+    fn capabilities(&self) -> HandshakeCapabilities {
+        // safety: only we can use our socket, and the capabilities never change after the handshake
+        unsafe { self.socket.get() }.capabilities
+    }
+
+    /// Returns `None` if a profile request can be answered now, otherwise how long until the next one will be.
+    fn profile_request_retry_after(&self) -> Option<Duration> {
+        let limiter = self.profile_rate_limiter.as_ref()?;
+
+        // safety: only we can use this ratelimiter.
+        let limiter = unsafe { limiter.get_mut() };
+        if limiter.try_tick() {
+            None
+        } else {
+            Some(limiter.time_until_refill())
+        }
+    }
+
+    /// Keeps track of consecutive send failures, returns `true` once there were too many of them in a row,
+    /// which means the peer is most likely gone and the thread should be terminated.
+    fn should_give_up_sending(&self, error: Option<&PacketHandlingError>) -> bool {
+        // safety: only we can access this field
+        let failures = unsafe { self.send_failures.get_mut() };
+
+        match error {
+            None => *failures = 0,
+            Some(PacketHandlingError::SocketSendFailed(_)) => *failures += 1,
+            Some(_) => {}
+        }
+
+        *failures >= MAX_CONSECUTIVE_SEND_FAILURES
+    }
+
+    /// Counts a strike for a malformed voice packet, or takes one away for a valid one. Returns `true` once the connection
+    /// has reached the configured strike limit, so a client that corrupts a packet now and then is never disconnected for it.
+    fn track_voice_strikes(&self, malformed: bool) -> bool {
+        // safety: only we can access this field
+        let strikes = unsafe { self.voice_strikes.get_mut() };
+
+        if malformed {
+            *strikes += 1;
+        } else {
+            *strikes = strikes.saturating_sub(1);
+        }
+
+        let limit = self.game_server.config.voice_strike_limit;
+        limit != 0 && *strikes >= limit
+    }
+
     /* public api for the main server */
 
     async fn poll_for_messages(&self) -> Option<ServerThreadMessage> {
@@ -171,8 +342,13 @@ impl ClientThread {
                 ClientThreadState::Established => {}
             }
 
-            // if more than 90 seconds since last packet, disonnect
-            if last_received_packet.elapsed().as_secs() > 90 {
+            // if nothing was received for a few keepalive intervals, disconnect
+            let idle_timeout = idle_timeout_for(self.keepalive_interval);
+            if last_received_packet.elapsed() > idle_timeout {
+                // the client is most likely gone already, but if it isn't, let it know why
+                let _ = self
+                    .kick(DisconnectReason::IdleTimeout, "No data was received from you for too long.")
+                    .await;
                 break self.terminate();
             }
 
@@ -187,9 +363,14 @@ impl ClientThread {
                             _ => {}
                         }
 
-                        match self.handle_message(message).await {
-                            Ok(()) => {}
-                            Err(e) => self.print_error(&e),
+                        let result = self.handle_message(message).await;
+                        if let Err(e) = &result {
+                            self.print_error(e);
+                        }
+
+                        if self.should_give_up_sending(result.as_ref().err()) {
+                            warn!("[{}] too many failed sends in a row, disconnecting", self.get_tcp_peer());
+                            break self.terminate();
                         }
                     }
                 }
@@ -208,7 +389,19 @@ impl ClientThread {
 
                         match self.recv_and_handle(message_len).await {
                             Ok(()) => {}
-                            Err(e) => self.print_error(&e),
+                            Err(e) if e.is_fatal() => {
+                                self.print_error(&e);
+                                break self.terminate();
+                            }
+                            Err(e) => {
+                                self.print_error(&e);
+
+                                // a successfully handled packet doesn't mean anything was sent, so only failures are counted here
+                                if self.should_give_up_sending(Some(&e)) {
+                                    warn!("[{}] too many failed sends in a row, disconnecting", self.get_tcp_peer());
+                                    break self.terminate();
+                                }
+                            }
                         }
                     }
                     Err(err) => {
@@ -217,7 +410,7 @@ impl ClientThread {
                     }
                 },
 
-                () = tokio::time::sleep(THREAD_MICRO_TIMEOUT) => {
+                () = tokio::time::sleep(THREAD_MICRO_TIMEOUT.min(idle_timeout)) => {
                     continue;
                 }
             };
@@ -228,6 +421,16 @@ impl ClientThread {
         self.account_id.load(Ordering::Relaxed) != 0
     }
 
+    /// authenticated and not a spectator, so should be visible to other players
+    pub fn is_visible(&self) -> bool {
+        self.authenticated() && !self.spectator
+    }
+
+    #[inline]
+    pub fn is_spectator(&self) -> bool {
+        self.spectator
+    }
+
     /// schedule the thread to terminate as soon as possible.
     #[inline]
     pub fn terminate(&self) -> ClientThreadOutcome {
@@ -243,22 +446,39 @@ impl ClientThread {
     }
 
     pub async fn push_new_message(&self, message: ServerThreadMessage) {
-        self.message_queue.lock().await.push_back(message);
+        {
+            let mut queue = self.message_queue.lock().await;
+
+            // voice is useless once it's late anyway, so a slow client just misses some of it,
+            // instead of its queue growing forever
+            if queue.len() >= self.game_server.config.message_queue_capacity && matches!(message, ServerThreadMessage::BroadcastVoice(_)) {
+                self.game_server.state.metrics.voice_packets_dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            queue.push_back(message);
+        }
+
         self.message_notify.notify_one();
     }
 
     /* private utilities */
 
     /// get the tcp address of the connected peer. do not call this from another clientthread
-    fn get_tcp_peer(&self) -> SocketAddrV4 {
+    fn get_tcp_peer(&self) -> SocketAddr {
         // safety: we trust this function is not called from the oustide
         unsafe { self.socket.get() }.tcp_peer
     }
 
     // the error printing is different in release and debug. some errors have higher severity than others.
     fn print_error(&self, error: &PacketHandlingError) {
+        self.game_server.state.metrics.record_packet_error(error);
+
+        let account_id = self.account_id.load(Ordering::Relaxed);
+        let peer = self.get_tcp_peer();
+
         if cfg!(debug_assertions) {
-            warn!("[{} @ {}] {}", self.account_id.load(Ordering::Relaxed), self.get_tcp_peer(), error);
+            warn!(account_id, peer:%; "[{account_id} @ {peer}] {error}");
         } else {
             match error {
                 // these are for the client being silly
@@ -266,18 +486,23 @@ impl ClientThread {
                 | PacketHandlingError::WrongCryptoBoxState
                 | PacketHandlingError::EncryptionError
                 | PacketHandlingError::DecryptionError
+                | PacketHandlingError::DecompressionError
                 | PacketHandlingError::NoHandler(_)
                 | PacketHandlingError::DebugOnlyPacket
                 | PacketHandlingError::PacketTooLong(_)
+                | PacketHandlingError::OversizedPacket(..)
+                | PacketHandlingError::CorruptedPacket
                 | PacketHandlingError::SocketSendFailed(_)
-                | PacketHandlingError::InvalidStreamMarker => {
-                    warn!("[{} @ {}] {}", self.account_id.load(Ordering::Relaxed), self.get_tcp_peer(), error);
+                | PacketHandlingError::InvalidStreamMarker
+                | PacketHandlingError::MalformedVoiceData
+                | PacketHandlingError::InvalidLevelStats => {
+                    warn!(account_id, peer:%; "[{account_id} @ {peer}] {error}");
                 }
 
                 PacketHandlingError::IOError(ref e) => match e.kind() {
                     // we ignore early eof and connection reset as they're pretty common and meaningless
                     ErrorKind::ConnectionReset | ErrorKind::UnexpectedEof => {}
-                    _ => warn!("[{} @ {}] {}", self.account_id.load(Ordering::Relaxed), self.get_tcp_peer(), e),
+                    _ => warn!(account_id, peer:%; "[{account_id} @ {peer}] {e}"),
                 },
                 // these are either our fault or a fatal error somewhere
                 PacketHandlingError::ColorParseFailed(_)
@@ -285,8 +510,9 @@ impl ClientThread {
                 | PacketHandlingError::SystemTimeError(_)
                 | PacketHandlingError::WebRequestError(_)
                 | PacketHandlingError::DangerousAllocation(_)
+                | PacketHandlingError::SendTooLong(..)
                 | PacketHandlingError::UnableToSendUdp => {
-                    error!("[{} @ {}] {}", self.account_id.load(Ordering::Relaxed), self.get_tcp_peer(), error);
+                    error!(account_id, peer:%; "[{account_id} @ {peer}] {error}");
                 }
                 // these can likely never happen unless network corruption or someone is pentesting, so ignore in release
                 PacketHandlingError::MalformedMessage
@@ -295,22 +521,72 @@ impl ClientThread {
                 | PacketHandlingError::MalformedPacketStructure(_)
                 | PacketHandlingError::SocketWouldBlock
                 | PacketHandlingError::Ratelimited
+                | PacketHandlingError::TooManyVoiceFrames(_)
                 | PacketHandlingError::UnexpectedPlayerData => {}
             }
         }
     }
 
     /// call `self.terminate()` and send a message to the user with the reason
-    async fn kick(&self, message: &str) -> Result<()> {
+    async fn kick(&self, reason: DisconnectReason, message: &str) -> Result<()> {
         self.terminate();
-        self.send_packet_dynamic(&ServerDisconnectPacket { message }).await
+        self.flush_queued_messages().await;
+        self.send_packet_dynamic(&ServerDisconnectPacket { message, reason }).await
     }
 
     async fn ban(&self, message: FastString, timestamp: i64) -> Result<()> {
         self.terminate();
+        self.flush_queued_messages().await;
         self.send_packet_dynamic(&ServerBannedPacket { message, timestamp }).await
     }
 
+    /// send the voice, chat messages and notices that are still queued, so that the last moment before a kick isn't lost.
+    /// everything else in the queue is thrown away, as the thread is terminating anyway
+    async fn flush_queued_messages(&self) {
+        let messages: Vec<_> = {
+            let mut queue = self.message_queue.lock().await;
+            let count = queue.len().min(MAX_FLUSHED_MESSAGES);
+            queue.drain(..count).collect()
+        };
+
+        let flush = async {
+            for message in messages {
+                let result = match message {
+                    ServerThreadMessage::BroadcastVoice(packet) => self.send_voice(packet).await,
+                    ServerThreadMessage::BroadcastText(packet) => self.send_packet_static(&packet).await,
+                    ServerThreadMessage::BroadcastNotice(packet) => self.send_packet_dynamic(&packet).await,
+                    ServerThreadMessage::Packet(packet) => {
+                        buffer_pool::put(packet);
+                        continue;
+                    }
+                    _ => continue,
+                };
+
+                if result.is_err() {
+                    break;
+                }
+            }
+        };
+
+        let _ = tokio::time::timeout(FLUSH_TIMEOUT, flush).await;
+    }
+
+    /// keep the voice mutes of this user around for a while, in case they reconnect soon
+    pub fn save_voice_mutes(&self) {
+        let muted = std::mem::take(&mut *self.voice_muted_players.lock());
+        self.game_server.state.mute_cache.save(self.account_id.load(Ordering::Relaxed), muted);
+    }
+
+    /// whether this user has muted the voice of the given player
+    pub fn is_voice_muted(&self, account_id: i32) -> bool {
+        self.voice_muted_players.lock().contains(&account_id)
+    }
+
+    /// whether the voice of the given player should be sent to this user, taking mutes and the friends only setting into account
+    pub fn accepts_voice_from(&self, account_id: i32) -> bool {
+        !self.is_voice_muted(account_id) && (!self.voice_friends_only.load(Ordering::Relaxed) || self.voice_friends.lock().contains(&account_id))
+    }
+
     fn is_chat_packet_allowed(&self, voice: bool, len: usize) -> bool {
         let accid = self.account_id.load(Ordering::Relaxed);
         if accid == 0 {
@@ -323,18 +599,29 @@ impl ClientThread {
             return false;
         }
 
+        if self.spectator {
+            // talking would give the spectator away
+            return false;
+        }
+
         // check for slowmode stuffs
         if voice {
-            if len > MAX_VOICE_PACKET_SIZE {
-                // voice packet is too big
-                return false;
-            }
-
             // safety: only we can access the rate limiters of our user.
             let block = !unsafe { self.voice_rate_limiter.get_mut().try_tick() };
             if block {
                 return false;
             }
+
+            // like the tps, the limit can change on the central server at any time, so keep it in sync
+            let throughput_limit = self.game_server.bridge.voice_throughput_limit.load(Ordering::Relaxed) as usize * 1024;
+            // safety: same as above.
+            let throughput_limiter = unsafe { self.voice_throughput_limiter.get_mut() };
+            throughput_limiter.set_rate(throughput_limit);
+
+            // if rate limiting is disabled, do not block
+            if throughput_limit != 0 && !throughput_limiter.try_consume(len) {
+                return false;
+            }
         } else {
             // if rate limiting is disabled, do not block
             let block = !self.chat_rate_limiter.as_ref().map_or(true, |x| unsafe { x.get_mut().try_tick() });
@@ -357,10 +644,14 @@ impl ClientThread {
     /// handle a message sent from the `GameServer`
     async fn handle_message(&self, message: ServerThreadMessage) -> Result<()> {
         match message {
-            ServerThreadMessage::Packet(mut packet) => self.handle_packet(&mut packet).await?,
+            ServerThreadMessage::Packet(mut packet) => {
+                let result = self.handle_packet(&mut packet).await;
+                buffer_pool::put(packet);
+                result?;
+            }
             ServerThreadMessage::SmallPacket((mut packet, len)) => self.handle_packet(&mut packet[..len]).await?,
             ServerThreadMessage::BroadcastText(text_packet) => self.send_packet_static(&text_packet).await?,
-            ServerThreadMessage::BroadcastVoice(voice_packet) => self.send_packet_dynamic(&*voice_packet).await?,
+            ServerThreadMessage::BroadcastVoice(voice_packet) => self.send_voice(voice_packet).await?,
             ServerThreadMessage::BroadcastNotice(packet) => {
                 self.send_packet_dynamic(&packet).await?;
                 info!("{} is receiving a notice: {}", self.account_data.lock().name, packet.message);
@@ -372,14 +663,99 @@ impl ClientThread {
             ServerThreadMessage::BroadcastBan(packet) => self.ban(packet.message, packet.timestamp).await?,
             ServerThreadMessage::BroadcastMute(packet) => self.send_packet_dynamic(&packet).await?,
             ServerThreadMessage::BroadcastRoleChange(packet) => self.send_packet_static(&packet).await?,
-            ServerThreadMessage::TerminationNotice(message) => self.kick(message.try_to_str()).await?,
+            ServerThreadMessage::BroadcastLevelJoin(packet) => self.send_packet_static(&packet).await?,
+            ServerThreadMessage::BroadcastLevelLeave(packet) => self.send_packet_static(&packet).await?,
+            ServerThreadMessage::TerminationNotice(reason, message) => self.kick(reason, message.try_to_str()).await?,
+            ServerThreadMessage::ForceDisconnect => {
+                self.terminate();
+            }
         }
 
         Ok(())
     }
 
-    /// handle an incoming packet
+    /// send a voice packet, coalescing it with the voice packets queued right after it if batching was agreed on
+    async fn send_voice(&self, voice_packet: Arc<EncodedVoiceBroadcastPacket>) -> Result<()> {
+        let mut batch = Vec::new();
+        // the amount of packets, then every packet prefixed with its length
+        let mut size = size_of_types!(u16) * 2 + voice_packet.data.len();
+
+        if self.capabilities().voice_batching() {
+            // the limit is for the whole datagram, so leave room for the header and encryption
+            let limit = (self.fragmentation_limit.load(Ordering::Relaxed) as usize).saturating_sub(PacketHeader::SIZE + socket::ENCRYPTION_OVERHEAD);
+
+            let mut queue = self.message_queue.lock().await;
+            while batch.len() + 1 < MAX_VOICE_BATCH_PACKETS {
+                let Some(ServerThreadMessage::BroadcastVoice(next)) = queue.front() else {
+                    break;
+                };
+
+                let next_size = size + size_of_types!(u16) + next.data.len();
+                if next_size > limit {
+                    break;
+                }
+
+                size = next_size;
+                if let Some(ServerThreadMessage::BroadcastVoice(next)) = queue.pop_front() {
+                    batch.push(next);
+                }
+            }
+        }
+
+        // nothing else was waiting, send it as a regular packet so clients without batching support are unaffected
+        if batch.is_empty() {
+            return self
+                .send_packet_alloca_with::<VoiceBroadcastPacket, _>(voice_packet.data.len(), |buf| buf.write_bytes(&voice_packet.data))
+                .await;
+        }
+
+        batch.insert(0, voice_packet);
+
+        self.send_packet_alloca_with::<VoiceBroadcastBatchPacket, _>(size, |buf| {
+            buf.write_length(batch.len());
+            for packet in &batch {
+                buf.write_byte_array(&packet.data);
+            }
+        })
+        .await
+    }
+
+    /// handle an incoming packet, warning if its handler took unusually long.
+    /// time spent waiting for a slow peer to accept the responses is not counted, as that isn't the handler's fault
     async fn handle_packet(&self, message: &mut [u8]) -> Result<()> {
+        // safety: only we can use our socket, and this is just a read of an atomic
+        let send_wait = || unsafe { self.socket.get() }.send_wait();
+
+        let started = Instant::now();
+        let send_wait_before = send_wait();
+        let result = self.dispatch_packet(message).await;
+
+        if let Some(threshold) = self.game_server.config.slow_handler_threshold {
+            let elapsed = started.elapsed().saturating_sub(send_wait() - send_wait_before);
+
+            if elapsed > threshold {
+                // decryption happens after the header, so it's still intact
+                let packet_id = ByteReader::from_bytes(message).read_packet_header().map_or(0, |h| h.packet_id);
+
+                // admin actions wait for the central server and webhooks, so they are expected to be slow
+                let is_admin_packet = (19000..20000).contains(&packet_id);
+
+                if !is_admin_packet {
+                    warn!(
+                        "[{} @ {}] handling packet {packet_id} took {}ms ({} bytes)",
+                        self.account_id.load(Ordering::Relaxed),
+                        self.get_tcp_peer(),
+                        elapsed.as_millis(),
+                        message.len()
+                    );
+                }
+            }
+        }
+
+        result
+    }
+
+    async fn dispatch_packet(&self, message: &mut [u8]) -> Result<()> {
         #[cfg(debug_assertions)]
         if message.len() < PacketHeader::SIZE {
             return Err(PacketHandlingError::MalformedMessage);
@@ -387,12 +763,28 @@ impl ClientThread {
 
         // if we are ratelimited, just discard the packet.
         // safety: only we can use this ratelimiter.
-        if !unsafe { self.rate_limiter.get_mut() }.try_tick() {
+        let rate_limiter = unsafe { self.rate_limiter.get_mut() };
+        if !rate_limiter.try_tick() {
             return Err(PacketHandlingError::Ratelimited);
         }
 
+        let header = ByteReader::from_bytes(message).read_packet_header()?;
+        let size_limit = packet_size_limit(header.packet_id).unwrap_or_else(|| self.game_server.bridge.max_packet_size());
+        if message.len() > size_limit {
+            return Err(PacketHandlingError::OversizedPacket(header.packet_id, message.len()));
+        }
+
+        let Some(len) = header.verify_checksum(message) else {
+            self.game_server.state.metrics.corrupted_packets.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        };
+
+        self.connection_stats.record_received(header.packet_id, message.len());
+        self.game_server.state.metrics.packet_counts.record(header.packet_id);
+
+        let message = &mut message[..len];
         let mut data = ByteReader::from_bytes(message);
-        let header = data.read_packet_header()?;
+        data.skip(PacketHeader::SIZE);
 
         // by far the most common packet, so we try it early
         if header.packet_id == PlayerDataPacket::PACKET_ID {
@@ -409,14 +801,22 @@ impl ClientThread {
         }
 
         // decrypt the packet in-place if encrypted
-        if header.encrypted {
+        if header.encrypted() {
             data = unsafe { self.socket.get_mut() }.decrypt(message)?;
         }
 
+        // and then inflate it if compressed
+        let decompressed;
+        if header.compressed() {
+            decompressed = unsafe { self.socket.get() }.decompress(&data, size_limit)?;
+            data = ByteReader::from_bytes(&decompressed);
+        }
+
         match header.packet_id {
             /* connection related */
             PingPacket::PACKET_ID => self.handle_ping(&mut data).await,
             KeepalivePacket::PACKET_ID => self.handle_keepalive(&mut data).await,
+            KeepaliveEchoPacket::PACKET_ID => self.handle_keepalive_echo(&mut data).await,
             DisconnectPacket::PACKET_ID => self.handle_disconnect(&mut data),
             ConnectionTestPacket::PACKET_ID => self.handle_connection_test(&mut data).await,
             KeepaliveTCPPacket::PACKET_ID => self.handle_keepalive_tcp(&mut data).await,
@@ -426,16 +826,22 @@ impl ClientThread {
             RequestGlobalPlayerListPacket::PACKET_ID => self.handle_request_global_list(&mut data).await,
             RequestLevelListPacket::PACKET_ID => self.handle_request_level_list(&mut data).await,
             RequestPlayerCountPacket::PACKET_ID => self.handle_request_player_count(&mut data).await,
+            RequestPlayerListPacket::PACKET_ID => self.handle_request_player_list(&mut data).await,
 
             /* game related */
             RequestPlayerProfilesPacket::PACKET_ID => self.handle_request_profiles(&mut data).await,
+            RequestChangedProfilesPacket::PACKET_ID => self.handle_request_changed_profiles(&mut data).await,
             LevelJoinPacket::PACKET_ID => self.handle_level_join(&mut data).await,
             LevelLeavePacket::PACKET_ID => self.handle_level_leave(&mut data).await,
             PlayerDataPacket::PACKET_ID => self.handle_player_data(&mut data).await,
             PlayerMetadataPacket::PACKET_ID => self.handle_player_metadata(&mut data).await,
+            LevelStatsPacket::PACKET_ID => self.handle_level_stats(&mut data).await,
+            RequestLeaderboardPacket::PACKET_ID => self.handle_request_leaderboard(&mut data).await,
 
             VoicePacket::PACKET_ID => self.handle_voice(&mut data).await,
             ChatMessagePacket::PACKET_ID => self.handle_chat_message(&mut data).await,
+            VoiceMutePacket::PACKET_ID => self.handle_voice_mute(&mut data).await,
+            VoiceSettingsPacket::PACKET_ID => self.handle_voice_settings(&mut data).await,
 
             /* room related */
             CreateRoomPacket::PACKET_ID => self.handle_create_room(&mut data).await,
@@ -452,6 +858,12 @@ impl ClientThread {
             AdminDisconnectPacket::PACKET_ID => self.handle_admin_disconnect(&mut data).await,
             AdminGetUserStatePacket::PACKET_ID => self.handle_admin_get_user_state(&mut data).await,
             AdminUpdateUserPacket::PACKET_ID => self.handle_admin_update_user(&mut data).await,
+            AdminSetMaintenancePacket::PACKET_ID => self.handle_admin_set_maintenance(&mut data).await,
+            AdminSetLogLevelPacket::PACKET_ID => self.handle_admin_set_log_level(&mut data).await,
+            AdminGetPlayerStatsPacket::PACKET_ID => self.handle_admin_get_player_stats(&mut data).await,
+            AdminGetServerStatsPacket::PACKET_ID => self.handle_admin_get_server_stats(&mut data).await,
+            AdminFindPlayerPacket::PACKET_ID => self.handle_admin_find_player(&mut data).await,
+            AdminForceDisconnectPacket::PACKET_ID => self.handle_admin_force_disconnect(&mut data).await,
             x => Err(PacketHandlingError::NoHandler(x)),
         }
     }
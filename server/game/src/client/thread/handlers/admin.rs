@@ -1,12 +1,18 @@
-use globed_shared::{info, warn};
+use std::time::Duration;
+
+use globed_shared::{info, parse_log_level, warn};
 
 use crate::{
     managers::ComputedRole,
+    metrics::ConnectionPhase,
     webhook::{BanMuteStateChange, WebhookMessage},
 };
 
 use super::*;
 
+/// most results returned by `AdminFindPlayerPacket`, a query matching more players is not specific enough anyway
+const MAX_FOUND_PLAYERS: usize = 50;
+
 macro_rules! admin_error {
     ($self:expr, $msg:expr) => {
         $self.send_packet_dynamic(&AdminErrorPacket { message: $msg }).await?;
@@ -14,6 +20,13 @@ macro_rules! admin_error {
     };
 }
 
+/// after this many wrong passwords, all further admin login attempts on the same connection are rejected
+const MAX_ADMIN_AUTH_FAILURES: u32 = 5;
+/// how long a log level set by an admin stays active, unless specified otherwise
+const DEFAULT_LOG_LEVEL_DURATION: Duration = Duration::from_secs(15 * 60);
+/// longest duration a log level set by an admin can stay active for
+const MAX_LOG_LEVEL_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
 #[derive(Clone, Copy)]
 enum AdminPerm {
     Any,
@@ -30,6 +43,13 @@ enum AdminPerm {
 impl ClientThread {
     // check if the user is logged in as admin, and if they have the given permission
     fn _has_perm(&self, perm: AdminPerm) -> bool {
+        // the key is rotated on all threads when the configuration is refreshed, but a login racing with the refresh could be missed
+        if self.is_global_admin.load(Ordering::Relaxed)
+            && self.global_admin_generation.load(Ordering::Relaxed) != self.game_server.bridge.admin_key_generation.load(Ordering::Relaxed)
+        {
+            self.revoke_global_admin();
+        }
+
         if !self.is_authorized_admin.load(Ordering::Relaxed) {
             return false;
         }
@@ -57,11 +77,46 @@ impl ClientThread {
         self.user_role.lock().clone_from(from);
     }
 
+    // append an action taken by this user to the audit log
+    fn _audit(&self, action: &str, target_id: Option<i32>, details: &str) {
+        let admin_name = self.account_data.lock().name.try_to_string();
+
+        self.game_server
+            .audit_log
+            .record(self.account_id.load(Ordering::Relaxed), &admin_name, action, target_id, details);
+    }
+
+    /// Logs the user out of the admin panel if they logged in with the global admin key, used when the key changes.
+    pub fn revoke_global_admin(&self) {
+        if !self.is_global_admin.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        self.is_authorized_admin.store(false, Ordering::Relaxed);
+
+        let role = self.game_server.state.role_manager.compute(&self.user_entry.lock().user_roles);
+        self._update_user_role(&role);
+    }
+
     gs_handler!(self, handle_admin_auth, AdminAuthPacket, packet, {
         let account_id = gs_needauth!(self);
 
-        // test for the global password first
-        if packet.key.constant_time_compare(&self.game_server.bridge.central_conf.lock().admin_key) {
+        // don't let anyone bruteforce the password
+        if self.admin_auth_failures.load(Ordering::Relaxed) >= MAX_ADMIN_AUTH_FAILURES {
+            return self.send_packet_static(&AdminAuthFailedPacket).await;
+        }
+
+        // test for the global password first. the generation is read under the same lock as the key, so it always matches it
+        let global_generation = {
+            let conf = self.game_server.bridge.central_conf.lock();
+
+            packet
+                .key
+                .constant_time_compare(&conf.admin_key)
+                .then(|| self.game_server.bridge.admin_key_generation.load(Ordering::Relaxed))
+        };
+
+        if let Some(generation) = global_generation {
             info!(
                 "[{} ({}) @ {}] just logged into the admin panel (with global password)",
                 self.account_data.lock().name,
@@ -70,6 +125,8 @@ impl ClientThread {
             );
 
             self.is_authorized_admin.store(true, Ordering::Relaxed);
+            self.global_admin_generation.store(generation, Ordering::Relaxed);
+            self.is_global_admin.store(true, Ordering::Relaxed);
             // give super admin perms
             let role = self.game_server.state.role_manager.get_superadmin();
             self._update_user_role(&role);
@@ -104,14 +161,25 @@ impl ClientThread {
             }
         }
 
+        let failures = self.admin_auth_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+        // the attempted password is intentionally not logged, as it might be a typo of the real one
         info!(
-            "[{} ({}) @ {}] just failed to login to the admin panel with password: {}",
+            "[{} ({}) @ {}] just failed to login to the admin panel ({failures} failed attempts)",
             self.account_data.lock().name,
             account_id,
             self.get_tcp_peer(),
-            packet.key
         );
 
+        if failures == MAX_ADMIN_AUTH_FAILURES {
+            warn!(
+                "[{} ({}) @ {}] too many failed admin login attempts, ignoring any further ones on this connection",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+            );
+        }
+
         // this is silly tbh
         // if self.game_server.bridge.has_webhook() {
         //     let name = self.account_data.lock().name.try_to_string();
@@ -128,6 +196,12 @@ impl ClientThread {
         let account_id = gs_needauth!(self);
 
         if !self._has_perm(AdminPerm::Notice) {
+            warn!(
+                "[{} ({}) @ {}] tried to send a notice without the required permissions",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+            );
             return Ok(());
         }
 
@@ -145,6 +219,12 @@ impl ClientThread {
         match packet.notice_type {
             AdminSendNoticeType::Everyone => {
                 if !self._has_perm(AdminPerm::NoticeToEveryone) {
+                    warn!(
+                        "[{} ({}) @ {}] tried to send a notice to everyone without the required permissions",
+                        self.account_data.lock().name,
+                        account_id,
+                        self.get_tcp_peer(),
+                    );
                     admin_error!(self, "no permission");
                 }
 
@@ -166,6 +246,12 @@ impl ClientThread {
                     notice_packet.message,
                 );
 
+                self._audit(
+                    "notice_everyone",
+                    None,
+                    &format!("sent to {} people: {}", threads.len(), notice_packet.message),
+                );
+
                 if self.game_server.bridge.has_webhook() {
                     if let Err(err) = self
                         .game_server
@@ -207,6 +293,12 @@ impl ClientThread {
                     self.get_tcp_peer()
                 );
 
+                self._audit(
+                    "notice_person",
+                    thread.as_ref().map(|thr| thr.account_id.load(Ordering::Relaxed)),
+                    &format!("sent to {player_name}: {notice_msg}"),
+                );
+
                 if self.game_server.bridge.has_webhook() {
                     if let Err(err) = self
                         .game_server
@@ -282,6 +374,17 @@ impl ClientThread {
                     threads.len()
                 );
 
+                self._audit(
+                    "notice_room",
+                    None,
+                    &format!(
+                        "sent to {} people in room {} on level {}: {notice_msg}",
+                        threads.len(),
+                        packet.room_id,
+                        packet.level_id
+                    ),
+                );
+
                 if self.game_server.bridge.has_webhook() {
                     if let Err(err) = self
                         .game_server
@@ -308,18 +411,50 @@ impl ClientThread {
     });
 
     gs_handler!(self, handle_admin_disconnect, AdminDisconnectPacket, packet, {
-        let _ = gs_needauth!(self);
+        let account_id = gs_needauth!(self);
 
         if !self._has_perm(AdminPerm::Kick) {
+            warn!(
+                "[{} ({}) @ {}] tried to kick {} without the required permissions",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+                packet.player
+            );
             return Ok(());
         }
 
         // to kick everyone, require admin
-        if &*packet.player == "@everyone" && self._has_perm(AdminPerm::KickEveryone) {
+        if &*packet.player == "@everyone" {
+            if !self._has_perm(AdminPerm::KickEveryone) {
+                warn!(
+                    "[{} ({}) @ {}] tried to kick everyone without the required permissions",
+                    self.account_data.lock().name,
+                    account_id,
+                    self.get_tcp_peer(),
+                );
+                admin_error!(self, "insufficient permissions");
+            }
+
+            info!(
+                "[{} ({}) @ {}] kicked everyone from the server, reason: {}",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+                packet.message
+            );
+
             let threads: Vec<_> = self.game_server.clients.lock().values().cloned().collect();
+
+            self._audit(
+                "kick_everyone",
+                None,
+                &format!("kicked {} people, reason: {}", threads.len(), packet.message),
+            );
+
             for thread in threads {
                 thread
-                    .push_new_message(ServerThreadMessage::TerminationNotice(packet.message.clone()))
+                    .push_new_message(ServerThreadMessage::TerminationNotice(DisconnectReason::Kicked, packet.message.clone()))
                     .await;
             }
 
@@ -340,7 +475,25 @@ impl ClientThread {
         if let Some(thread) = self.game_server.find_user(&packet.player) {
             let reason_string = packet.message.try_to_string();
 
-            thread.push_new_message(ServerThreadMessage::TerminationNotice(packet.message)).await;
+            info!(
+                "[{} ({}) @ {}] kicked {} ({}), reason: {}",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+                thread.account_data.lock().name,
+                thread.account_id.load(Ordering::Relaxed),
+                reason_string
+            );
+
+            self._audit(
+                "kick",
+                Some(thread.account_id.load(Ordering::Relaxed)),
+                &format!("reason: {reason_string}"),
+            );
+
+            thread
+                .push_new_message(ServerThreadMessage::TerminationNotice(DisconnectReason::Kicked, packet.message))
+                .await;
 
             if self.game_server.bridge.has_webhook() {
                 let own_name = self.account_data.lock().name.try_to_string();
@@ -531,6 +684,7 @@ impl ClientThread {
             if c_user_roles {
                 let special_data = SpecialUserData::from_user_entry(&new_user_entry, &self.game_server.state.role_manager);
                 thread.account_data.lock().special_user_data.clone_from(&special_data);
+                self.game_server.state.profile_cache.invalidate(thread.account_id.load(Ordering::Relaxed));
 
                 // tell the user that their roles changed
                 thread
@@ -578,6 +732,12 @@ impl ClientThread {
 
         match result {
             Ok(()) => {
+                if c_is_banned {
+                    self.game_server.state.ban_manager.update_from(&new_user_entry);
+                }
+
+                self.game_server.state.user_cache.invalidate(target_account_id);
+
                 let own_name = self.account_data.lock().name.try_to_string();
 
                 info!(
@@ -588,6 +748,44 @@ impl ClientThread {
                     target_account_id
                 );
 
+                let mut changes = Vec::new();
+                if c_is_banned {
+                    changes.push(if new_user_entry.is_banned { "banned" } else { "unbanned" }.to_owned());
+                }
+
+                if c_is_muted {
+                    changes.push(if new_user_entry.is_muted { "muted" } else { "unmuted" }.to_owned());
+                }
+
+                if c_is_whitelisted {
+                    changes.push(if new_user_entry.is_whitelisted { "whitelisted" } else { "unwhitelisted" }.to_owned());
+                }
+
+                if c_violation_reason || c_violation_expiry {
+                    changes.push(format!(
+                        "violation reason: {:?}, expiry: {:?}",
+                        new_user_entry.violation_reason, new_user_entry.violation_expiry
+                    ));
+                }
+
+                if c_user_roles {
+                    changes.push(format!("roles: {:?} -> {:?}", user_entry.user_roles, new_user_entry.user_roles));
+                }
+
+                if c_name_color {
+                    changes.push(format!("name color: {:?} -> {:?}", user_entry.name_color, new_user_entry.name_color));
+                }
+
+                if c_admin_password {
+                    changes.push("changed the admin password".to_owned());
+                }
+
+                self._audit(
+                    "update_user",
+                    Some(target_account_id),
+                    &format!("{target_user_name}: {}", changes.join(", ")),
+                );
+
                 if self.game_server.bridge.has_webhook() {
                     // this is crazy
                     let mut messages = FastVec::<WebhookMessage, 4>::new();
@@ -659,4 +857,236 @@ impl ClientThread {
             }
         }
     });
+
+    gs_handler!(self, handle_admin_set_maintenance, AdminSetMaintenancePacket, packet, {
+        let account_id = gs_needauth!(self);
+
+        if !self._has_perm(AdminPerm::Admin) {
+            warn!(
+                "[{} ({}) @ {}] tried to change maintenance mode without the required permissions",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+            );
+            admin_error!(self, "no permission");
+        }
+
+        let maintenance = self.game_server.bridge.set_local_maintenance(packet.enabled);
+
+        info!(
+            "[{} ({}) @ {}] {} maintenance mode",
+            self.account_data.lock().name,
+            account_id,
+            self.get_tcp_peer(),
+            if packet.enabled { "enabled" } else { "disabled" }
+        );
+
+        let mut kicked = 0;
+        if maintenance && packet.kick_players {
+            // kick everyone except admins, so they can still check on the server
+            let threads: Vec<_> = self
+                .game_server
+                .clients
+                .lock()
+                .values()
+                .filter(|thr| thr.authenticated() && !thr.user_role.lock().admin)
+                .cloned()
+                .collect();
+
+            kicked = threads.len();
+
+            for thread in threads {
+                thread
+                    .push_new_message(ServerThreadMessage::TerminationNotice(
+                        DisconnectReason::Maintenance,
+                        FastString::new("The server is now under maintenance, please try connecting again later"),
+                    ))
+                    .await;
+            }
+        }
+
+        self._audit(
+            "maintenance",
+            None,
+            &format!(
+                "{} maintenance mode, kicked {kicked} people{}",
+                if packet.enabled { "enabled" } else { "disabled" },
+                if maintenance == packet.enabled {
+                    ""
+                } else {
+                    " (still enabled by the central server)"
+                }
+            ),
+        );
+
+        let message = if maintenance == packet.enabled {
+            format!(
+                "Maintenance mode is now {}{}",
+                if maintenance { "enabled" } else { "disabled" },
+                if kicked == 0 {
+                    String::new()
+                } else {
+                    format!(", kicked {kicked} people")
+                }
+            )
+        } else {
+            "Maintenance mode is still enabled by the central server".to_owned()
+        };
+
+        self.send_packet_dynamic(&AdminSuccessMessagePacket { message: &message }).await
+    });
+
+    gs_handler!(self, handle_admin_set_log_level, AdminSetLogLevelPacket, packet, {
+        let account_id = gs_needauth!(self);
+
+        if !self._has_perm(AdminPerm::Admin) {
+            warn!(
+                "[{} ({}) @ {}] tried to change the log level without the required permissions",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+            );
+            admin_error!(self, "no permission");
+        }
+
+        let Some(level) = parse_log_level(packet.level.try_to_str()) else {
+            admin_error!(
+                self,
+                "invalid log level, possible values are 'trace', 'debug', 'info', 'warn', 'error' and 'off'"
+            );
+        };
+
+        let duration = if packet.duration == 0 {
+            DEFAULT_LOG_LEVEL_DURATION
+        } else {
+            Duration::from_secs(u64::from(packet.duration)).min(MAX_LOG_LEVEL_DURATION)
+        };
+
+        // warn, so that this is still visible even with a low log level
+        warn!(
+            "[{} ({}) @ {}] changed the log level to {level} for {} seconds",
+            self.account_data.lock().name,
+            account_id,
+            self.get_tcp_peer(),
+            duration.as_secs()
+        );
+
+        self._audit("log_level", None, &format!("set to {level} for {} seconds", duration.as_secs()));
+
+        self.game_server.set_temporary_log_level(level, duration);
+
+        let message = format!("Log level set to {level} for {} minutes", duration.as_secs().div_ceil(60));
+        self.send_packet_dynamic(&AdminSuccessMessagePacket { message: &message }).await
+    });
+
+    gs_handler!(self, handle_admin_get_player_stats, AdminGetPlayerStatsPacket, packet, {
+        let _ = gs_needauth!(self);
+
+        if !self._has_perm(AdminPerm::Any) {
+            admin_error!(self, "No permission (not a mod)");
+        }
+
+        let Some(thread) = self.game_server.get_user_by_id(packet.account_id) else {
+            admin_error!(self, "This player is not connected to the server");
+        };
+
+        let stats = &thread.connection_stats;
+
+        self.send_packet_dynamic(&AdminPlayerStatsPacket {
+            account_id: packet.account_id,
+            bytes_sent: stats.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: stats.bytes_received.load(Ordering::Relaxed),
+            packets_sent: stats.packets_sent.load(Ordering::Relaxed),
+            packets_received: stats.packets_received.load(Ordering::Relaxed),
+            rtt_last: stats.rtt_last.load(Ordering::Relaxed),
+            rtt_average: stats.rtt_average.load(Ordering::Relaxed),
+            voice_jitter: stats.voice_jitter(),
+            voice_intervals: stats.voice_interval_histogram(),
+            packet_counts: stats.packet_counts(),
+        })
+        .await
+    });
+
+    gs_handler!(self, handle_admin_get_server_stats, AdminGetServerStatsPacket, _packet, {
+        let _ = gs_needauth!(self);
+
+        if !self._has_perm(AdminPerm::Any) {
+            admin_error!(self, "No permission (not a mod)");
+        }
+
+        let metrics = &self.game_server.state.metrics;
+
+        self.send_packet_dynamic(&AdminServerStatsPacket {
+            handshake_connections: metrics.connection_count(ConnectionPhase::Handshake),
+            unauthenticated_connections: metrics.connection_count(ConnectionPhase::Unauthenticated),
+            authenticated_connections: metrics.connection_count(ConnectionPhase::Authenticated),
+            player_count: self.game_server.state.get_player_count(),
+            packet_counts: metrics.packet_counts.get_all(),
+        })
+        .await
+    });
+
+    gs_handler!(self, handle_admin_find_player, AdminFindPlayerPacket, packet, {
+        let _ = gs_needauth!(self);
+
+        if !self._has_perm(AdminPerm::Any) {
+            admin_error!(self, "No permission (not a mod)");
+        }
+
+        let query = packet.query.try_to_str().trim();
+        if query.is_empty() {
+            admin_error!(self, "Search query is empty");
+        }
+
+        let players = self.game_server.search_players(query, MAX_FOUND_PLAYERS);
+
+        let packet = AdminFoundPlayersPacket { players };
+        self.send_packet_dynamic(&packet).await
+    });
+
+    gs_handler!(self, handle_admin_force_disconnect, AdminForceDisconnectPacket, packet, {
+        let account_id = gs_needauth!(self);
+
+        if !self._has_perm(AdminPerm::Kick) {
+            warn!(
+                "[{} ({}) @ {}] tried to force disconnect {} without the required permissions",
+                self.account_data.lock().name,
+                account_id,
+                self.get_tcp_peer(),
+                packet.account_id
+            );
+            admin_error!(self, "no permission");
+        }
+
+        if packet.account_id == account_id {
+            admin_error!(self, "you cannot disconnect yourself");
+        }
+
+        let Some(thread) = self.game_server.get_user_by_id(packet.account_id) else {
+            admin_error!(self, "This player is not connected to the server");
+        };
+
+        let target_name = thread.account_data.lock().name.try_to_string();
+
+        info!(
+            "[{} ({}) @ {}] force disconnected {} ({})",
+            self.account_data.lock().name,
+            account_id,
+            self.get_tcp_peer(),
+            target_name,
+            packet.account_id
+        );
+
+        self._audit("force_disconnect", Some(packet.account_id), &target_name);
+
+        // terminate right away instead of after everything that is already queued for them,
+        // the message only wakes the thread up so that it notices
+        thread.terminate();
+        thread.push_new_message(ServerThreadMessage::ForceDisconnect).await;
+
+        self.send_packet_dynamic(&AdminSuccessMessagePacket {
+            message: &format!("Disconnected {target_name}"),
+        })
+        .await
+    });
 }
@@ -2,11 +2,15 @@ use std::sync::atomic::Ordering;
 
 use super::*;
 
+/// round trip times longer than this (in milliseconds) are assumed to be bogus
+const MAX_VALID_RTT: u64 = 30_000;
+
 impl ClientThread {
     gs_handler!(self, handle_ping, PingPacket, packet, {
         self.send_packet_static(&PingResponsePacket {
             id: packet.id,
             player_count: self.game_server.state.get_player_count(),
+            uptime: self.game_server.uptime(),
         })
         .await
     });
@@ -16,10 +20,24 @@ impl ClientThread {
 
         self.send_packet_static(&KeepaliveResponsePacket {
             player_count: self.game_server.state.get_player_count(),
+            timestamp: self.game_server.uptime_millis(),
         })
         .await
     });
 
+    gs_handler!(self, handle_keepalive_echo, KeepaliveEchoPacket, packet, {
+        let _ = gs_needauth!(self);
+
+        let now = self.game_server.uptime_millis();
+
+        // ignore timestamps from the future or ones that are way too old to be a real keepalive response
+        if let Some(rtt) = now.checked_sub(packet.timestamp).filter(|rtt| *rtt <= MAX_VALID_RTT) {
+            self.connection_stats.record_rtt(rtt as u32);
+        }
+
+        Ok(())
+    });
+
     gs_handler_sync!(self, handle_disconnect, DisconnectPacket, _packet, {
         self.terminate();
         Ok(())
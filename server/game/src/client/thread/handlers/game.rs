@@ -1,9 +1,13 @@
 use std::sync::{atomic::Ordering, Arc};
 
 use super::*;
+use crate::managers::LevelLeaderboard;
 
-/// max voice packet size in bytes
-pub const MAX_VOICE_PACKET_SIZE: usize = 4096;
+/// max amount of players a single user can mute
+const MAX_VOICE_MUTED_PLAYERS: usize = 1024;
+
+/// percentage of `voice_max_bitrate` a single voice packet can reach, opus uses variable bitrate so some frames are larger than the target
+const VOICE_BITRATE_TOLERANCE: u64 = 150;
 
 impl ClientThread {
     gs_handler!(self, handle_level_join, LevelJoinPacket, packet, {
@@ -13,6 +17,18 @@ impl ClientThread {
         let room_id = self.room_id.load(Ordering::Relaxed);
 
         self.game_server.state.room_manager.with_any(room_id, |pm| {
+            if self.is_spectator() {
+                if old_level != 0 {
+                    pm.manager.remove_spectator_from_level(old_level, account_id);
+                }
+
+                if packet.level_id != 0 {
+                    pm.manager.add_spectator_to_level(packet.level_id, account_id);
+                }
+
+                return;
+            }
+
             if old_level != 0 {
                 pm.manager.remove_from_level(old_level, account_id);
             }
@@ -22,6 +38,11 @@ impl ClientThread {
             }
         });
 
+        if old_level != packet.level_id && !self.is_spectator() {
+            self.game_server.broadcast_level_leave(account_id, old_level, room_id).await;
+            self.game_server.broadcast_level_join(account_id, packet.level_id, room_id).await;
+        }
+
         Ok(())
     });
 
@@ -32,9 +53,19 @@ impl ClientThread {
         if level_id != 0 {
             let room_id = self.room_id.load(Ordering::Relaxed);
 
+            if self.is_spectator() {
+                self.game_server.state.room_manager.with_any(room_id, |pm| {
+                    pm.manager.remove_spectator_from_level(level_id, account_id);
+                });
+
+                return Ok(());
+            }
+
             self.game_server.state.room_manager.with_any(room_id, |pm| {
                 pm.manager.remove_from_level(level_id, account_id);
             });
+
+            self.game_server.broadcast_level_leave(account_id, level_id, room_id).await;
         }
 
         Ok(())
@@ -51,13 +82,19 @@ impl ClientThread {
         let room_id = self.room_id.load(Ordering::Relaxed);
 
         let written_players = self.game_server.state.room_manager.with_any(room_id, |pm| {
+            // the position of a spectator is never stored, so it can't be sent to anyone
+            if self.is_spectator() {
+                return pm.manager.get_player_count_on_level(level_id).unwrap_or(0);
+            }
+
             pm.manager.set_player_data(account_id, &packet.data);
             // this unwrap should be safe and > 0 given that self.level_id != 0, but we leave a default just in case
             pm.manager.get_player_count_on_level(level_id).unwrap_or(1) - 1
         });
 
-        // no one else on the level, no need to send a response packet
-        if written_players == 0 {
+        // no one else on the level, no need to send a response packet.
+        // the data is always stored though, so others see the latest state even if we don't respond this time.
+        if written_players == 0 || !self.should_send_level_data() {
             return Ok(());
         }
 
@@ -134,7 +171,9 @@ impl ClientThread {
 
         let room_id = self.room_id.load(Ordering::Relaxed);
         let players = self.game_server.state.room_manager.with_any(room_id, |pm| {
-            pm.manager.set_player_meta(account_id, &packet.data);
+            if !self.is_spectator() {
+                pm.manager.set_player_meta(account_id, &packet.data);
+            }
 
             let mut vec = Vec::with_capacity(pm.manager.get_player_count_on_level(level_id).unwrap_or(0));
             pm.manager.for_each_player_on_level(
@@ -160,14 +199,97 @@ impl ClientThread {
         }
     });
 
-    gs_handler!(self, handle_request_profiles, RequestPlayerProfilesPacket, packet, {
+    gs_handler!(self, handle_level_stats, LevelStatsPacket, packet, {
+        let account_id = gs_needauth!(self);
+
+        let level_id = self.level_id.load(Ordering::Relaxed);
+        if level_id == 0 {
+            return Err(PacketHandlingError::UnexpectedPlayerData);
+        }
+
+        // spectators aren't playing the level
+        if self.is_spectator() {
+            return Ok(());
+        }
+
+        if !LevelLeaderboard::is_valid_result(packet.best, packet.platformer) {
+            return Err(PacketHandlingError::InvalidLevelStats);
+        }
+
+        let entry = LeaderboardEntry {
+            account_id,
+            name: self.account_data.lock().name.clone(),
+            best: packet.best,
+        };
+
+        let room_id = self.room_id.load(Ordering::Relaxed);
+        self.game_server.state.room_manager.with_any(room_id, |pm| {
+            pm.manager.submit_level_result(level_id, entry, packet.platformer);
+        });
+
+        Ok(())
+    });
+
+    /// not a `gs_handler!`, as the packet has no body before protocol v8
+    pub(crate) async fn handle_request_leaderboard(&self, buf: &mut esp::ByteReader<'_>) -> crate::client::Result<()> {
         let _ = gs_needauth!(self);
 
+        // older clients only know about the classic leaderboard
+        let platformer = if self.protocol.load(Ordering::Relaxed) >= 8 {
+            RequestLeaderboardPacket::decode_from_reader(buf)?.platformer
+        } else {
+            false
+        };
+
+        let level_id = self.level_id.load(Ordering::Relaxed);
+        let room_id = self.room_id.load(Ordering::Relaxed);
+
+        let entries = self.game_server.state.room_manager.with_any(room_id, |pm| {
+            pm.manager
+                .get_leaderboard(level_id, platformer)
+                .map(|board| board.entries.clone())
+                .unwrap_or_default()
+        });
+
+        self.send_packet_dynamic(&LevelLeaderboardPacket {
+            level_id,
+            platformer,
+            entries,
+        })
+        .await
+    }
+
+    gs_handler!(self, handle_request_profiles, RequestPlayerProfilesPacket, packet, {
+        let account_id = gs_needauth!(self);
+
+        if !self.game_server.bridge.profiles_enabled.load(Ordering::Relaxed) {
+            if !self.profiles_disabled_notified.swap(true, Ordering::Relaxed) {
+                gs_notice!(self, "Player profiles are currently disabled on this server.");
+            }
+
+            return Ok(());
+        }
+
+        self.profiles_disabled_notified.store(false, Ordering::Relaxed);
+
         let level_id = self.level_id.load(Ordering::Relaxed);
         if level_id == 0 {
             return Err(PacketHandlingError::UnexpectedPlayerData);
         }
 
+        if let Some(retry_after) = self.profile_request_retry_after() {
+            // clients that don't know the packet would disconnect on it, so they don't get a response at all
+            if !self.capabilities().profiles_ratelimited() {
+                return Ok(());
+            }
+
+            return self
+                .send_packet_static(&ProfilesRatelimitedPacket {
+                    retry_after: retry_after.as_millis() as u32,
+                })
+                .await;
+        }
+
         let room_id = self.room_id.load(Ordering::Relaxed);
 
         // if they requested just one player - use the fast heapless path
@@ -188,17 +310,22 @@ impl ClientThread {
             return Ok(());
         }
 
-        let players = self.game_server.state.room_manager.with_any(room_id, |pm| {
-            // this unwrap should be safe and > 0 given that self.level_id != 0, but we leave a default just in case
-            let total_players = pm.manager.get_player_count_on_level(level_id).unwrap_or(1) - 1;
+        let max_players = self.game_server.bridge.central_conf.lock().profile_request_max_players as usize;
+
+        // everyone loading into a crowded level asks for the same profiles at once, so they share a single gather.
+        // it includes the requesting player, so one extra profile is gathered to still have enough after leaving them out
+        let gathered = self.game_server.state.profile_cache.get_or_gather_level(room_id, level_id, || {
+            self.game_server.state.room_manager.with_any(room_id, |pm| {
+                let total_players = pm.manager.get_player_count_on_level(level_id).unwrap_or(0).min(max_players + 1);
 
-            if total_players == 0 {
-                Vec::new()
-            } else {
                 let mut vec = Vec::with_capacity(total_players);
                 pm.manager.for_each_player_on_level(
                     level_id,
                     |player, _count, vec| {
+                        if vec.len() > max_players {
+                            return false;
+                        }
+
                         let account_data = self.game_server.get_player_account_data(player.account_id);
                         if let Some(d) = account_data {
                             vec.push(d);
@@ -210,29 +337,191 @@ impl ClientThread {
                 );
 
                 vec
-            }
+            })
         });
 
+        let players: Vec<_> = gathered
+            .iter()
+            .filter(|player| player.account_id != account_id)
+            .take(max_players)
+            .cloned()
+            .collect();
+
         self.send_packet_dynamic(&PlayerProfilesPacket { players }).await
     });
 
+    gs_handler!(self, handle_request_changed_profiles, RequestChangedProfilesPacket, packet, {
+        let account_id = gs_needauth!(self);
+
+        if !self.game_server.bridge.profiles_enabled.load(Ordering::Relaxed) {
+            if !self.profiles_disabled_notified.swap(true, Ordering::Relaxed) {
+                gs_notice!(self, "Player profiles are currently disabled on this server.");
+            }
+
+            return Ok(());
+        }
+
+        self.profiles_disabled_notified.store(false, Ordering::Relaxed);
+
+        let level_id = self.level_id.load(Ordering::Relaxed);
+        if level_id == 0 {
+            return Err(PacketHandlingError::UnexpectedPlayerData);
+        }
+
+        if let Some(retry_after) = self.profile_request_retry_after() {
+            // clients that don't know the packet would disconnect on it, so they don't get a response at all
+            if !self.capabilities().profiles_ratelimited() {
+                return Ok(());
+            }
+
+            return self
+                .send_packet_static(&ProfilesRatelimitedPacket {
+                    retry_after: retry_after.as_millis() as u32,
+                })
+                .await;
+        }
+
+        let room_id = self.room_id.load(Ordering::Relaxed);
+        let max_players = self.game_server.bridge.central_conf.lock().profile_request_max_players as usize;
+
+        let level_players: Vec<i32> = self.game_server.state.room_manager.with_any(room_id, |pm| {
+            pm.manager
+                .get_level(level_id)
+                .map(|players| players.iter().copied().filter(|&id| id != account_id).take(max_players).collect())
+                .unwrap_or_default()
+        });
+
+        let mut revisions = Vec::with_capacity(level_players.len());
+        let mut players = Vec::new();
+
+        for player_id in level_players {
+            // the revision is read before the profile, so if it changes in between, the client just gets the newer profile again later
+            let revision = self.game_server.state.profile_cache.get_revision(player_id);
+
+            let known = packet.known.iter().any(|x| x.account_id == player_id && x.revision == revision);
+
+            if !known {
+                let Some(data) = self.game_server.get_player_account_data(player_id) else {
+                    continue;
+                };
+
+                players.push(data);
+            }
+
+            revisions.push(ProfileRevision {
+                account_id: player_id,
+                revision,
+            });
+        }
+
+        self.send_packet_dynamic(&ChangedPlayerProfilesPacket { revisions, players }).await
+    });
+
     /* Note: blocking logic for voice & chat packets is not in here but in the packet receiving function */
 
     gs_handler!(self, handle_voice, VoicePacket, packet, {
         let account_id = gs_needauth!(self);
 
-        let vpkt = Arc::new(VoiceBroadcastPacket {
+        if !self.game_server.bridge.voice_enabled.load(Ordering::Relaxed) {
+            if !self.voice_disabled_notified.swap(true, Ordering::Relaxed) {
+                gs_notice!(self, "Voice chat is currently disabled on this server.");
+            }
+
+            return Ok(());
+        }
+
+        self.voice_disabled_notified.store(false, Ordering::Relaxed);
+
+        self.game_server.state.metrics.voice_packets.fetch_add(1, Ordering::Relaxed);
+        self.connection_stats.record_voice_arrival();
+
+        let max_frames = match self.game_server.bridge.voice_max_frames.load(Ordering::Relaxed) {
+            0 => usize::MAX,
+            x => x as usize,
+        };
+
+        // don't forward garbage to other clients' decoders. too many frames is only a setting the client doesn't know about yet,
+        // so those packets are dropped without counting against the client
+        let average_frame_size = match packet.data.average_frame_size(max_frames) {
+            Ok(x) => x,
+            Err(VoiceFrameError::TooManyFrames(frames)) => return Err(PacketHandlingError::TooManyVoiceFrames(frames)),
+            Err(VoiceFrameError::Malformed) => {
+                if self.track_voice_strikes(true) {
+                    self.kick(DisconnectReason::Other, "Too many malformed voice packets were received from you.")
+                        .await?;
+                }
+
+                return Err(PacketHandlingError::MalformedVoiceData);
+            }
+        };
+
+        self.track_voice_strikes(false);
+
+        // nothing but silence, no point in sending it to everyone
+        let silence_threshold = self.game_server.bridge.voice_silence_threshold.load(Ordering::Relaxed) as usize;
+        if average_frame_size < silence_threshold {
+            self.game_server.state.metrics.voice_packets_gated.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let max_bitrate = self.game_server.bridge.voice_max_bitrate.load(Ordering::Relaxed);
+        if max_bitrate != 0
+            && packet
+                .data
+                .bitrate()
+                .is_some_and(|bitrate| u64::from(bitrate) > u64::from(max_bitrate) * 1000 * VOICE_BITRATE_TOLERANCE / 100)
+        {
+            self.game_server.state.metrics.voice_packets_over_bitrate.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        // encode it just once here, instead of separately for every recipient
+        let vpkt = Arc::new(EncodedVoiceBroadcastPacket::new(&VoiceBroadcastPacket {
             player_id: account_id,
             data: packet.data,
-        });
+        }));
+
+        let level_id = self.level_id.load(Ordering::Relaxed);
+
+        if self.game_server.voice_recorder.as_ref().is_some_and(|rec| !rec.record(&vpkt, level_id)) {
+            self.game_server.state.metrics.voice_recording_dropped.fetch_add(1, Ordering::Relaxed);
+        }
 
         self.game_server
-            .broadcast_voice_packet(&vpkt, self.level_id.load(Ordering::Relaxed), self.room_id.load(Ordering::Relaxed))
+            .broadcast_voice_packet(&vpkt, level_id, self.room_id.load(Ordering::Relaxed))
             .await;
 
         Ok(())
     });
 
+    gs_handler!(self, handle_voice_mute, VoiceMutePacket, packet, {
+        let _ = gs_needauth!(self);
+
+        let mut muted = self.voice_muted_players.lock();
+
+        if !packet.muted {
+            muted.remove(&packet.account_id);
+        } else if muted.len() < MAX_VOICE_MUTED_PLAYERS {
+            muted.insert(packet.account_id);
+        }
+
+        Ok(())
+    });
+
+    gs_handler!(self, handle_voice_settings, VoiceSettingsPacket, packet, {
+        let _ = gs_needauth!(self);
+
+        // the friend list comes from the client and isn't checked, the central server has no way to read it from the GD servers.
+        // anyone can be listed as a friend, so this only decides what this user hears and must never be used to hide anything from them
+        let mut friends = self.voice_friends.lock();
+        friends.clear();
+        friends.extend(packet.friends.iter().copied());
+
+        self.voice_friends_only.store(packet.friends_only, Ordering::Relaxed);
+
+        Ok(())
+    });
+
     gs_handler!(self, handle_chat_message, ChatMessagePacket, packet, {
         let account_id = gs_needauth!(self);
 
@@ -240,6 +529,11 @@ impl ClientThread {
             return Ok(());
         }
 
+        if !self.game_server.bridge.chat_enabled.load(Ordering::Relaxed) {
+            gs_notice!(self, "Text chat is currently disabled on this server.");
+            return Ok(());
+        }
+
         let cpkt = ChatMessageBroadcastPacket {
             player_id: account_id,
             message: packet.message,
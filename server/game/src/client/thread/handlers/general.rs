@@ -2,9 +2,16 @@ use super::*;
 
 impl ClientThread {
     gs_handler!(self, handle_sync_icons, SyncIconsPacket, packet, {
-        let _ = gs_needauth!(self);
+        let account_id = gs_needauth!(self);
+
+        // too fast, just ignore it
+        if !self.try_icon_sync() {
+            return Ok(());
+        }
+
+        self.account_data.lock().icons = packet.icons.sanitized();
+        self.game_server.state.profile_cache.invalidate(account_id);
 
-        self.account_data.lock().icons.clone_from(&packet.icons);
         Ok(())
     });
 
@@ -39,6 +46,8 @@ impl ClientThread {
                 &mut vec,
             );
 
+            // busiest levels first
+            vec.sort_unstable_by_key(|level| std::cmp::Reverse(level.player_count));
             vec
         });
 
@@ -62,4 +71,31 @@ impl ClientThread {
 
         self.send_packet_dynamic(&LevelPlayerCountPacket { levels }).await
     });
+
+    gs_handler!(self, handle_request_player_list, RequestPlayerListPacket, packet, {
+        let _ = gs_needauth!(self);
+
+        // don't reveal who is in an editorcollab session
+        if is_editorcollab_level(packet.level_id) {
+            return self
+                .send_packet_dynamic(&PlayerListPacket {
+                    page: packet.page,
+                    total_players: 0,
+                    players: Vec::new(),
+                })
+                .await;
+        }
+
+        let room_id = self.room_id.load(Ordering::Relaxed);
+        let level_id = (packet.level_id != 0).then_some(packet.level_id);
+
+        let (total_players, players) = self.game_server.get_player_list_page(room_id, level_id, packet.page as usize);
+
+        self.send_packet_dynamic(&PlayerListPacket {
+            page: packet.page,
+            total_players: total_players as u32,
+            players,
+        })
+        .await
+    });
 }
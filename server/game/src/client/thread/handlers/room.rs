@@ -6,6 +6,15 @@ impl ClientThread {
 
         let room_id: u32 = self.room_id.load(Ordering::Relaxed);
 
+        // the owner of a room is always visible in it
+        if self.is_spectator() {
+            return self
+                .send_packet_dynamic(&RoomCreateFailedPacket {
+                    reason: "Spectators can't create rooms",
+                })
+                .await;
+        }
+
         // if we are already in a room, just return the same room info, otherwise create a new one
         let room_info = if room_id == 0 {
             // check if data is valid
@@ -25,11 +34,20 @@ impl ClientThread {
                 return self.send_packet_dynamic(&RoomCreateFailedPacket { reason }).await;
             }
 
-            let room_info = self
-                .game_server
+            let room_info = self.game_server.state.room_manager.create_room(
+                account_id,
+                packet.room_name,
+                packet.password,
+                self.limit_room_settings(packet.settings),
+            );
+
+            // move the player out of the global room, so they aren't in two rooms at once
+            let level_id = self.level_id.load(Ordering::Relaxed);
+            self.game_server.state.room_manager.remove_with_any(0, account_id, level_id, false);
+            self.game_server
                 .state
                 .room_manager
-                .create_room(account_id, packet.room_name, packet.password, packet.settings);
+                .add_with_any(room_info.id, account_id, level_id, false);
 
             self.room_id.store(room_info.id, Ordering::Relaxed);
             room_info
@@ -96,11 +114,15 @@ impl ClientThread {
         let level_id = self.level_id.load(Ordering::Relaxed);
 
         // remove the player from the previously connected room (or the global room)
-        self.game_server.state.room_manager.remove_with_any(old_room_id, account_id, level_id);
+        self.game_server
+            .state
+            .room_manager
+            .remove_with_any(old_room_id, account_id, level_id, self.is_spectator());
 
-        self.game_server.state.room_manager.with_any(packet.room_id, |pm| {
-            pm.manager.create_player(account_id);
-        });
+        self.game_server
+            .state
+            .room_manager
+            .add_with_any(packet.room_id, account_id, level_id, self.is_spectator());
 
         self.send_packet_static(&RoomJoinedPacket).await
     });
@@ -115,7 +137,11 @@ impl ClientThread {
 
         let level_id = self.level_id.load(Ordering::Relaxed);
 
-        let should_send_update = self.game_server.state.room_manager.remove_with_any(room_id, account_id, level_id);
+        let should_send_update = self
+            .game_server
+            .state
+            .room_manager
+            .remove_with_any(room_id, account_id, level_id, self.is_spectator());
 
         // if we were the owner, send update packets to everyone
         if should_send_update {
@@ -123,7 +149,10 @@ impl ClientThread {
         }
 
         // add them to the global room
-        self.game_server.state.room_manager.get_global().manager.create_player(account_id);
+        self.game_server
+            .state
+            .room_manager
+            .add_with_any(0, account_id, level_id, self.is_spectator());
 
         // respond with the global room list
         self._respond_with_room_list(0).await
@@ -149,7 +178,7 @@ impl ClientThread {
 
         self.game_server.state.room_manager.with_any(room_id, |room| {
             if room.owner == account_id {
-                room.set_settings(&packet.settings);
+                room.set_settings(&self.limit_room_settings(packet.settings));
                 success = true;
             }
         });
@@ -243,4 +272,16 @@ impl ClientThread {
         })
         .await
     }
+
+    /// Caps the player limit of a room to the maximum room size configured on the server, if any.
+    /// An unlimited room (player limit of 0) gets the maximum size as well.
+    fn limit_room_settings(&self, mut settings: RoomSettings) -> RoomSettings {
+        let max_size = self.game_server.config.max_room_size;
+
+        if max_size != 0 && (settings.player_limit == 0 || settings.player_limit > max_size) {
+            settings.player_limit = max_size;
+        }
+
+        settings
+    }
 }
@@ -1,26 +1,30 @@
 use std::{
-    net::SocketAddrV4,
+    net::{IpAddr, SocketAddr},
     sync::{
-        atomic::{AtomicI32, AtomicU16, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU16, AtomicU32, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[allow(unused_imports)]
 use globed_shared::{
-    debug, info,
+    debug, info, negotiate_protocol,
     rand::{self, Rng},
-    warn, SyncMutex, UserEntry, MIN_CLIENT_VERSION, PROTOCOL_VERSION,
+    token_issuer::TokenValidationFailure,
+    warn, SyncMutex, UserEntry, MIN_CLIENT_VERSION, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
 
 use super::*;
 use crate::{
+    bridge::idle_timeout_for,
     data::*,
     managers::ComputedRole,
+    metrics::{ConnectionPhase, ConnectionPhaseTracker},
     server::GameServer,
     tokio::{self, net::TcpStream, sync::Notify},
     util::LockfreeMutCell,
+    webhook::WebhookMessage,
 };
 
 /// `UnauthorizedThread` is a thread that can be formed for 2 reasons:
@@ -36,8 +40,10 @@ pub struct UnauthorizedThread {
     pub game_server: &'static GameServer,
     pub socket: LockfreeMutCell<ClientSocket>,
     pub connection_state: AtomicClientThreadState,
+    pub connection_phase: ConnectionPhaseTracker,
 
-    pub secret_key: u32,
+    /// used to claim or recover the thread, changes after every recovery so that it can't be used twice
+    pub secret_key: AtomicU32,
 
     pub account_id: AtomicI32,
     pub level_id: AtomicLevelId,
@@ -48,14 +54,28 @@ pub struct UnauthorizedThread {
     pub user_role: SyncMutex<Option<ComputedRole>>,
 
     pub fragmentation_limit: AtomicU16,
-
-    pub claim_udp_peer: SyncMutex<Option<SocketAddrV4>>,
+    /// protocol version negotiated in the handshake
+    pub protocol: AtomicU16,
+    pub spectator: AtomicBool,
+    /// keepalive interval (in seconds) the client was last told to use. The idle timeout is based on it instead of the
+    /// current setting, as the client doesn't find out about changes until it logs in again
+    pub keepalive_interval: AtomicU32,
+
+    pub claim_udp_peer: SyncMutex<Option<SocketAddr>>,
     pub claim_udp_notify: Notify,
+    /// address of the tcp connection, unlike the socket it can be read by other threads (for example when claiming)
+    pub tcp_ip: SyncMutex<IpAddr>,
 
-    pub recover_stream: SyncMutex<Option<(TcpStream, SocketAddrV4)>>,
+    pub recover_stream: SyncMutex<Option<(TcpStream, SocketAddr)>>,
     pub recover_notify: Notify,
 
     pub terminate_notify: Notify,
+    /// amount of packets received that can't be handled before logging in, or that had an invalid checksum
+    preauth_strikes: AtomicU32,
+    /// set while the login is waiting in the login queue for the server to have a free slot
+    queued_login: SyncMutex<Option<QueuedLogin>>,
+    /// after the handshake, the `LoginPacket` has to arrive before this
+    login_deadline: SyncMutex<Option<Instant>>,
 
     pub destruction_notify: Arc<Notify>,
 }
@@ -66,15 +86,38 @@ pub enum UnauthorizedThreadOutcome {
 }
 
 const TIMEOUT: Duration = Duration::from_secs(90);
+/// packets that can't be handled before logging in or that are corrupted are dropped, after this many the connection is terminated
+const MAX_PREAUTH_STRIKES: u32 = 5;
+/// how often a queued login checks for a free slot, in case it missed a notification
+const LOGIN_QUEUE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Everything from a `LoginPacket` that is still needed after all the checks have passed.
+struct PendingLogin {
+    account_id: i32,
+    user_id: i32,
+    name: InlineString<MAX_NAME_SIZE>,
+    icons: PlayerIconData,
+    platform: InlineString<72>,
+    spectator: bool,
+}
+
+struct QueuedLogin {
+    login: PendingLogin,
+    ticket: u64,
+    /// last position the client was told about
+    position: usize,
+    last_keepalive: Instant,
+}
 
 impl UnauthorizedThread {
-    pub fn new(socket: TcpStream, peer: SocketAddrV4, game_server: &'static GameServer) -> Self {
+    pub fn new(socket: TcpStream, peer: SocketAddr, game_server: &'static GameServer) -> Self {
         Self {
             game_server,
             socket: LockfreeMutCell::new(ClientSocket::new(socket, peer, game_server)),
             connection_state: AtomicClientThreadState::default(),
+            connection_phase: ConnectionPhaseTracker::new(&game_server.state.metrics),
 
-            secret_key: rand::thread_rng().gen(),
+            secret_key: AtomicU32::new(rand::thread_rng().gen()),
 
             account_id: AtomicI32::new(0),
             level_id: AtomicLevelId::new(0),
@@ -85,26 +128,37 @@ impl UnauthorizedThread {
             user_role: SyncMutex::new(None),
 
             fragmentation_limit: AtomicU16::new(0),
+            protocol: AtomicU16::new(PROTOCOL_VERSION),
+            spectator: AtomicBool::new(false),
+            keepalive_interval: AtomicU32::new(game_server.bridge.keepalive_interval()),
 
             claim_udp_peer: SyncMutex::new(None),
             claim_udp_notify: Notify::new(),
+            tcp_ip: SyncMutex::new(peer.ip()),
 
             recover_stream: SyncMutex::new(None),
             recover_notify: Notify::new(),
 
             terminate_notify: Notify::new(),
+            preauth_strikes: AtomicU32::new(0),
+            queued_login: SyncMutex::new(None),
+            login_deadline: SyncMutex::new(None),
 
             destruction_notify: Arc::new(Notify::new()),
         }
     }
 
     pub fn downgrade(thread: ClientThread) -> Self {
+        // safety: we own the thread, so nothing else can access the socket
+        let tcp_ip = unsafe { thread.socket.get() }.tcp_peer.ip();
+
         Self {
             game_server: thread.game_server,
             socket: thread.socket,
             connection_state: AtomicClientThreadState::new(ClientThreadState::Disconnected),
+            connection_phase: thread.connection_phase,
 
-            secret_key: thread.secret_key,
+            secret_key: AtomicU32::new(thread.secret_key),
 
             account_id: thread.account_id,
             level_id: thread.level_id,
@@ -115,14 +169,21 @@ impl UnauthorizedThread {
             user_role: SyncMutex::new(Some(std::mem::take(&mut *thread.user_role.lock()))),
 
             fragmentation_limit: thread.fragmentation_limit,
+            protocol: thread.protocol,
+            spectator: AtomicBool::new(thread.spectator),
+            keepalive_interval: AtomicU32::new(thread.keepalive_interval),
 
             claim_udp_peer: SyncMutex::new(None),
             claim_udp_notify: Notify::new(),
+            tcp_ip: SyncMutex::new(tcp_ip),
 
             recover_stream: SyncMutex::new(None),
             recover_notify: Notify::new(),
 
             terminate_notify: Notify::new(),
+            preauth_strikes: AtomicU32::new(0),
+            queued_login: SyncMutex::new(None),
+            login_deadline: SyncMutex::new(None),
 
             destruction_notify: thread.destruction_notify,
         }
@@ -135,7 +196,10 @@ impl UnauthorizedThread {
 
             match state {
                 ClientThreadState::Established => break UnauthorizedThreadOutcome::Upgrade,
-                ClientThreadState::Terminating => break UnauthorizedThreadOutcome::Terminate,
+                ClientThreadState::Terminating => {
+                    self.leave_login_queue();
+                    break UnauthorizedThreadOutcome::Terminate;
+                }
 
                 /* disconnected state, wait until another tcp stream tries to recover us */
                 ClientThreadState::Disconnected => tokio::select! {
@@ -152,8 +216,13 @@ impl UnauthorizedThread {
                                 tcp_peer
                             );
 
+                            // the old key has been used up, the client gets the new one in the login success packet.
+                            // rotate it first, so that the old key can't claim the thread once the address changes
+                            self.secret_key.store(rand::thread_rng().gen(), Ordering::Relaxed);
+
                             socket.socket = stream;
                             socket.tcp_peer = tcp_peer;
+                            *self.tcp_ip.lock() = tcp_peer.ip();
 
                             if let Err(e) = self.send_login_success().await {
                                 warn!("failed to send login success: {e}");
@@ -174,12 +243,13 @@ impl UnauthorizedThread {
                     }
                 },
 
-                /* unauthorized state, wait until the user sends a handshake and a LoginPacket */
+                /* unauthorized state, wait until the user sends a handshake and a LoginPacket, or a slot if the login is queued */
                 ClientThreadState::Unauthorized => tokio::select! {
-                    x = tokio::time::timeout(TIMEOUT, self.get_socket().poll_for_tcp_data()) => match x {
+                    x = tokio::time::timeout(self.unauthorized_timeout(), self.get_socket().poll_for_tcp_data()) => match x {
                         Ok(Ok(datalen)) => match self.recv_and_handle(datalen).await {
                             Ok(()) => {}
                             Err(e) => {
+                                self.game_server.state.metrics.record_packet_error(&e);
                                 warn!("error on an unauth thread: {e}");
                                 self.terminate();
                             }
@@ -191,12 +261,24 @@ impl UnauthorizedThread {
                             self.terminate();
                         }
 
+                        Err(_) if self.login_deadline.lock().is_some_and(|x| x <= Instant::now()) => {
+                            debug!("[{}] no login after the handshake, terminating", self.get_tcp_peer());
+                            let _ = self.kick(DisconnectReason::IdleTimeout, "Timed out waiting for login.").await;
+                        }
+
                         Err(_) => {
                             // time is up, call quits
                             self.terminate();
                         }
                     },
 
+                    () = self.wait_for_login_queue(), if self.is_queued() => {
+                        if let Err(e) = self.update_login_queue().await {
+                            warn!("error on a queued thread: {e}");
+                            self.terminate();
+                        }
+                    },
+
                     () = self.wait_for_termianted() => {
                         self.terminate();
                     }
@@ -224,12 +306,12 @@ impl UnauthorizedThread {
         }
     }
 
-    pub fn claim(&self, udp_peer: SocketAddrV4) {
+    pub fn claim(&self, udp_peer: SocketAddr) {
         *self.claim_udp_peer.lock() = Some(udp_peer);
         self.claim_udp_notify.notify_one();
     }
 
-    pub fn recover(&self, tcp_stream: TcpStream, peer: SocketAddrV4) {
+    pub fn recover(&self, tcp_stream: TcpStream, peer: SocketAddr) {
         *self.recover_stream.lock() = Some((tcp_stream, peer));
         self.recover_notify.notify_one();
     }
@@ -241,27 +323,83 @@ impl UnauthorizedThread {
         socket.recv_and_handle(message_size, async |buf| self.handle_packet(buf).await).await
     }
 
+    /// Drops a packet that can't be handled before logging in, or returns `error` once there were too many of them.
+    fn add_preauth_strike(&self, error: PacketHandlingError) -> Result<()> {
+        let strikes = self.preauth_strikes.fetch_add(1, Ordering::Relaxed) + 1;
+        if strikes >= MAX_PREAUTH_STRIKES {
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
     async fn handle_packet(&self, message: &mut [u8]) -> Result<()> {
         #[cfg(debug_assertions)]
         if message.len() < PacketHeader::SIZE {
             return Err(PacketHandlingError::MalformedMessage);
         }
 
+        let header = ByteReader::from_bytes(message).read_packet_header()?;
+        let size_limit = packet_size_limit(header.packet_id).unwrap_or_else(|| self.game_server.bridge.max_packet_size());
+        if message.len() > size_limit {
+            return Err(PacketHandlingError::OversizedPacket(header.packet_id, message.len()));
+        }
+
+        let Some(len) = header.verify_checksum(message) else {
+            self.game_server.state.metrics.corrupted_packets.fetch_add(1, Ordering::Relaxed);
+            return self.add_preauth_strike(PacketHandlingError::CorruptedPacket);
+        };
+
+        self.game_server.state.metrics.packet_counts.record(header.packet_id);
+
+        let message = &mut message[..len];
         let mut data = ByteReader::from_bytes(message);
-        let header = data.read_packet_header()?;
+        data.skip(PacketHeader::SIZE);
+
+        // a queued client has already logged in, it only needs to show that it's still there
+        if self.is_queued() {
+            if header.packet_id != KeepaliveTCPPacket::PACKET_ID {
+                return self.add_preauth_strike(PacketHandlingError::NoHandler(header.packet_id));
+            }
+
+            if let Some(queued) = self.queued_login.lock().as_mut() {
+                queued.last_keepalive = Instant::now();
+            }
+
+            return self.get_socket().send_packet_static(&KeepaliveTCPResponsePacket).await;
+        }
+
+        // nothing else can be handled yet, so don't waste time decrypting it
+        if header.packet_id != CryptoHandshakeStartPacket::PACKET_ID && header.packet_id != LoginPacket::PACKET_ID {
+            return self.add_preauth_strike(PacketHandlingError::NoHandler(header.packet_id));
+        }
 
         // reject cleartext credentials
-        if header.packet_id == LoginPacket::PACKET_ID && !header.encrypted {
+        if header.packet_id == LoginPacket::PACKET_ID && !header.encrypted() {
             return Err(PacketHandlingError::MalformedLoginAttempt);
         }
 
         // decrypt the packet in-place if encrypted
-        if header.encrypted {
+        if header.encrypted() {
             data = self.get_socket().decrypt(message)?;
         }
 
+        // and then inflate it if compressed
+        let decompressed;
+        if header.compressed() {
+            decompressed = self.get_socket().decompress(&data, size_limit)?;
+            data = ByteReader::from_bytes(&decompressed);
+        }
+
         match header.packet_id {
-            CryptoHandshakeStartPacket::PACKET_ID => self.handle_crypto_handshake(&mut data).await,
+            CryptoHandshakeStartPacket::PACKET_ID => {
+                let result = self.handle_crypto_handshake(&mut data).await;
+                if result.is_err() {
+                    self.game_server.state.preauth_limiter.record_handshake_failure(self.get_tcp_peer().ip());
+                }
+
+                result
+            }
             LoginPacket::PACKET_ID => self.handle_login(&mut data).await,
             x => Err(PacketHandlingError::NoHandler(x)),
         }
@@ -272,42 +410,91 @@ impl UnauthorizedThread {
     gs_handler!(self, handle_crypto_handshake, CryptoHandshakeStartPacket, packet, {
         let socket = self.get_socket();
 
-        if packet.protocol != PROTOCOL_VERSION && packet.protocol != 0xffff {
+        // a connection only ever needs one handshake, so a second one is never legitimate
+        if socket.has_crypto_box() {
+            return Err(PacketHandlingError::WrongCryptoBoxState);
+        }
+
+        // too many handshakes from this address, drop the connection without doing any crypto work.
+        // shared networks listed in the per-IP account limit exemptions can make a lot of connections at once
+        let address = socket.tcp_peer.ip();
+        let handshake_limit = if self.game_server.state.address_limiter.is_exempt(&address) {
+            0
+        } else {
+            self.game_server.bridge.central_conf.lock().handshake_limit
+        };
+
+        if !self.game_server.state.preauth_limiter.try_handshake(address, handshake_limit) {
+            self.terminate();
+            return Ok(());
+        }
+
+        let Some(protocol) = negotiate_protocol(packet.protocol) else {
             self.terminate();
 
             socket
                 .send_packet_dynamic(&ProtocolMismatchPacket {
                     protocol: PROTOCOL_VERSION,
                     min_client_version: MIN_CLIENT_VERSION,
+                    min_protocol: MIN_PROTOCOL_VERSION,
                 })
                 .await?;
 
             return Ok(());
+        };
+
+        self.protocol.store(protocol, Ordering::Relaxed);
+
+        // the box and the response must use the same keypair, even if it gets rotated in the meantime
+        let keys = self.game_server.current_keys();
+
+        // only use the features that both sides support
+        let mut capabilities = 0;
+        if self.game_server.config.compression && packet.capabilities.compression() {
+            capabilities |= HandshakeCapabilities::COMPRESSION;
+        }
+
+        if packet.capabilities.profiles_ratelimited() {
+            capabilities |= HandshakeCapabilities::PROFILES_RATELIMITED;
         }
 
-        socket.init_crypto_box(&packet.key)?;
+        if self.game_server.config.voice_batching && packet.capabilities.voice_batching() {
+            capabilities |= HandshakeCapabilities::VOICE_BATCHING;
+        }
+
+        let capabilities = HandshakeCapabilities(capabilities);
+
+        socket.init_crypto_box(&packet.key, &keys.secret_key)?;
         socket
             .send_packet_static(&CryptoHandshakeResponsePacket {
-                key: self.game_server.public_key.clone().into(),
+                key: keys.public_key.into(),
+                protocol,
+                capabilities,
             })
-            .await
+            .await?;
+
+        socket.capabilities = capabilities;
+        self.connection_phase.set(ConnectionPhase::Unauthenticated);
+
+        if let Some(timeout) = self.game_server.config.login_timeout {
+            *self.login_deadline.lock() = Some(Instant::now() + timeout);
+        }
+
+        Ok(())
     });
 
     gs_handler!(self, handle_login, LoginPacket, packet, {
         // preemptively set the status to terminating, in case anything fails later.
         // if login was successful, change the status back at the end of the method body.
         self.terminate();
+        *self.login_deadline.lock() = None;
 
         let socket = self.get_socket();
 
-        // disconnect if server is under maintenance
-        if self.game_server.bridge.central_conf.lock().maintenance {
-            gs_disconnect!(self, "The server is currently under maintenance, please try connecting again later.");
-        }
-
         if packet.fragmentation_limit < 1300 {
             gs_disconnect!(
                 self,
+                DisconnectReason::Other,
                 &format!(
                     "The client fragmentation limit is too low ({} bytes) to be accepted",
                     packet.fragmentation_limit
@@ -322,42 +509,109 @@ impl UnauthorizedThread {
                 "Invalid account/user ID was sent ({} and {}). Please note that you must be signed into a Geometry Dash account before connecting.",
                 packet.account_id, packet.user_id
             );
-            socket.send_packet_dynamic(&LoginFailedPacket { message: &message }).await?;
+            socket
+                .send_packet_dynamic(&LoginFailedPacket {
+                    message: &message,
+                    reason: DisconnectReason::AuthFailed,
+                })
+                .await?;
             return Ok(());
         }
 
         // skip authentication if standalone
         let standalone = self.game_server.standalone;
         let player_name = if standalone {
-            packet.name
+            sanitize_player_name(packet.name.try_to_str())
         } else {
             // lets verify the given token
-            let result = {
-                self.game_server
-                    .bridge
-                    .token_issuer
-                    .lock()
-                    .validate(packet.account_id, packet.user_id, packet.token.to_str().unwrap())
+            let result = self
+                .game_server
+                .bridge
+                .validate_token(packet.account_id, packet.user_id, packet.token.to_str().unwrap(), async || {
+                    self.game_server.refresh_bootdata_logged().await
+                })
+                .await;
+
+            let verified = if result.is_ok() {
+                &self.game_server.state.metrics.central_verify_success
+            } else {
+                &self.game_server.state.metrics.central_verify_failure
             };
+            verified.fetch_add(1, Ordering::Relaxed);
 
             match result {
-                Ok(x) => InlineString::new(&x),
+                Ok(x) => sanitize_player_name(&x),
+                Err(TokenValidationFailure::Expired) => {
+                    socket
+                        .send_packet_dynamic(&LoginFailedPacket {
+                            message: "your login token has expired, please refresh it and try again",
+                            reason: DisconnectReason::TokenExpired,
+                        })
+                        .await?;
+                    return Ok(());
+                }
                 Err(err) => {
                     let mut message = FastString::new("authentication failed: ");
                     message.extend(err.error_message());
 
-                    socket.send_packet_dynamic(&LoginFailedPacket { message: &message }).await?;
+                    socket
+                        .send_packet_dynamic(&LoginFailedPacket {
+                            message: &message,
+                            reason: DisconnectReason::AuthFailed,
+                        })
+                        .await?;
                     return Ok(());
                 }
             }
         };
 
+        // don't let clients spam logins, as every attempt makes a request to the central server.
+        // this is done after validating the token, so that nobody can get someone else's account ratelimited
+        if !self
+            .game_server
+            .state
+            .login_limiter
+            .try_attempt(&self.game_server.bridge, packet.account_id, self.get_tcp_peer().ip())
+        {
+            debug!("[{}] too many login attempts (account ID {})", self.get_tcp_peer(), packet.account_id);
+            socket
+                .send_packet_dynamic(&LoginFailedPacket {
+                    message: "Too many login attempts, please slow down and try again later.",
+                    reason: DisconnectReason::Ratelimited,
+                })
+                .await?;
+            return Ok(());
+        }
+
+        // reject banned users early, without asking the central server
+        if let Some(ban) = self.game_server.state.ban_manager.get_ban(packet.account_id) {
+            socket
+                .send_packet_dynamic(&ServerBannedPacket {
+                    message: FastString::new(ban.reason.as_deref().unwrap_or("No reason given")),
+                    timestamp: ban.expiry.unwrap_or_default(),
+                })
+                .await?;
+
+            return Ok(());
+        }
+
         // check if the user is already logged in, kick the other instance
         self.game_server.check_already_logged_in(packet.account_id).await?;
 
         // fetch data from the central
         if !standalone {
-            let user_entry = match self.game_server.bridge.get_user_data(&packet.account_id.to_string()).await {
+            let cached = self.game_server.state.user_cache.get(&self.game_server.bridge, packet.account_id);
+            let fetched = match cached {
+                Some(user) => Ok(user),
+                None => self
+                    .game_server
+                    .bridge
+                    .get_user_data_with_retry(&packet.account_id.to_string())
+                    .await
+                    .inspect(|user| self.game_server.state.user_cache.insert(&self.game_server.bridge, user)),
+            };
+
+            let user_entry = match fetched {
                 Ok(user) if user.is_banned => {
                     socket
                         .send_packet_dynamic(&ServerBannedPacket {
@@ -368,21 +622,23 @@ impl UnauthorizedThread {
 
                     return Ok(());
                 }
-                Ok(user) if self.game_server.bridge.is_whitelist() && !user.is_whitelisted => {
-                    socket
-                        .send_packet_dynamic(&LoginFailedPacket {
-                            message: "This server has whitelist enabled and your account has not been allowed.",
-                        })
-                        .await?;
-
-                    return Ok(());
-                }
                 Ok(user) => user,
                 Err(err) => {
-                    let mut message = InlineString::<256>::new("failed to fetch user data: ");
+                    self.game_server.state.user_cache.invalidate(packet.account_id);
+
+                    let mut message = if err.is_transient() {
+                        InlineString::<256>::new("The central server is temporarily unavailable, please try again later. ")
+                    } else {
+                        InlineString::<256>::new("failed to fetch user data: ")
+                    };
                     message.extend_safe(&err.to_string());
 
-                    socket.send_packet_dynamic(&LoginFailedPacket { message: &message }).await?;
+                    socket
+                        .send_packet_dynamic(&LoginFailedPacket {
+                            message: &message,
+                            reason: DisconnectReason::Other,
+                        })
+                        .await?;
                     return Ok(());
                 }
             };
@@ -391,23 +647,144 @@ impl UnauthorizedThread {
             *self.user_entry.lock() = Some(user_entry);
         }
 
-        self.account_id.store(packet.account_id, Ordering::Relaxed);
-        self.game_server.state.inc_player_count(); // increment player count
+        // reject everyone besides admins if the server is under maintenance, admins can still join to check on the server
+        if self.game_server.bridge.is_maintenance() && !self.user_role.lock().as_ref().is_some_and(|role| role.admin) {
+            socket
+                .send_packet_dynamic(&LoginFailedPacket {
+                    message: "The server is currently under maintenance, please try connecting again later.",
+                    reason: DisconnectReason::Maintenance,
+                })
+                .await?;
+            return Ok(());
+        }
+
+        // admins can always join, so that they can manage the server
+        if self.game_server.bridge.is_whitelist() && !self.is_whitelisted(packet.account_id) {
+            socket
+                .send_packet_dynamic(&LoginFailedPacket {
+                    message: "This server has whitelist enabled and your account has not been allowed.",
+                    reason: DisconnectReason::NotWhitelisted,
+                })
+                .await?;
+            return Ok(());
+        }
+
+        let (max_players, login_queue_size) = {
+            let conf = self.game_server.bridge.central_conf.lock();
+            (conf.max_players, conf.login_queue_size as usize)
+        };
+
+        let login = PendingLogin {
+            account_id: packet.account_id,
+            user_id: packet.user_id,
+            name: player_name,
+            icons: packet.icons,
+            platform: packet.platform,
+            spectator: packet.spectator,
+        };
+
+        // spectators are not counted towards the limit, and admins can join a full server
+        if max_players != 0 && !packet.spectator && !self.user_role.lock().as_ref().is_some_and(|role| role.admin) {
+            let queue = &self.game_server.state.login_queue;
+
+            // once someone is waiting, everyone else gets in line behind them, even if a slot just opened
+            let must_wait = self.game_server.state.get_player_count() >= max_players || (login_queue_size != 0 && !queue.is_empty());
+
+            if must_wait {
+                if let Some(ticket) = (login_queue_size != 0).then(|| queue.join(login_queue_size)).flatten() {
+                    return self.enter_login_queue(login, ticket).await;
+                }
+
+                socket
+                    .send_packet_dynamic(&LoginFailedPacket {
+                        message: "This server is full, please try connecting to another server.",
+                        reason: DisconnectReason::ServerFull,
+                    })
+                    .await?;
+                return Ok(());
+            }
+        }
+
+        self.finish_login(login, false).await
+    });
+
+    /// Logs in a user that passed all the checks. `counted` is whether they have already been counted towards the player count,
+    /// which is the case when they got a free slot from the login queue.
+    async fn finish_login(&self, login: PendingLogin, counted: bool) -> Result<()> {
+        let socket = self.get_socket();
+
+        let (soft_max_players, max_accounts_per_ip) = {
+            let conf = self.game_server.bridge.central_conf.lock();
+            (conf.soft_max_players, conf.max_accounts_per_ip)
+        };
+
+        // someone could have logged into the same account while this login was waiting in the queue.
+        // the slot is only given back on disconnect once the account ID is set, so until then it's done here
+        if counted {
+            if let Err(e) = self.game_server.check_already_logged_in(login.account_id).await {
+                self.game_server.state.dec_player_count();
+                return Err(e.into());
+            }
+        }
+
+        if login.spectator {
+            if !self.user_role.lock().as_ref().is_some_and(|role| role.admin) {
+                socket
+                    .send_packet_dynamic(&LoginFailedPacket {
+                        message: "Only admins can join as a spectator.",
+                        reason: DisconnectReason::Other,
+                    })
+                    .await?;
+                return Ok(());
+            }
+
+            self.spectator.store(true, Ordering::Relaxed);
+        } else if !self
+            .game_server
+            .state
+            .address_limiter
+            .try_add(login.account_id, self.get_tcp_peer().ip(), max_accounts_per_ip)
+        {
+            debug!(
+                "[{}] too many accounts from the same address (account ID {})",
+                self.get_tcp_peer(),
+                login.account_id
+            );
+
+            if counted {
+                self.game_server.state.dec_player_count();
+            }
+
+            socket
+                .send_packet_dynamic(&LoginFailedPacket {
+                    message: "Too many connections from your network, please disconnect from another account first.",
+                    reason: DisconnectReason::TooManyAccounts,
+                })
+                .await?;
+            return Ok(());
+        }
+
+        self.account_id.store(login.account_id, Ordering::Relaxed);
+
+        // spectators are not counted
+        if !login.spectator && !counted {
+            self.game_server.state.inc_player_count();
+        }
 
         info!(
             "[{} ({}) @ {}] Login successful, platform: {}",
-            player_name,
-            packet.account_id,
+            login.name,
+            login.account_id,
             self.get_tcp_peer(),
-            packet.platform
+            login.platform
         );
 
         {
             let mut account_data = self.account_data.lock();
-            account_data.account_id = packet.account_id;
-            account_data.user_id = packet.user_id;
-            account_data.name = player_name;
-            account_data.icons.clone_from(&packet.icons);
+            account_data.account_id = login.account_id;
+            account_data.user_id = login.user_id;
+            account_data.name = login.name;
+            account_data.icons = login.icons.sanitized();
 
             let user_entry = self.user_entry.lock();
             if let Some(user_entry) = &*user_entry {
@@ -417,28 +794,174 @@ impl UnauthorizedThread {
             }
         };
 
+        self.game_server.state.profile_cache.invalidate(login.account_id);
+
         // add them to the global room
-        self.game_server.state.room_manager.get_global().manager.create_player(packet.account_id);
+        if !login.spectator {
+            self.game_server.state.room_manager.get_global().manager.create_player(login.account_id);
+        }
+
+        if !login.spectator && self.game_server.bridge.has_join_leave_webhook() {
+            self.game_server.bridge.queue_webhook_message(WebhookMessage::PlayerJoined(
+                self.account_data.lock().name.try_to_string(),
+                login.account_id,
+                self.game_server.state.get_player_count(),
+            ));
+        }
 
         self.send_login_success().await?;
 
+        // still let them in, but suggest going somewhere else before the server actually fills up
+        if soft_max_players != 0 && !login.spectator && self.game_server.state.get_player_count() >= soft_max_players {
+            self.get_socket()
+                .send_packet_dynamic(&ServerNoticePacket {
+                    message: FastString::new("This server is almost full, consider connecting to another server for a better experience."),
+                })
+                .await?;
+        }
+
         self.connection_state.store(ClientThreadState::Unclaimed); // as we still need ClaimThreadPacket to arrive
+        self.connection_phase.set(ConnectionPhase::Authenticated);
 
         Ok(())
-    });
+    }
+
+    async fn enter_login_queue(&self, login: PendingLogin, ticket: u64) -> Result<()> {
+        debug!(
+            "[{}] server is full, queueing login (account ID {})",
+            self.get_tcp_peer(),
+            login.account_id
+        );
+
+        *self.queued_login.lock() = Some(QueuedLogin {
+            login,
+            ticket,
+            position: 0,
+            last_keepalive: Instant::now(),
+        });
+
+        // still not logged in, just waiting
+        self.connection_state.store(ClientThreadState::Unauthorized);
+
+        // a slot could have opened up right before joining the queue
+        self.update_login_queue().await
+    }
+
+    fn is_queued(&self) -> bool {
+        self.queued_login.lock().is_some()
+    }
+
+    fn unauthorized_timeout(&self) -> Duration {
+        // queued clients send keepalives, so they don't need to wait as long as a client that is still logging in
+        if self.is_queued() {
+            return idle_timeout_for(self.keepalive_interval.load(Ordering::Relaxed));
+        }
+
+        // after the handshake, wait only until the login deadline
+        self.login_deadline
+            .lock()
+            .map_or(TIMEOUT, |deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    async fn wait_for_login_queue(&self) {
+        let _ = tokio::time::timeout(LOGIN_QUEUE_CHECK_INTERVAL, self.game_server.state.login_queue.changed()).await;
+    }
+
+    /// Lets the queued login in if it's first in line and there is a free slot, otherwise tells the client its new position.
+    async fn update_login_queue(&self) -> Result<()> {
+        let max_players = self.game_server.bridge.central_conf.lock().max_players;
+        let queue = &self.game_server.state.login_queue;
+
+        let (ticket, last_keepalive) = match self.queued_login.lock().as_ref() {
+            Some(queued) => (queued.ticket, queued.last_keepalive),
+            None => return Ok(()),
+        };
+
+        if last_keepalive.elapsed() > idle_timeout_for(self.keepalive_interval.load(Ordering::Relaxed)) {
+            debug!("[{}] queued client stopped sending keepalives", self.get_tcp_peer());
+            self.terminate();
+            return Ok(());
+        }
+
+        let promoted = queue.try_promote(ticket, || {
+            let state = &self.game_server.state;
+
+            // max players could have been disabled in the meantime
+            if max_players == 0 || state.get_player_count() < max_players {
+                state.inc_player_count();
+                true
+            } else {
+                false
+            }
+        });
+
+        if promoted {
+            let login = self.queued_login.lock().take().map(|queued| queued.login);
+            if let Some(login) = login {
+                // same as in handle_login, the state only changes back if the login succeeds
+                self.terminate();
+                return self.finish_login(login, true).await;
+            }
+
+            return Ok(());
+        }
+
+        let Some(position) = queue.position(ticket) else {
+            // removed from the queue, should not happen unless we are shutting down
+            self.terminate();
+            return Ok(());
+        };
+
+        let changed = self.queued_login.lock().as_mut().is_some_and(|queued| {
+            let changed = queued.position != position;
+            queued.position = position;
+            changed
+        });
+
+        if changed {
+            let keepalive_interval = self.game_server.bridge.keepalive_interval();
+            self.keepalive_interval.store(keepalive_interval, Ordering::Relaxed);
+
+            self.get_socket()
+                .send_packet_static(&LoginQueuedPacket {
+                    position: u32::try_from(position).unwrap_or(u32::MAX),
+                    keepalive_interval,
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn leave_login_queue(&self) {
+        let ticket = self.queued_login.lock().take().map(|queued| queued.ticket);
+        if let Some(ticket) = ticket {
+            self.game_server.state.login_queue.leave(ticket);
+        }
+    }
 
     async fn send_login_success(&self) -> Result<()> {
-        let tps = self.game_server.bridge.central_conf.lock().tps;
+        let (tps, voice_min_bitrate, voice_max_bitrate) = {
+            let conf = self.game_server.bridge.central_conf.lock();
+            (conf.tps, conf.voice_min_bitrate, conf.voice_max_bitrate)
+        };
         let all_roles = self.game_server.state.role_manager.get_all_roles();
         let special_user_data = self.account_data.lock().special_user_data.clone();
 
+        let keepalive_interval = self.game_server.bridge.keepalive_interval();
+        self.keepalive_interval.store(keepalive_interval, Ordering::Relaxed);
+
         let socket = self.get_socket();
         socket
             .send_packet_dynamic(&LoggedInPacket {
                 tps,
                 all_roles,
-                secret_key: self.secret_key,
+                secret_key: self.secret_key.load(Ordering::Relaxed),
                 special_user_data,
+                protocol: self.protocol.load(Ordering::Relaxed),
+                keepalive_interval,
+                voice_min_bitrate,
+                voice_max_bitrate,
             })
             .await
     }
@@ -465,7 +988,7 @@ impl UnauthorizedThread {
     }
 
     /// Blocks until we get notified that we got recovered and have an assigned TCP stream
-    async fn wait_for_recovered(&self) -> (TcpStream, SocketAddrV4) {
+    async fn wait_for_recovered(&self) -> (TcpStream, SocketAddr) {
         {
             let mut p = self.recover_stream.lock();
             if p.is_some() {
@@ -504,14 +1027,21 @@ impl UnauthorizedThread {
     }
 
     /// get the tcp address of the connected peer. do not call this from another clientthread
-    fn get_tcp_peer(&self) -> SocketAddrV4 {
+    fn get_tcp_peer(&self) -> SocketAddr {
         self.get_socket().tcp_peer
     }
 
+    /// whether the account is whitelisted on the central server or in the local whitelist, or is an admin
+    fn is_whitelisted(&self, account_id: i32) -> bool {
+        self.game_server.state.whitelist_manager.contains(account_id)
+            || self.user_entry.lock().as_ref().is_some_and(|user| user.is_whitelisted)
+            || self.user_role.lock().as_ref().is_some_and(|role| role.admin)
+    }
+
     /// terminate and send a message to the user with the reason
-    async fn kick(&self, message: &str) -> Result<()> {
+    async fn kick(&self, reason: DisconnectReason, message: &str) -> Result<()> {
         self.terminate();
-        self.get_socket().send_packet_dynamic(&ServerDisconnectPacket { message }).await
+        self.get_socket().send_packet_dynamic(&ServerDisconnectPacket { message, reason }).await
     }
 
     pub fn upgrade(self) -> ClientThread {
@@ -2,6 +2,14 @@
 pub const MAX_NOTICE_SIZE: usize = 224;
 /// maximum characters in a user message (156)
 pub const MAX_MESSAGE_SIZE: usize = 156;
+/// maximum size of a `VoicePacket` in bytes, including the header (4096)
+pub const MAX_VOICE_PACKET_SIZE: usize = 4096;
+/// maximum amount of friends in a `VoiceSettingsPacket` (1024)
+pub const MAX_VOICE_FRIENDS: usize = 1024;
+/// maximum amount of known profile revisions in a `RequestChangedProfilesPacket` (512)
+pub const MAX_KNOWN_PROFILES: usize = 512;
+/// amount of results kept in the leaderboard of a level (10)
+pub const LEADERBOARD_SIZE: usize = 10;
 /// amount of chars in a room id string (6)
 pub const ROOM_ID_LENGTH: usize = 6;
 
@@ -43,3 +43,41 @@ pub struct AdminGetUserStatePacket {
 pub struct AdminUpdateUserPacket {
     pub user_entry: UserEntry,
 }
+
+#[derive(Packet, Decodable)]
+#[packet(id = 19005)]
+pub struct AdminSetMaintenancePacket {
+    pub enabled: bool,
+    pub kick_players: bool,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 19006)]
+pub struct AdminGetPlayerStatsPacket {
+    pub account_id: i32,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 19007)]
+pub struct AdminSetLogLevelPacket {
+    pub level: FastString,
+    /// seconds until the previous level is restored, 0 for the default
+    pub duration: u32,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 19008)]
+pub struct AdminGetServerStatsPacket;
+
+#[derive(Packet, Decodable)]
+#[packet(id = 19009)]
+pub struct AdminFindPlayerPacket {
+    pub query: InlineString<MAX_NAME_SIZE>,
+}
+
+/// Unlike `AdminDisconnectPacket`, the player is not told why, the connection is just closed.
+#[derive(Packet, Decodable)]
+#[packet(id = 19010)]
+pub struct AdminForceDisconnectPacket {
+    pub account_id: i32,
+}
@@ -11,6 +11,7 @@ pub struct PingPacket {
 pub struct CryptoHandshakeStartPacket {
     pub protocol: u16,
     pub key: CryptoPublicKey,
+    pub capabilities: HandshakeCapabilities,
 }
 
 #[derive(Packet, Decodable)]
@@ -29,6 +30,7 @@ pub struct LoginPacket {
     pub icons: PlayerIconData,
     pub fragmentation_limit: u16,
     pub platform: InlineString<72>,
+    pub spectator: bool,
 }
 
 #[derive(Packet, Decodable)]
@@ -45,6 +47,17 @@ pub struct DisconnectPacket;
 #[packet(id = 10007)]
 pub struct KeepaliveTCPPacket;
 
+#[derive(Packet, Decodable)]
+#[packet(id = 10008)]
+pub struct KeepaliveEchoPacket {
+    /// the timestamp from the last `KeepaliveResponsePacket`
+    pub timestamp: u64,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 10009)]
+pub struct RequestServerInfoPacket;
+
 #[derive(Packet, Decodable)]
 #[packet(id = 10200)]
 pub struct ConnectionTestPacket {
@@ -28,12 +28,45 @@ pub struct PlayerMetadataPacket {
     pub data: PlayerMetadata,
 }
 
+#[derive(Packet, Decodable)]
+#[packet(id = 12005)]
+pub struct LevelStatsPacket {
+    pub best: u32, // percentage or milliseconds in platformer
+    pub platformer: bool,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 12006)]
+pub struct RequestLeaderboardPacket {
+    pub platformer: bool,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 12007)]
+pub struct RequestChangedProfilesPacket {
+    pub known: FastVec<ProfileRevision, MAX_KNOWN_PROFILES>,
+}
+
 #[derive(Packet, Decodable)]
 #[packet(id = 12010, encrypted = true)]
 pub struct VoicePacket {
     pub data: FastEncodedAudioFrame,
 }
 
+#[derive(Packet, Decodable)]
+#[packet(id = 12012)]
+pub struct VoiceMutePacket {
+    pub account_id: i32,
+    pub muted: bool,
+}
+
+#[derive(Packet, Decodable)]
+#[packet(id = 12013)]
+pub struct VoiceSettingsPacket {
+    pub friends_only: bool,
+    pub friends: FastVec<i32, MAX_VOICE_FRIENDS>,
+}
+
 #[derive(Packet, Decodable)]
 #[packet(id = 12011, encrypted = true)]
 pub struct ChatMessagePacket {
@@ -19,3 +19,11 @@ pub struct RequestLevelListPacket;
 pub struct RequestPlayerCountPacket {
     pub level_ids: FastVec<LevelId, 128>,
 }
+
+#[derive(Packet, Decodable)]
+#[packet(id = 11004)]
+pub struct RequestPlayerListPacket {
+    /// if nonzero, only players on this level are listed
+    pub level_id: LevelId,
+    pub page: u32,
+}
@@ -16,10 +16,37 @@ pub trait PacketMetadata {
     const NAME: &'static str;
 }
 
+/// set in `PacketHeader::flags` if the packet data is encrypted
+pub const PACKET_FLAG_ENCRYPTED: u8 = 1 << 0;
+/// set in `PacketHeader::flags` if the packet data is compressed with zstd (before being encrypted),
+/// only used once both sides agreed on it in the handshake
+pub const PACKET_FLAG_COMPRESSED: u8 = 1 << 1;
+/// set in `PacketHeader::flags` if the packet ends with a CRC32 of everything before it. Meant for cleartext packets,
+/// which otherwise have nothing that would catch corruption (encrypted ones are already authenticated)
+pub const PACKET_FLAG_CHECKSUM: u8 = 1 << 2;
+
+/// most bytes a packet can take on top of its data: the header, the nonce and mac if encrypted, and the checksum
+const PACKET_OVERHEAD: usize = PacketHeader::SIZE + 24 + 16 + size_of_types!(u32);
+
+/// Returns the largest size a packet with this ID can have, for the packets that are much smaller than the general
+/// maximum packet size can ever be. `None` if only the general maximum applies.
+/// Compressed packets are only sent if they got smaller, so the same limit also applies to their inflated size.
+pub const fn packet_size_limit(packet_id: u16) -> Option<usize> {
+    let data_size = match packet_id {
+        VoicePacket::PACKET_ID => return Some(MAX_VOICE_PACKET_SIZE),
+        ChatMessagePacket::PACKET_ID => size_of_types!(InlineString<MAX_MESSAGE_SIZE>),
+        PlayerDataPacket::PACKET_ID => size_of_types!(PlayerData),
+        PlayerMetadataPacket::PACKET_ID => size_of_types!(PlayerMetadata),
+        _ => return None,
+    };
+
+    Some(PACKET_OVERHEAD + data_size)
+}
+
 #[derive(Encodable, Decodable, StaticSize)]
 pub struct PacketHeader {
     pub packet_id: u16,
-    pub encrypted: bool,
+    pub flags: u8,
 }
 
 impl PacketHeader {
@@ -27,8 +54,34 @@ impl PacketHeader {
     pub const fn from_packet<P: PacketMetadata>() -> Self {
         Self {
             packet_id: P::PACKET_ID,
-            encrypted: P::ENCRYPTED,
+            flags: if P::ENCRYPTED { PACKET_FLAG_ENCRYPTED } else { 0 },
+        }
+    }
+
+    #[inline]
+    pub const fn encrypted(&self) -> bool {
+        self.flags & PACKET_FLAG_ENCRYPTED != 0
+    }
+
+    #[inline]
+    pub const fn compressed(&self) -> bool {
+        self.flags & PACKET_FLAG_COMPRESSED != 0
+    }
+
+    #[inline]
+    pub const fn checksummed(&self) -> bool {
+        self.flags & PACKET_FLAG_CHECKSUM != 0
+    }
+
+    /// Returns the length of the message without the trailing checksum, or `None` if the checksum does not match.
+    /// If the header doesn't have `PACKET_FLAG_CHECKSUM`, the whole message is returned.
+    pub fn verify_checksum(&self, message: &[u8]) -> Option<usize> {
+        if !self.checksummed() {
+            return Some(message.len());
         }
+
+        ByteReader::from_bytes(message).validate_self_checksum().ok()?;
+        Some(message.len() - size_of_types!(u32))
     }
 
     pub const SIZE: usize = Self::ENCODED_SIZE;
@@ -30,3 +30,51 @@ pub struct AdminSuccessMessagePacket<'a> {
 #[derive(Packet, Encodable, StaticSize)]
 #[packet(id = 29004, tcp = true)]
 pub struct AdminAuthFailedPacket;
+
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 29005, tcp = true)]
+pub struct AdminPlayerStatsPacket {
+    pub account_id: i32,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    /// round trip time in milliseconds, 0 if not measured yet
+    pub rtt_last: u32,
+    pub rtt_average: u32,
+    /// voice jitter in milliseconds
+    pub voice_jitter: u32,
+    /// amount of voice packets per interval bucket (<50ms, <100ms, <200ms, <400ms, <1s)
+    pub voice_intervals: Vec<u64>,
+    pub packet_counts: Vec<(u16, u64)>,
+}
+
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 29006, tcp = true)]
+pub struct AdminServerStatsPacket {
+    /// amount of open connections waiting for the crypto handshake
+    pub handshake_connections: u32,
+    /// amount of open connections that completed the handshake, but haven't logged in
+    pub unauthenticated_connections: u32,
+    /// amount of logged in connections, including spectators and players that can still reconnect
+    pub authenticated_connections: u32,
+    pub player_count: u32,
+    /// amount of received packets per packet ID since the server started, at most one entry per known packet, see `PacketCounters`
+    pub packet_counts: Vec<(u16, u64)>,
+}
+
+#[derive(Encodable, DynamicSize)]
+pub struct AdminFoundPlayer {
+    pub account_id: i32,
+    pub name: InlineString<MAX_NAME_SIZE>,
+    /// address the player sends UDP traffic from
+    pub peer: FastString,
+    /// 0 if not in a level
+    pub level_id: LevelId,
+}
+
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 29007, tcp = true)]
+pub struct AdminFoundPlayersPacket {
+    pub players: Vec<AdminFoundPlayer>,
+}
@@ -5,24 +5,32 @@ use crate::{data::*, managers::GameServerRole};
 pub struct PingResponsePacket {
     pub id: u32,
     pub player_count: u32,
+    /// how long the server has been running, in seconds
+    pub uptime: u64,
 }
 
 #[derive(Packet, Encodable, StaticSize)]
 #[packet(id = 20001, tcp = true)]
 pub struct CryptoHandshakeResponsePacket {
     pub key: CryptoPublicKey,
+    /// protocol version that both sides should use from now on
+    pub protocol: u16,
+    pub capabilities: HandshakeCapabilities,
 }
 
 #[derive(Packet, Encodable, StaticSize)]
 #[packet(id = 20002, tcp = false)]
 pub struct KeepaliveResponsePacket {
     pub player_count: u32,
+    /// milliseconds since the server started
+    pub timestamp: u64,
 }
 
 #[derive(Packet, Encodable, DynamicSize)]
 #[packet(id = 20003, tcp = true)]
 pub struct ServerDisconnectPacket<'a> {
     pub message: &'a str,
+    pub reason: DisconnectReason,
 }
 
 #[derive(Packet, Encodable, DynamicSize)]
@@ -32,12 +40,21 @@ pub struct LoggedInPacket {
     pub special_user_data: SpecialUserData,
     pub all_roles: Vec<GameServerRole>,
     pub secret_key: u32,
+    /// protocol version negotiated in the handshake, repeated here because this is the first encrypted packet
+    /// and can't be tampered with. clients must disconnect if it's not the version they requested.
+    pub protocol: u16,
+    /// seconds, see `CentralBridge::idle_timeout`
+    pub keepalive_interval: u32,
+    /// range of opus bitrates (in kbit/s) the client should encode voice with, 0 if there is no limit
+    pub voice_min_bitrate: u32,
+    pub voice_max_bitrate: u32,
 }
 
 #[derive(Packet, Encodable, DynamicSize)]
 #[packet(id = 20005, tcp = true)]
 pub struct LoginFailedPacket<'a> {
     pub message: &'a str,
+    pub reason: DisconnectReason,
 }
 
 #[derive(Packet, Encodable, DynamicSize)]
@@ -45,6 +62,8 @@ pub struct LoginFailedPacket<'a> {
 pub struct ProtocolMismatchPacket<'a> {
     pub protocol: u16,
     pub min_client_version: &'a str,
+    /// last so that clients on older protocols, which don't know about it, can still read the rest
+    pub min_protocol: u16,
 }
 
 #[derive(Packet, Encodable, StaticSize)]
@@ -59,6 +78,33 @@ pub struct ClaimThreadFailedPacket;
 #[packet(id = 20009, tcp = true)]
 pub struct LoginRecoveryFailedPacket;
 
+/// sent instead of `LoggedInPacket` when the server is full, and again whenever the position changes
+#[derive(Packet, Encodable, StaticSize)]
+#[packet(id = 20011, tcp = true)]
+pub struct LoginQueuedPacket {
+    /// starting from 1
+    pub position: u32,
+    /// same as in `LoggedInPacket`, the client is disconnected if it stops sending keepalives while queued
+    pub keepalive_interval: u32,
+}
+
+// everything a server browser needs to show the server, in a single response.
+// it is much larger than the request, so unconnected addresses only get a few per window, see `PreauthLimiter::try_server_info`
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 20010, tcp = false)]
+pub struct ServerInfoPacket<'a> {
+    pub name: &'a str,
+    pub motd: &'a str,
+    pub version: &'a str,
+    pub protocol: u16,
+    pub player_count: u32,
+    /// 0 if there is no limit
+    pub max_players: u32,
+    /// how long the server has been running, in seconds
+    pub uptime: u64,
+    pub maintenance: bool,
+}
+
 // used to communicate a simple message to the user
 #[derive(Packet, Encodable, DynamicSize, Clone)]
 #[packet(id = 20100, tcp = false)]
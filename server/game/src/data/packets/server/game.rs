@@ -18,6 +18,43 @@ pub struct LevelPlayerMetadataPacket {
     pub players: Vec<AssociatedPlayerMetadata>,
 }
 
+#[derive(Clone, Packet, Encodable, StaticSize)]
+#[packet(id = 22003, tcp = true)]
+pub struct LevelPlayerJoinedPacket {
+    pub account_id: i32,
+}
+
+#[derive(Clone, Packet, Encodable, StaticSize)]
+#[packet(id = 22004, tcp = true)]
+pub struct LevelPlayerLeftPacket {
+    pub account_id: i32,
+}
+
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 22005, tcp = true)]
+pub struct LevelLeaderboardPacket {
+    pub level_id: LevelId,
+    pub platformer: bool,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// Response to `RequestChangedProfilesPacket`, has the current revision of everyone on the level,
+/// but only the profiles that the client does not have the current revision of.
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 22006, tcp = true)]
+pub struct ChangedPlayerProfilesPacket {
+    pub revisions: Vec<ProfileRevision>,
+    pub players: Vec<PlayerAccountData>,
+}
+
+/// Sent instead of a response to a profile request when the client makes too many of them.
+#[derive(Packet, Encodable, StaticSize)]
+#[packet(id = 22007, tcp = true)]
+pub struct ProfilesRatelimitedPacket {
+    /// in milliseconds, how long until the next request will be answered
+    pub retry_after: u32,
+}
+
 #[derive(Packet, Encodable, DynamicSize)]
 #[packet(id = 22010, encrypted = true, tcp = false)]
 pub struct VoiceBroadcastPacket {
@@ -25,6 +62,33 @@ pub struct VoiceBroadcastPacket {
     pub data: FastEncodedAudioFrame,
 }
 
+/// A `VoiceBroadcastPacket` that has already been encoded, so that broadcasting it to many people only encodes it once.
+/// Encryption still has to be done separately for every recipient.
+pub struct EncodedVoiceBroadcastPacket {
+    pub player_id: i32,
+    pub data: Vec<u8>,
+}
+
+impl EncodedVoiceBroadcastPacket {
+    pub fn new(packet: &VoiceBroadcastPacket) -> Self {
+        let mut buf = ByteBuffer::with_capacity(packet.encoded_size());
+        buf.write_value(packet);
+
+        Self {
+            player_id: packet.player_id,
+            data: buf.into_vec(),
+        }
+    }
+}
+
+/// Multiple voice packets coalesced into a single datagram, only sent when voice batching is enabled and the client supports it.
+/// Every element is an encoded `VoiceBroadcastPacket` (without the header), prefixed with its length.
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 22012, encrypted = true, tcp = false)]
+pub struct VoiceBroadcastBatchPacket {
+    pub packets: Vec<Vec<u8>>,
+}
+
 #[derive(Clone, Packet, Encodable, StaticSize)]
 #[packet(id = 22011, encrypted = true, tcp = false)]
 pub struct ChatMessageBroadcastPacket {
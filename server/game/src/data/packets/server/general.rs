@@ -27,3 +27,11 @@ pub struct LevelPlayerCountPacket {
 pub struct RolesUpdatedPacket {
     pub special_user_data: SpecialUserData,
 }
+
+#[derive(Packet, Encodable, DynamicSize)]
+#[packet(id = 21004, tcp = true)]
+pub struct PlayerListPacket {
+    pub page: u32,
+    pub total_players: u32,
+    pub players: Vec<PlayerPreviewAccountData>,
+}
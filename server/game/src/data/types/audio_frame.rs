@@ -1,6 +1,10 @@
 use crate::data::*;
 
 const VOICE_MAX_FRAMES_IN_AUDIO_FRAME: usize = 10;
+/// the largest possible size of a single opus packet
+const VOICE_MAX_OPUS_FRAME_SIZE: usize = 1275;
+/// opus frame durations are always expressed in samples at this rate, regardless of the actual sample rate
+const OPUS_SAMPLE_RATE: u64 = 48_000;
 
 type EncodedOpusData = Vec<u8>;
 
@@ -9,8 +13,107 @@ pub struct EncodedAudioFrame {
     pub opus_frames: [Option<EncodedOpusData>; VOICE_MAX_FRAMES_IN_AUDIO_FRAME],
 }
 
+/// Why an audio frame was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceFrameError {
+    /// the data doesn't have the structure of an `EncodedAudioFrame`
+    Malformed,
+    /// the data is fine, but has more opus frames than the server allows
+    TooManyFrames(usize),
+}
+
 /// `FastEncodedAudioFrame` requires just one heap allocation as opposed to 10.
 #[derive(Clone, Encodable, Decodable, DynamicSize)]
 pub struct FastEncodedAudioFrame {
     pub data: RemainderBytes,
 }
+
+impl FastEncodedAudioFrame {
+    /// Checks that the data has the same structure as an `EncodedAudioFrame`, without allocating.
+    /// Every present opus frame must be non-empty and no larger than what opus can produce, and there must be no trailing data.
+    pub fn is_valid(&self) -> bool {
+        self.scan().is_some()
+    }
+
+    /// Returns the average size of the opus frames in this audio frame (0 if there are none). Opus produces tiny frames for silence,
+    /// so this is a cheap way to tell whether someone is actually talking.
+    pub fn average_frame_size(&self, max_frames: usize) -> Result<usize, VoiceFrameError> {
+        let (frames, total_size) = self.scan().ok_or(VoiceFrameError::Malformed)?;
+
+        if frames > max_frames {
+            return Err(VoiceFrameError::TooManyFrames(frames));
+        }
+
+        Ok(total_size.checked_div(frames).unwrap_or(0))
+    }
+
+    /// Returns the bitrate of the opus frames in this audio frame in bits per second, or `None` if the data is invalid.
+    /// Frames claiming to contain no audio at all count as an infinite bitrate.
+    pub fn bitrate(&self) -> Option<u32> {
+        let mut samples = 0;
+        let (_, total_size) = scan_opus_frames(&self.data, |frame| samples += opus_packet_samples(frame))?;
+
+        if total_size == 0 {
+            return Some(0);
+        }
+
+        let bitrate = (total_size as u64 * 8 * OPUS_SAMPLE_RATE).checked_div(samples).unwrap_or(u64::MAX);
+        Some(u32::try_from(bitrate).unwrap_or(u32::MAX))
+    }
+
+    /// Walks through the data, returning the amount of present opus frames and their total size, or `None` if the data is invalid.
+    fn scan(&self) -> Option<(usize, usize)> {
+        scan_opus_frames(&self.data, |_| {})
+    }
+}
+
+/// Walks through the data of an encoded audio frame, calling `on_frame` with every present opus frame.
+/// Returns the amount of opus frames and their total size, or `None` if the data is invalid.
+pub fn scan_opus_frames(data: &[u8], mut on_frame: impl FnMut(&[u8])) -> Option<(usize, usize)> {
+    let mut reader = ByteReader::from_bytes(data);
+    let mut frames = 0;
+    let mut total_size = 0;
+
+    for _ in 0..VOICE_MAX_FRAMES_IN_AUDIO_FRAME {
+        if !reader.read_bool().ok()? {
+            continue;
+        }
+
+        let length = reader.read_length().ok()?;
+        let start = reader.get_rpos();
+
+        if length == 0 || length > VOICE_MAX_OPUS_FRAME_SIZE || start + length > reader.len() {
+            return None;
+        }
+
+        on_frame(&data[start..start + length]);
+
+        reader.skip(length);
+        frames += 1;
+        total_size += length;
+    }
+
+    (reader.get_rpos() == reader.len()).then_some((frames, total_size))
+}
+
+/// Returns the amount of 48 kHz samples in an opus packet, based on its TOC byte.
+pub fn opus_packet_samples(packet: &[u8]) -> u64 {
+    let Some(&toc) = packet.first() else {
+        return 0;
+    };
+
+    let config = usize::from(toc >> 3);
+    let frame_size = match config {
+        0..=11 => [480, 960, 1920, 2880][config % 4],
+        12..=15 => [480, 960][config % 2],
+        _ => [120, 240, 480, 960][config % 4],
+    };
+
+    let frame_count = match toc & 0x3 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => packet.get(1).map_or(0, |x| u64::from(x & 0x3f)),
+    };
+
+    frame_size * frame_count
+}
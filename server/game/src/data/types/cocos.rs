@@ -91,3 +91,11 @@ pub struct Point {
     pub x: FiniteF32,
     pub y: FiniteF32,
 }
+
+impl Point {
+    pub fn distance_squared(&self, other: &Point) -> f32 {
+        let dx = self.x.get() - other.x.get();
+        let dy = self.y.get() - other.y.get();
+        dx * dx + dy * dy
+    }
+}
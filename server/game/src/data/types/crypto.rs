@@ -24,3 +24,45 @@ decode_impl!(CryptoPublicKey, buf, {
 
 static_size_calc_impl!(CryptoPublicKey, KEY_SIZE);
 dynamic_size_calc_impl!(CryptoPublicKey, self, KEY_SIZE);
+
+/// Optional features exchanged in the handshake. The client sends the ones it supports,
+/// and the server responds with the ones that will actually be used.
+#[derive(Clone, Copy, Default)]
+pub struct HandshakeCapabilities(pub u8);
+
+impl HandshakeCapabilities {
+    /// packets above a size threshold may be compressed with zstd, see `PACKET_FLAG_COMPRESSED`
+    pub const COMPRESSION: u8 = 1 << 0;
+    /// the client understands `ProfilesRatelimitedPacket`, otherwise ratelimited profile requests get no response
+    pub const PROFILES_RATELIMITED: u8 = 1 << 1;
+    /// the client understands `VoiceBroadcastBatchPacket`, otherwise voice packets are always sent one by one
+    pub const VOICE_BATCHING: u8 = 1 << 2;
+
+    pub const fn compression(self) -> bool {
+        self.0 & Self::COMPRESSION != 0
+    }
+
+    pub const fn profiles_ratelimited(self) -> bool {
+        self.0 & Self::PROFILES_RATELIMITED != 0
+    }
+
+    pub const fn voice_batching(self) -> bool {
+        self.0 & Self::VOICE_BATCHING != 0
+    }
+}
+
+encode_impl!(HandshakeCapabilities, buf, self, {
+    buf.write_u8(self.0);
+});
+
+decode_impl!(HandshakeCapabilities, buf, {
+    // older clients don't send this at all
+    if buf.get_rpos() >= buf.len() {
+        return Ok(Self::default());
+    }
+
+    Ok(Self(buf.read_u8()?))
+});
+
+static_size_calc_impl!(HandshakeCapabilities, size_of_types!(u8));
+dynamic_size_calc_impl!(HandshakeCapabilities, self, size_of_types!(u8));
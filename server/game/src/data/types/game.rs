@@ -48,6 +48,16 @@ pub struct PlayerMetadata {
     pub attempts: i32,
 }
 
+/* LeaderboardEntry (best result of a player on a level) */
+
+#[derive(Clone, Encodable, StaticSize, DynamicSize)]
+#[dynamic_size(as_static = true)]
+pub struct LeaderboardEntry {
+    pub account_id: i32,
+    pub name: InlineString<MAX_NAME_SIZE>,
+    pub best: u32, // percentage or milliseconds in platformer
+}
+
 /* PlayerData (data in a level) */
 // 45 bytes best-case, 77 bytes worst-case (with 2 spider teleports).
 
@@ -4,6 +4,24 @@ use crate::{data::*, managers::RoleManager};
 
 pub const NO_GLOW: u8 = u8::MAX;
 
+/// highest icon ids that exist in the game, anything above that can't be rendered by clients
+const MAX_CUBE: i16 = 485;
+const MAX_SHIP: i16 = 169;
+const MAX_BALL: i16 = 118;
+const MAX_UFO: i16 = 149;
+const MAX_WAVE: i16 = 96;
+const MAX_ROBOT: i16 = 68;
+const MAX_SPIDER: i16 = 69;
+const MAX_SWING: i16 = 43;
+const MAX_JETPACK: i16 = 8;
+const MAX_DEATH_EFFECT: u8 = 20;
+const MAX_COLOR: u8 = 106;
+const MAX_STREAK: u8 = 7;
+const MAX_SHIP_STREAK: u8 = 6;
+
+/// name used if a name has no valid characters at all
+const FALLBACK_NAME: &str = "Player";
+
 #[derive(Clone, Encodable, Decodable, StaticSize, DynamicSize)]
 #[dynamic_size(as_static = true)]
 pub struct PlayerIconData {
@@ -48,7 +66,75 @@ impl Default for PlayerIconData {
 
 impl PlayerIconData {
     pub const fn is_valid(&self) -> bool {
-        true
+        const fn icon(id: i16, max: i16) -> bool {
+            id >= 1 && id <= max
+        }
+
+        const fn color(id: u8) -> bool {
+            id <= MAX_COLOR
+        }
+
+        icon(self.cube, MAX_CUBE)
+            && icon(self.ship, MAX_SHIP)
+            && icon(self.ball, MAX_BALL)
+            && icon(self.ufo, MAX_UFO)
+            && icon(self.wave, MAX_WAVE)
+            && icon(self.robot, MAX_ROBOT)
+            && icon(self.spider, MAX_SPIDER)
+            && icon(self.swing, MAX_SWING)
+            && icon(self.jetpack, MAX_JETPACK)
+            && self.death_effect >= 1
+            && self.death_effect <= MAX_DEATH_EFFECT
+            && color(self.color1)
+            && color(self.color2)
+            && (self.glow_color == NO_GLOW || color(self.glow_color))
+            && self.streak >= 1
+            && self.streak <= MAX_STREAK
+            && self.ship_streak >= 1
+            && self.ship_streak <= MAX_SHIP_STREAK
+    }
+
+    /// Returns a copy of these icons where every out of range value is replaced with its default.
+    pub fn sanitized(&self) -> Self {
+        fn icon(id: i16, max: i16, default: i16) -> i16 {
+            if (1..=max).contains(&id) {
+                id
+            } else {
+                default
+            }
+        }
+
+        fn value(id: u8, range: std::ops::RangeInclusive<u8>, default: u8) -> u8 {
+            if range.contains(&id) {
+                id
+            } else {
+                default
+            }
+        }
+
+        let default = Self::default();
+
+        Self {
+            cube: icon(self.cube, MAX_CUBE, default.cube),
+            ship: icon(self.ship, MAX_SHIP, default.ship),
+            ball: icon(self.ball, MAX_BALL, default.ball),
+            ufo: icon(self.ufo, MAX_UFO, default.ufo),
+            wave: icon(self.wave, MAX_WAVE, default.wave),
+            robot: icon(self.robot, MAX_ROBOT, default.robot),
+            spider: icon(self.spider, MAX_SPIDER, default.spider),
+            swing: icon(self.swing, MAX_SWING, default.swing),
+            jetpack: icon(self.jetpack, MAX_JETPACK, default.jetpack),
+            death_effect: value(self.death_effect, 1..=MAX_DEATH_EFFECT, default.death_effect),
+            color1: value(self.color1, 0..=MAX_COLOR, default.color1),
+            color2: value(self.color2, 0..=MAX_COLOR, default.color2),
+            glow_color: if self.glow_color == NO_GLOW {
+                NO_GLOW
+            } else {
+                value(self.glow_color, 0..=MAX_COLOR, default.glow_color)
+            },
+            streak: value(self.streak, 1..=MAX_STREAK, default.streak),
+            ship_streak: value(self.ship_streak, 1..=MAX_SHIP_STREAK, default.ship_streak),
+        }
     }
 
     pub fn to_simple(&self) -> PlayerIconDataSimple {
@@ -100,6 +186,34 @@ impl SpecialUserData {
     }
 }
 
+/// Makes a name safe to show to other players: control characters are removed, surrounding whitespace is trimmed
+/// and it gets cut off (on a character boundary) if it's too long. Names with nothing left are replaced with a placeholder.
+pub fn sanitize_player_name(name: &str) -> InlineString<MAX_NAME_SIZE> {
+    // the last byte of an `InlineString` stores its length
+    let capacity = InlineString::<MAX_NAME_SIZE>::capacity() - 1;
+
+    let mut sanitized = String::with_capacity(capacity);
+    for c in name.trim().chars().filter(|c| !c.is_control()) {
+        if sanitized.len() + c.len_utf8() > capacity {
+            break;
+        }
+
+        sanitized.push(c);
+    }
+
+    let sanitized = sanitized.trim();
+    InlineString::new(if sanitized.is_empty() { FALLBACK_NAME } else { sanitized })
+}
+
+/* ProfileRevision */
+
+#[derive(Clone, Copy, Default, Encodable, Decodable, StaticSize, DynamicSize)]
+#[dynamic_size(as_static = true)]
+pub struct ProfileRevision {
+    pub account_id: i32,
+    pub revision: u32,
+}
+
 /* PlayerAccountData */
 
 #[derive(Clone, Default, Encodable, Decodable, StaticSize, DynamicSize)]
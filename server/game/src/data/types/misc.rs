@@ -50,3 +50,32 @@ impl CustomErrorMessage {
         }
     }
 }
+
+/// Why the client is being disconnected or why the login failed, so that the client can react to it
+/// without having to parse the message, which is only meant for humans.
+#[derive(Default, Debug, Copy, Clone, Encodable, StaticSize, DynamicSize)]
+#[dynamic_size(as_static = true)]
+#[repr(u8)]
+pub enum DisconnectReason {
+    #[default]
+    Other = 0,
+    /// normally `ProtocolMismatchPacket` is sent instead
+    ProtocolMismatch = 1,
+    /// normally `ServerBannedPacket` is sent instead
+    Banned = 2,
+    Kicked = 3,
+    ServerFull = 4,
+    Maintenance = 5,
+    AuthFailed = 6,
+    IdleTimeout = 7,
+    /// someone logged into the same account from a different place
+    DuplicateLogin = 8,
+    /// too many login attempts in a short time
+    Ratelimited = 9,
+    /// the server has a whitelist and the account is not on it
+    NotWhitelisted = 10,
+    /// the login token is older than the token expiry period, the client should get a new one
+    TokenExpired = 11,
+    /// the address already has as many accounts logged in as the server allows
+    TooManyAccounts = 12,
+}
@@ -0,0 +1,83 @@
+use std::net::SocketAddr;
+
+use globed_shared::{debug, info, warn};
+
+use crate::{
+    metrics::{ACCEPT_ERROR_BACKOFF, HTTP_REQUEST_TIMEOUT},
+    server::GameServer,
+    tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    },
+};
+
+/// Serves a health check on the given address, meant for orchestration tools (load balancers, container health checks).
+/// Responds with 200 and a small JSON body while healthy, or with 503 if the central server cannot be reached.
+/// Every request gets the same response, regardless of the path.
+pub async fn serve(game_server: &'static GameServer, address: SocketAddr) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(x) => x,
+        Err(err) => {
+            warn!("failed to bind the health check endpoint to {address}: {err}");
+            return;
+        }
+    };
+
+    info!("Health check is available on http://{address}/health");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(x) => x,
+            Err(err) => {
+                warn!("failed to accept a health check connection: {err}");
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            match tokio::time::timeout(HTTP_REQUEST_TIMEOUT, respond(game_server, stream)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => debug!("failed to serve health check to {peer}: {err}"),
+                Err(_) => debug!("health check request from {peer} timed out"),
+            }
+        });
+    }
+}
+
+async fn respond(game_server: &'static GameServer, mut stream: TcpStream) -> std::io::Result<()> {
+    // same as with metrics, the request itself doesn't matter
+    let mut buf = [0u8; 1024];
+    let _ = stream.read(&mut buf).await?;
+
+    // a standalone server never talks to a central server, so it can't be in a failed state
+    let central_reachable = game_server.standalone || game_server.bridge.is_central_reachable();
+
+    let player_count = game_server.state.get_player_count();
+    let (max_players, soft_max_players) = {
+        let conf = game_server.bridge.central_conf.lock();
+        (conf.max_players, conf.soft_max_players)
+    };
+
+    let body = serde_json::json!({
+        "player_count": player_count,
+        "max_players": max_players,
+        // lets a load balancer or the server browser send new players elsewhere before the server is actually full
+        "almost_full": soft_max_players != 0 && player_count >= soft_max_players,
+        "full": max_players != 0 && player_count >= max_players,
+        "uptime": game_server.uptime(),
+        "central_reachable": central_reachable,
+    })
+    .to_string();
+
+    let status = if central_reachable { "200 OK" } else { "503 Service Unavailable" };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -1,4 +1,4 @@
-#![feature(sync_unsafe_cell, duration_constructors, async_closure)]
+#![feature(sync_unsafe_cell, duration_constructors, async_closure, async_trait_bounds)]
 #![allow(
     clippy::must_use_candidate,
     clippy::module_name_repetitions,
@@ -10,13 +10,17 @@
     clippy::redundant_closure_for_method_calls
 )]
 
+pub mod audit;
 pub mod bridge;
 pub mod client;
 pub mod data;
+pub mod health;
 pub mod managers;
+pub mod metrics;
 pub mod server;
 pub mod state;
 pub mod util;
+pub mod voice_recorder;
 pub mod webhook;
 
 #[cfg(feature = "use_tokio_tracing")]
@@ -1,4 +1,4 @@
-#![feature(sync_unsafe_cell, duration_constructors, async_closure, iter_collect_into)]
+#![feature(sync_unsafe_cell, duration_constructors, async_closure, async_trait_bounds, iter_collect_into)]
 #![allow(
     clippy::must_use_candidate,
     clippy::module_name_repetitions,
@@ -19,33 +19,67 @@ use tokio;
 
 use std::{
     error::Error,
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 
-use bridge::{CentralBridge, CentralBridgeError};
-use globed_shared::{log::Log, *};
+use bridge::{CentralBridge, CentralBridgeError, CentralServer, CentralTlsConfig};
+use globed_shared::*;
 use reqwest::StatusCode;
+use serde::Deserialize;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
 use state::ServerState;
 use tokio::{
     fs::File,
     io::AsyncReadExt,
     net::{TcpListener, UdpSocket},
+    sync::Notify,
 };
 
-use server::GameServer;
+use server::{
+    GameServer, GameServerConfiguration, DEFAULT_LOGIN_TIMEOUT, DEFAULT_MESSAGE_QUEUE_CAPACITY, DEFAULT_SLOW_HANDLER_THRESHOLD,
+    DEFAULT_VOICE_STRIKE_LIMIT,
+};
+use voice_recorder::VoiceRecordingConfig;
 
+pub mod audit;
 pub mod bridge;
 pub mod client;
 pub mod data;
+pub mod health;
 pub mod managers;
+pub mod metrics;
 pub mod server;
 pub mod state;
 pub mod util;
+pub mod voice_recorder;
 pub mod webhook;
 
+/// how many rotated log files are kept, if `GLOBED_GS_LOG_MAX_FILES` is not set
+const DEFAULT_MAX_OLD_LOG_FILES: usize = 5;
+/// port of the metrics endpoint, if `GLOBED_GS_METRICS_ADDR` doesn't specify one
+const DEFAULT_METRICS_PORT: u16 = 9100;
+const DEFAULT_HEALTH_PORT: u16 = 8080;
+/// how long voice recordings are kept, in hours
+const DEFAULT_VOICE_RECORDING_RETENTION: u64 = 72;
+/// where admin actions are recorded, if `GLOBED_GS_AUDIT_LOG` is not set
+const DEFAULT_AUDIT_LOG: &str = "globed_game_server_audit.log";
+/// how many times fetching the configuration is retried on startup, while the central server can't be reached or is still starting
+const DEFAULT_STARTUP_RETRIES: u32 = 12;
+/// seconds between the startup retries
+const DEFAULT_STARTUP_RETRY_DELAY: u64 = 5;
+/// how many times binding a socket is attempted while its address is still in use, for example by the process that is being restarted
+const BIND_ATTEMPTS: u32 = 5;
+const BIND_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 struct StartupConfiguration {
     bind_address: SocketAddr,
-    central_data: Option<(String, String)>,
+    udp_bind_address: SocketAddr,
+    central_data: Option<Vec<CentralServer>>,
 }
 
 fn abort_misconfig() -> ! {
@@ -53,92 +87,543 @@ fn abort_misconfig() -> ! {
     std::process::exit(1);
 }
 
-fn censor_key(key: &str, keep_first_n_chars: usize) -> String {
-    if key.len() <= keep_first_n_chars {
-        return "*".repeat(key.len());
-    }
+/// Returns `true` if the value is a non-zero number or `true`.
+fn parse_flag(value: &str) -> bool {
+    value.parse::<i32>().map_or_else(|_| value.eq_ignore_ascii_case("true"), |n| n != 0)
+}
 
-    format!("{}{}", &key[..keep_first_n_chars], "*".repeat(key.len() - keep_first_n_chars))
+/// Reads a setting from the environment variable `name`, falling back to `file_value` from the config file, and logs which one was used.
+/// `parse` returns `None` for an invalid value, which is then ignored with a warning.
+fn setting_with<T: Display>(name: &str, file_value: Option<T>, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+    let env_value = std::env::var(name).ok().filter(|x| !x.is_empty()).and_then(|x| {
+        let value = parse(&x);
+        if value.is_none() {
+            warn!("ignoring invalid value for the environment variable '{name}': {x}");
+        }
+
+        value
+    });
+
+    let (value, source) = match (env_value, file_value) {
+        (Some(value), _) => (value, "environment"),
+        (None, Some(value)) => (value, "config file"),
+        (None, None) => {
+            debug!("{name} is not set, using the default");
+            return None;
+        }
+    };
+
+    info!("{name} = {value} (from the {source})");
+    Some(value)
+}
+
+/// Like `setting_with`, for any value that can be parsed from a string.
+fn setting<T: FromStr + Display>(name: &str, file_value: Option<T>) -> Option<T> {
+    setting_with(name, file_value, |x| x.parse().ok())
 }
 
-fn parse_configuration() -> StartupConfiguration {
-    let mut args = std::env::args();
+/// Like `setting_with`, for a flag that is disabled by default. The environment variable can be a number or `true`.
+fn flag_setting(name: &str, file_value: Option<bool>) -> bool {
+    setting_with(name, file_value, |x| Some(parse_flag(x))).unwrap_or(false)
+}
 
-    let exe_name = args.next().unwrap(); // skip executable
-    let arg = args.next();
+/// Reads the ID sent to the central server on boot, aborting if it has characters that would need escaping in a URL.
+fn server_id(file_config: &FileConfiguration) -> Option<String> {
+    let id = setting::<String>("GLOBED_GS_SERVER_ID", file_config.server_id.clone())?;
 
-    let env_addr = std::env::var("GLOBED_GS_ADDRESS");
-    let using_env_variables: bool = env_addr.is_ok();
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        error!("invalid game server ID: {id}");
+        warn!("hint: the ID can only contain letters, digits, '-', '_' and '.'");
+        abort_misconfig();
+    }
 
-    if arg.is_none() && !using_env_variables {
-        // standalone with default params
-        return StartupConfiguration {
-            bind_address: format!("0.0.0.0:{DEFAULT_GAME_SERVER_PORT}").parse().unwrap(),
-            central_data: None,
+    Some(id)
+}
+
+/// Builds the TLS settings for requests to the central server, aborting if they are invalid.
+fn central_tls_config(file_config: &FileConfiguration) -> CentralTlsConfig {
+    let mut config = CentralTlsConfig {
+        pin_certificates: flag_setting("GLOBED_GS_CENTRAL_CA_PIN", file_config.central_ca_pin),
+        insecure: flag_setting("GLOBED_GS_CENTRAL_INSECURE_TLS", file_config.central_insecure_tls),
+        ..Default::default()
+    };
+
+    if let Some(path) = setting::<String>("GLOBED_GS_CENTRAL_CA", file_config.central_ca.clone()) {
+        let pem = match std::fs::read(&path) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("failed to read the central server CA certificate from {path}: {e}");
+                abort_misconfig();
+            }
+        };
+
+        config.root_certificates = match reqwest::Certificate::from_pem_bundle(&pem) {
+            Ok(x) if !x.is_empty() => x,
+            Ok(_) => {
+                error!("no certificates found in {path}");
+                warn!("hint: the file must contain one or more PEM encoded certificates");
+                abort_misconfig();
+            }
+            Err(e) => {
+                error!("failed to parse the central server CA certificate from {path}: {e}");
+                abort_misconfig();
+            }
         };
+
+        info!(
+            "Trusting {} additional root certificate(s) for the central server",
+            config.root_certificates.len()
+        );
+    }
+
+    if config.pin_certificates {
+        if config.root_certificates.is_empty() {
+            error!("GLOBED_GS_CENTRAL_CA_PIN is enabled, but no certificate was provided with GLOBED_GS_CENTRAL_CA");
+            abort_misconfig();
+        }
+
+        info!("Only the certificates from GLOBED_GS_CENTRAL_CA are trusted for the central server");
+    }
+
+    if config.insecure {
+        warn!("==================================================================");
+        warn!("TLS certificate verification for the central server is DISABLED!");
+        warn!("Anyone between this server and the central server can read and");
+        warn!("modify the traffic, including the central server password.");
+        warn!("Only use GLOBED_GS_CENTRAL_INSECURE_TLS for local development.");
+        warn!("==================================================================");
+    }
+
+    config
+}
+
+/// Reads the voice recording settings, recording is only enabled if a directory is set.
+fn voice_recording_config(file_config: &FileConfiguration) -> Option<VoiceRecordingConfig> {
+    let directory = setting::<String>("GLOBED_GS_VOICE_RECORDING_DIR", file_config.voice_recording_dir.clone())?;
+
+    let levels = setting::<String>("GLOBED_GS_VOICE_RECORDING_LEVELS", file_config.voice_recording_levels.clone()).map(|x| {
+        x.split(',')
+            .map(|id| {
+                id.trim().parse().unwrap_or_else(|_| {
+                    error!("invalid level ID in GLOBED_GS_VOICE_RECORDING_LEVELS: '{id}'");
+                    abort_misconfig();
+                })
+            })
+            .collect()
+    });
+
+    let retention_hours = setting("GLOBED_GS_VOICE_RECORDING_RETENTION", file_config.voice_recording_retention)
+        .filter(|x| *x != 0)
+        .unwrap_or(DEFAULT_VOICE_RECORDING_RETENTION);
+
+    warn!("Voice recording is enabled, received voice chat is saved to {directory} for {retention_hours} hours");
+    warn!("Make sure that the players on this server know that their voice is being recorded.");
+
+    Some(VoiceRecordingConfig {
+        directory: directory.into(),
+        levels,
+        retention: Duration::from_hours(retention_hours),
+    })
+}
+
+/// Whether a failed request for the configuration should be retried on startup, because the central server may just not be up yet.
+fn is_startup_retryable(err: &CentralBridgeError) -> bool {
+    match err {
+        CentralBridgeError::RequestError(err) => err.is_connect() || err.is_timeout(),
+        CentralBridgeError::CentralError((code, _)) => *code == StatusCode::SERVICE_UNAVAILABLE,
+        _ => false,
+    }
+}
+
+/// Fetches the configuration from the central server, waiting for it to come up if it can't be reached or responds with 503.
+async fn request_startup_boot_data(bridge: &CentralBridge, file_config: &FileConfiguration) -> bridge::Result<GameServerBootData> {
+    let max_retries = setting("GLOBED_GS_STARTUP_RETRIES", file_config.startup_retries).unwrap_or(DEFAULT_STARTUP_RETRIES);
+
+    let delay = setting("GLOBED_GS_STARTUP_RETRY_DELAY", file_config.startup_retry_delay)
+        .map_or(Duration::from_secs(DEFAULT_STARTUP_RETRY_DELAY), Duration::from_secs);
+
+    let mut retries = 0;
+
+    loop {
+        match bridge.request_boot_data().await {
+            Err(err) if retries < max_retries && is_startup_retryable(&err) => {
+                retries += 1;
+                warn!(
+                    "the central server is not available yet ({err}), retrying in {} seconds ({retries}/{max_retries})",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => break result,
+        }
+    }
+}
+
+/// Reads a buffer size setting like `setting`, but aborts if the environment variable is set to something invalid.
+fn buffer_size_setting(name: &str, file_value: Option<usize>) -> Option<usize> {
+    setting_with(name, file_value, |value| {
+        if let Ok(x) = value.parse::<usize>() {
+            Some(x)
+        } else {
+            error!("invalid value for the environment variable '{name}': {value}");
+            warn!("hint: the value must be a size in bytes, for example 4194304 for 4 MiB");
+            abort_misconfig();
+        }
+    })
+}
+
+/// Creates a nonblocking socket bound to the given address, setting `SO_REUSEPORT` if `reuse_port` is true. For the TCP listener,
+/// `SO_REUSEADDR` is set as well so that a restarted server doesn't have to wait for the connections of the old one to leave `TIME_WAIT`.
+fn bind_socket(address: SocketAddr, ty: Type, protocol: Protocol, reuse_port: bool) -> std::io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(address), ty, Some(protocol))?;
+
+    // UDP has no TIME_WAIT, and there SO_REUSEADDR would let a second server silently share the port with us.
+    // on windows, it would let another process bind to the same port even for TCP
+    #[cfg(unix)]
+    if protocol == Protocol::TCP {
+        socket.set_reuse_address(true)?;
+    }
+
+    if reuse_port {
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true)?;
+    }
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+
+    Ok(socket)
+}
+
+/// Like `bind_socket`, but retries for a few seconds if the address is in use.
+async fn bind_socket_with_retry(address: SocketAddr, ty: Type, protocol: Protocol, reuse_port: bool) -> std::io::Result<Socket> {
+    let mut attempt = 1;
+
+    loop {
+        match bind_socket(address, ty, protocol, reuse_port) {
+            Err(err) if err.kind() == std::io::ErrorKind::AddrInUse && attempt < BIND_ATTEMPTS => {
+                warn!("address {address} is still in use, retrying in {BIND_RETRY_DELAY:?} ({attempt}/{BIND_ATTEMPTS})");
+                attempt += 1;
+                tokio::time::sleep(BIND_RETRY_DELAY).await;
+            }
+            result => break result,
+        }
+    }
+}
+
+fn configure_udp_buffers(udp_socket: &UdpSocket, file_config: &FileConfiguration) {
+    let rcvbuf = buffer_size_setting("GLOBED_GS_UDP_RCVBUF", file_config.udp_rcvbuf);
+    let sndbuf = buffer_size_setting("GLOBED_GS_UDP_SNDBUF", file_config.udp_sndbuf);
+
+    if rcvbuf.is_none() && sndbuf.is_none() {
+        return;
+    }
+
+    let sock = SockRef::from(udp_socket);
+
+    // the kernel is allowed to clamp (or on linux, double) the requested size, so always print the actual value
+    if let Some(size) = rcvbuf {
+        match sock.set_recv_buffer_size(size).and_then(|()| sock.recv_buffer_size()) {
+            Ok(applied) => info!("UDP receive buffer size set to {applied} bytes (requested {size})"),
+            Err(e) => warn!("failed to set the UDP receive buffer size: {e}"),
+        }
+    }
+
+    if let Some(size) = sndbuf {
+        match sock.set_send_buffer_size(size).and_then(|()| sock.send_buffer_size()) {
+            Ok(applied) => info!("UDP send buffer size set to {applied} bytes (requested {size})"),
+            Err(e) => warn!("failed to set the UDP send buffer size: {e}"),
+        }
+    }
+}
+
+/// Looks for a file in the current working directory, or otherwise next to the executable.
+fn find_local_file(name: &str) -> Option<PathBuf> {
+    let exe_path = std::env::current_exe()
+        .expect("failed to get current executable")
+        .parent()
+        .unwrap()
+        .join(name);
+
+    let cwd_path = std::env::current_dir().expect("failed to get current dir").join(name);
+
+    match (exe_path.exists(), cwd_path.exists()) {
+        (_, true) => Some(cwd_path),
+        (true, false) => Some(exe_path),
+        (false, false) => None,
     }
+}
 
-    // env variable takes precedence, otherwise grab the 1st arg from the command line
-    let bind_address = env_addr.ok().or(arg).unwrap();
+fn censor_key(key: &str, keep_first_n_chars: usize) -> String {
+    if key.len() <= keep_first_n_chars {
+        return "*".repeat(key.len());
+    }
+
+    format!("{}{}", &key[..keep_first_n_chars], "*".repeat(key.len() - keep_first_n_chars))
+}
 
-    let bind_address = match bind_address.parse::<SocketAddr>() {
+/// Parses a bind address, either a full socket address or just an IP address, in which case `default_port` is used.
+fn parse_bind_address(address: &str, default_port: u16) -> SocketAddr {
+    match address.parse::<SocketAddr>() {
         Ok(x) => x,
         Err(_) => {
             // try to parse it as an ip addr and use a default port
-            match bind_address.parse::<Ipv4Addr>() {
-                Ok(x) => SocketAddr::new(IpAddr::V4(x), DEFAULT_GAME_SERVER_PORT),
+            match address.parse::<IpAddr>() {
+                Ok(x) => SocketAddr::new(x, default_port),
                 Err(e) => {
-                    error!("failed to parse the given IP address ({bind_address}): {e}");
-                    warn!("hint: you have to provide a valid IPv4 address with an optional port number");
+                    error!("failed to parse the given IP address ({address}): {e}");
+                    warn!("hint: you have to provide a valid IPv4 or IPv6 address with an optional port number");
                     warn!("hint: for example \"0.0.0.0\" or \"0.0.0.0:{DEFAULT_GAME_SERVER_PORT}\"");
+                    warn!("hint: IPv6 addresses with a port must be enclosed in brackets, for example \"[::]:{DEFAULT_GAME_SERVER_PORT}\"");
                     abort_misconfig();
                 }
             }
         }
+    }
+}
+
+/// Settings that can be put in the config file passed with `--config` or `GLOBED_GS_CONFIG`.
+/// Environment variables and command line arguments take precedence over all of these.
+/// Besides the addresses, every key is named like its environment variable without the `GLOBED_GS_` prefix.
+/// Only `GLOBED_GS_NO_FILE_LOG` is missing, as the log file is opened before the config file is read.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfiguration {
+    bind_address: Option<String>,
+    udp_bind_address: Option<String>,
+    /// can contain multiple comma-separated URLs, same as `GLOBED_GS_CENTRAL_URL`
+    central_url: Option<String>,
+    central_password: Option<String>,
+    central_ca: Option<String>,
+    central_ca_pin: Option<bool>,
+    central_insecure_tls: Option<bool>,
+    server_id: Option<String>,
+    startup_retries: Option<u32>,
+    startup_retry_delay: Option<u64>,
+
+    log_level: Option<String>,
+    log_json: Option<bool>,
+    log_max_size: Option<usize>,
+    log_max_files: Option<usize>,
+    /// an empty string disables the audit log
+    audit_log: Option<String>,
+
+    proxy_protocol: Option<bool>,
+    reuse_port: Option<bool>,
+    udp_rcvbuf: Option<usize>,
+    udp_sndbuf: Option<usize>,
+    require_udp_address_match: Option<bool>,
+    max_packet_size: Option<usize>,
+    no_compression: Option<bool>,
+    message_queue_capacity: Option<usize>,
+    login_timeout: Option<u64>,
+    key_rotation_interval: Option<u64>,
+    slow_handler_threshold: Option<u64>,
+    max_room_size: Option<u32>,
+    metrics_addr: Option<String>,
+    health_addr: Option<String>,
+
+    voice_batching: Option<bool>,
+    voice_strike_limit: Option<u32>,
+    voice_recording_dir: Option<String>,
+    /// comma-separated level IDs, same as `GLOBED_GS_VOICE_RECORDING_LEVELS`
+    voice_recording_levels: Option<String>,
+    voice_recording_retention: Option<u64>,
+
+    /// only used in standalone mode
+    server_name: Option<String>,
+    motd: Option<String>,
+    whitelist: Option<bool>,
+}
+
+/// Removes `<name> <value>` (or `<name>=<value>`) from the arguments and returns the value,
+/// so that options can be given anywhere without shifting the positional arguments.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    if let Some(idx) = args.iter().position(|x| x == name) {
+        args.remove(idx);
+        if idx < args.len() {
+            return Some(args.remove(idx));
+        }
+
+        error!("expected a value after '{name}'");
+        abort_misconfig();
+    }
+
+    let prefix = format!("{name}=");
+    let idx = args.iter().position(|x| x.starts_with(&prefix))?;
+    Some(args.remove(idx)[prefix.len()..].to_owned())
+}
+
+/// Removes `--config <path>` (or `--config=<path>`) from the arguments and returns the path, falling back to `GLOBED_GS_CONFIG`.
+fn take_config_path(args: &mut Vec<String>) -> Option<PathBuf> {
+    take_option(args, "--config")
+        .or_else(|| std::env::var("GLOBED_GS_CONFIG").ok().filter(|x| !x.is_empty()))
+        .map(PathBuf::from)
+}
+
+fn load_file_configuration(path: &Path) -> FileConfiguration {
+    let content = match std::fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("failed to read the config file ({}): {e}", path.display());
+            abort_misconfig();
+        }
     };
 
-    let arg = if using_env_variables {
-        std::env::var("GLOBED_GS_CENTRAL_URL").ok()
-    } else {
-        args.next()
+    match toml::from_str(&content) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("failed to parse the config file ({}): {e}", path.display());
+            abort_misconfig();
+        }
+    }
+}
+
+/// Returns the value of the environment variable if it's set, otherwise the given fallback.
+fn env_or(name: &str, fallback: Option<&String>) -> Option<String> {
+    std::env::var(name).ok().filter(|x| !x.is_empty()).or_else(|| fallback.cloned())
+}
+
+/// Unlike other settings, the audit log is enabled by default and setting it to an empty string disables it.
+fn audit_log_path(file_config: &FileConfiguration) -> Option<PathBuf> {
+    let (path, source) = match std::env::var("GLOBED_GS_AUDIT_LOG") {
+        Ok(x) => (x, "environment"),
+        Err(_) => match &file_config.audit_log {
+            Some(x) => (x.clone(), "config file"),
+            None => {
+                debug!("GLOBED_GS_AUDIT_LOG is not set, using the default");
+                return Some(PathBuf::from(DEFAULT_AUDIT_LOG));
+            }
+        },
     };
 
-    if arg.is_none() {
-        // standalone with a specified bind addr
+    if path.is_empty() {
+        info!("GLOBED_GS_AUDIT_LOG is empty, the audit log is disabled (from the {source})");
+        return None;
+    }
+
+    info!("GLOBED_GS_AUDIT_LOG = {path} (from the {source})");
+    Some(PathBuf::from(path))
+}
+
+/// The UDP socket uses the same address as the TCP one, unless `GLOBED_GS_UDP_ADDRESS` or `--udp-address` is set.
+/// If only an IP address is given there, the port of the TCP address is used.
+fn parse_udp_bind_address(tcp_address: SocketAddr, arg_address: Option<String>, file_config: &FileConfiguration) -> SocketAddr {
+    let address = env_or("GLOBED_GS_UDP_ADDRESS", None)
+        .map(|x| (x, "environment"))
+        .or(arg_address.map(|x| (x, "command line")))
+        .or_else(|| file_config.udp_bind_address.clone().map(|x| (x, "config file")));
+
+    let Some((address, source)) = address else {
+        debug!("udp bind address is not set, using the tcp bind address");
+        return tcp_address;
+    };
+
+    info!("udp bind address = {address} (from the {source})");
+    parse_bind_address(&address, tcp_address.port())
+}
+
+/// Every setting is looked up in this order: environment variables, then command line arguments, then the config file.
+fn parse_configuration(exe_name: &str, mut args: Vec<String>, file_config: &FileConfiguration) -> StartupConfiguration {
+    let arg_udp_address = take_option(&mut args, "--udp-address");
+    let mut args = args.into_iter();
+
+    // always consume the address argument, so that the rest of the arguments stay in place even if it's overridden
+    let arg_address = args.next();
+    let bind_address = env_or("GLOBED_GS_ADDRESS", None)
+        .map(|x| (x, "environment"))
+        .or(arg_address.map(|x| (x, "command line")))
+        .or_else(|| file_config.bind_address.clone().map(|x| (x, "config file")));
+
+    let Some((bind_address, source)) = bind_address else {
+        // standalone with default params
+        let bind_address = format!("0.0.0.0:{DEFAULT_GAME_SERVER_PORT}").parse().unwrap();
         return StartupConfiguration {
             bind_address,
+            udp_bind_address: parse_udp_bind_address(bind_address, arg_udp_address, file_config),
             central_data: None,
         };
-    }
+    };
 
-    let mut central_url = arg.unwrap();
-    if !central_url.ends_with('/') {
-        central_url += "/";
-    }
+    info!("bind address = {bind_address} (from the {source})");
+
+    let bind_address = parse_bind_address(&bind_address, DEFAULT_GAME_SERVER_PORT);
+    let udp_bind_address = parse_udp_bind_address(bind_address, arg_udp_address, file_config);
+
+    let split_list = |x: String| x.split(',').map(|x| x.trim().to_owned()).collect::<Vec<_>>();
 
-    let arg = if using_env_variables {
-        std::env::var("GLOBED_GS_CENTRAL_PASSWORD").ok()
+    // multiple central servers can be given, either comma-separated in the env variables and the config file, or as extra pairs of arguments
+    let (urls, passwords, from_args): (Vec<String>, Vec<String>, bool) = if let Some(urls) = env_or("GLOBED_GS_CENTRAL_URL", None) {
+        info!("central server URL = {urls} (from the environment)");
+        let passwords = env_or("GLOBED_GS_CENTRAL_PASSWORD", file_config.central_password.as_ref())
+            .map(split_list)
+            .unwrap_or_default();
+
+        (split_list(urls), passwords, false)
+    } else if let Some(url) = args.next() {
+        info!("central server URL = {url} (from the command line)");
+        let mut urls = vec![url];
+        let mut passwords = Vec::new();
+
+        while let Some(pw) = args.next() {
+            passwords.push(pw);
+
+            match args.next() {
+                Some(url) => urls.push(url),
+                None => break,
+            }
+        }
+
+        (urls, passwords, true)
+    } else if let Some(urls) = file_config.central_url.clone() {
+        info!("central server URL = {urls} (from the config file)");
+        let passwords = env_or("GLOBED_GS_CENTRAL_PASSWORD", file_config.central_password.as_ref())
+            .map(split_list)
+            .unwrap_or_default();
+
+        (split_list(urls), passwords, false)
     } else {
-        args.next()
+        // standalone with a specified bind addr
+        return StartupConfiguration {
+            bind_address,
+            udp_bind_address,
+            central_data: None,
+        };
     };
 
-    if arg.is_none() {
-        if using_env_variables {
-            error!("expected the environment variable 'GLOBED_GS_CENTRAL_PASSWORD', couldn't find it");
-        } else {
+    if passwords.len() < urls.len() && passwords.len() != 1 {
+        if from_args {
             error!("not enough arguments, expected the password of the central server");
-            error!("correct usage: \"{exe_name} <address> <central-url> <central-password>\"");
+            error!(
+                "correct usage: \"{exe_name} [--udp-address <address>] <address> <central-url> <central-password> [<central-url> <central-password>...]\""
+            );
+        } else {
+            error!("expected the environment variable 'GLOBED_GS_CENTRAL_PASSWORD' (or 'central_password' in the config file) with a password for every central server, couldn't find it");
         }
         warn!("hint: you must specify the password for connecting to the central server, see the server readme.");
         abort_misconfig();
     }
 
-    let central_pw = arg.unwrap();
+    let central_servers = urls
+        .into_iter()
+        .enumerate()
+        .map(|(idx, mut url)| {
+            if !url.ends_with('/') {
+                url += "/";
+            }
+
+            // a single password is used for all central servers
+            let password = passwords.get(idx).unwrap_or(&passwords[0]).clone();
+
+            CentralServer { url, password }
+        })
+        .collect();
 
     // full configuration with a central server
     StartupConfiguration {
         bind_address,
-        central_data: Some((central_url, central_pw)),
+        udp_bind_address,
+        central_data: Some(central_servers),
     }
 }
 
@@ -149,27 +634,70 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let write_to_file = std::env::var("GLOBED_GS_NO_FILE_LOG").map(|p| p.parse::<i32>().unwrap()).unwrap_or(0) == 0;
 
-    log::set_logger(Logger::instance("globed_game_server", write_to_file)).unwrap();
+    let logger = Logger::instance("globed_game_server", write_to_file);
+    log::set_logger(logger).unwrap();
+
+    // parse the configuration file first, as it can also contain the log level
+    log::set_max_level(LogLevelFilter::Warn);
+
+    let mut args: Vec<String> = std::env::args().collect();
+    let exe_name = args.remove(0);
+
+    let config_path = take_config_path(&mut args);
+    let file_config = config_path.as_deref().map(load_file_configuration).unwrap_or_default();
+
+    let (log_level, log_level_source) = match (std::env::var("GLOBED_GS_LOG_LEVEL"), &file_config.log_level) {
+        (Err(_), Some(level)) => (parse_log_level(level), "config file"),
+        (Ok(_), _) => (get_log_level("GLOBED_GS_LOG_LEVEL"), "environment"),
+        (Err(_), None) => (get_log_level("GLOBED_GS_LOG_LEVEL"), "default"),
+    };
 
-    if let Some(log_level) = get_log_level("GLOBED_GS_LOG_LEVEL") {
+    if let Some(log_level) = log_level {
         log::set_max_level(log_level);
+        info!("GLOBED_GS_LOG_LEVEL = {log_level} (from the {log_level_source})");
     } else {
         log::set_max_level(LogLevelFilter::Warn); // we have to print these logs somehow lol
-        error!("invalid value for the log level environment varaible");
+        error!("invalid value for the log level environment varaible or config option");
         warn!("hint: possible values are 'trace', 'debug', 'info', 'warn', 'error', and 'none'.");
         abort_misconfig();
     }
 
-    // set the interrupt handler to flush the logfile and exit
+    logger.set_json(flag_setting("GLOBED_GS_LOG_JSON", file_config.log_json));
 
-    if let Err(e) = ctrlc::set_handler(move || {
-        warn!("Interrupt signal received, terminating the server");
-        Logger::instance("globed_game_server", true).flush();
-        std::process::exit(1);
-    }) {
+    if let Some(max_size) = buffer_size_setting("GLOBED_GS_LOG_MAX_SIZE", file_config.log_max_size) {
+        let max_files = setting("GLOBED_GS_LOG_MAX_FILES", file_config.log_max_files).unwrap_or(DEFAULT_MAX_OLD_LOG_FILES);
+
+        logger.set_rotation(max_size as u64, max_files);
+    }
+
+    // the interrupt handler only wakes up the shutdown task, which finishes the voice recordings, flushes the logfile and exits.
+    // this way the final lines are written from the runtime, instead of racing with it from the handler thread
+
+    let shutdown_notify = Arc::new(Notify::new());
+    let handler_notify = shutdown_notify.clone();
+    // only set once the server is running, an interrupt during startup has no recordings to finish
+    let running_server: Arc<OnceLock<&'static GameServer>> = Arc::new(OnceLock::new());
+    let shutdown_server = running_server.clone();
+
+    if let Err(e) = ctrlc::set_handler(move || handler_notify.notify_one()) {
         warn!("error setting up interrupt handler: {e}");
     }
 
+    tokio::spawn(async move {
+        shutdown_notify.notified().await;
+        warn!("Interrupt signal received, terminating the server");
+
+        if let Some(recorder) = shutdown_server.get().and_then(|server| server.voice_recorder.as_ref()) {
+            let _ = tokio::task::spawn_blocking(|| recorder.shutdown()).await;
+        }
+
+        if let Ok(Err(e)) = tokio::task::spawn_blocking(|| logger.flush_for_shutdown()).await {
+            eprintln!("failed to flush the logfile: {e}");
+        }
+
+        std::process::exit(1);
+    });
+
     // setup tokio-console in debug builds
 
     if cfg!(all(tokio_unstable, feature = "use_tokio_tracing")) {
@@ -179,23 +707,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // parse the configuration from environment variables or command line
 
-    let startup_config = parse_configuration();
+    if let Some(path) = &config_path {
+        info!(
+            "Loaded configuration from {}, environment variables and command line arguments take precedence over it",
+            path.display()
+        );
+    }
+
+    let startup_config = parse_configuration(&exe_name, args, &file_config);
     let standalone = startup_config.central_data.is_none();
 
     // check if there's a word filter
-    let word_filter_path = std::env::current_exe()
-        .expect("failed to get current executable")
-        .parent()
-        .unwrap()
-        .join("word-filter.txt");
-
-    let word_filter_path2 = std::env::current_dir().expect("failed to get current dir").join("word-filter.txt");
-
-    let chosen = match (word_filter_path.exists(), word_filter_path2.exists()) {
-        (_, true) => Some(word_filter_path2),
-        (true, false) => Some(word_filter_path),
-        (false, false) => None,
-    };
+    let chosen = find_local_file("word-filter.txt");
 
     let mut filter_words = Vec::new();
     if let Some(chosen) = chosen {
@@ -214,26 +737,89 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let filter_words_count = filter_words.len();
 
     let state = ServerState::new(&filter_words);
+
+    // check if there's a list of banned ip addresses
+    if let Some(path) = find_local_file("ip-bans.txt") {
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => state.ip_ban_manager.load_local(&content),
+            Err(e) => warn!("failed to read ip-bans.txt: {e}"),
+        }
+    }
+
+    // and a list of whitelisted accounts
+    if let Some(path) = find_local_file("whitelist.txt") {
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => {
+                let count = state.whitelist_manager.load_local(&content);
+                info!("Loaded {count} whitelisted accounts from whitelist.txt");
+            }
+            Err(e) => warn!("failed to read whitelist.txt: {e}"),
+        }
+    }
+
+    // and a list of addresses that can have more accounts logged in at once
+    if let Some(path) = find_local_file("ip-limit-exempt.txt") {
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => {
+                let count = state.address_limiter.load_exempt(&content);
+                info!("Loaded {count} addresses exempt from the per-IP account limit from ip-limit-exempt.txt");
+            }
+            Err(e) => warn!("failed to read ip-limit-exempt.txt: {e}"),
+        }
+    }
+
+    // and a list of banned accounts
+    if let Some(path) = find_local_file("bans.json") {
+        match tokio::fs::read_to_string(&path)
+            .await
+            .map(|content| state.ban_manager.load_local(&content))
+        {
+            Ok(Ok(count)) => info!("Loaded {count} account bans from bans.json"),
+            Ok(Err(e)) => {
+                error!("failed to parse bans.json: {e}");
+                abort_misconfig();
+            }
+            Err(e) => warn!("failed to read bans.json: {e}"),
+        }
+    }
     let bridge = if standalone {
         warn!("Starting in standalone mode, authentication is disabled");
         warn!("Note: use Direct Connection option in-game to connect, Add Server cannot be used.");
-        CentralBridge::new("", "")
+
+        let whitelist = flag_setting("GLOBED_GS_WHITELIST", file_config.whitelist);
+        if whitelist {
+            warn!("The whitelist is enabled, but without authentication anyone can join by sending the account ID of a whitelisted player.");
+            warn!("Do not rely on it to keep people out, run the server with a central server if you need that.");
+        }
+
+        let bridge = CentralBridge::new(Vec::new(), None, CentralTlsConfig::default());
+        bridge.set_boot_data(GameServerBootData {
+            server_name: setting("GLOBED_GS_SERVER_NAME", file_config.server_name.clone()).unwrap_or_default(),
+            motd: setting("GLOBED_GS_MOTD", file_config.motd.clone()).unwrap_or_default(),
+            whitelist,
+            ..Default::default()
+        });
+
+        bridge
     } else {
-        let (central_url, central_pw) = startup_config.central_data.unwrap();
+        let central_servers = startup_config.central_data.unwrap();
 
         // check if the user put a wrong url
-        if central_url.contains("http://0.0.0.0") || central_url.contains("https://0.0.0.0") {
+        if central_servers
+            .iter()
+            .any(|server| server.url.contains("http://0.0.0.0") || server.url.contains("https://0.0.0.0"))
+        {
             error!("invalid central server URL was provided");
             warn!("hint: 0.0.0.0 is an address that is only valid for *listening*, not *connecting*");
             warn!("hint: try 127.0.0.1 if the server is on your local machine");
             abort_misconfig();
         }
 
-        let bridge = CentralBridge::new(&central_url, &central_pw);
+        let bridge = CentralBridge::new(central_servers, server_id(&file_config), central_tls_config(&file_config));
 
         info!("Retrieving config from the central server..");
 
-        let central_conf = match bridge.request_boot_data().await {
+        let central_conf = match request_startup_boot_data(&bridge, &file_config).await {
             Ok(x) => x,
             Err(CentralBridgeError::RequestError(err)) => {
                 error!("failed to make a request to the central server: {err}");
@@ -289,6 +875,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
         bridge
     };
 
+    if let Some(size) = buffer_size_setting("GLOBED_GS_MAX_PACKET_SIZE", file_config.max_packet_size) {
+        let size = u32::try_from(size).unwrap_or(u32::MAX);
+        let applied = bridge.set_local_max_packet_size(size);
+        if applied != size {
+            warn!("GLOBED_GS_MAX_PACKET_SIZE is too small, using {applied} bytes instead");
+        }
+    }
+
     {
         // output useful information
 
@@ -321,16 +915,38 @@ async fn main() -> Result<(), Box<dyn Error>> {
             debug!("Filtered words: {filter_words_count}");
         }
 
+        if !standalone {
+            debug!("* Banned users: {}", gsbd.banned_users.len());
+        }
+
         state.role_manager.refresh_from(&gsbd);
+        state.ban_manager.refresh_from(&gsbd);
+        state.ip_ban_manager.refresh_from(&gsbd);
+
+        let ip_ban_count = state.ip_ban_manager.entry_count();
+        if ip_ban_count != 0 {
+            debug!("Banned IP addresses/ranges: {ip_ban_count}");
+        }
+    }
+
+    let reuse_port = flag_setting("GLOBED_GS_REUSE_PORT", file_config.reuse_port);
+    if reuse_port && cfg!(not(unix)) {
+        warn!("GLOBED_GS_REUSE_PORT is only supported on Unix systems, ignoring it");
     }
 
     // bind the UDP socket
 
-    let udp_socket = match UdpSocket::bind(&startup_config.bind_address).await {
+    let udp_socket = match bind_socket_with_retry(startup_config.udp_bind_address, Type::DGRAM, Protocol::UDP, reuse_port)
+        .await
+        .and_then(|socket| UdpSocket::from_std(socket.into()))
+    {
         Ok(x) => x,
         Err(err) => {
-            error!("Failed to bind the UDP socket with address {}: {err}", startup_config.bind_address);
-            if startup_config.bind_address.port() < 1024 {
+            error!(
+                "Failed to bind the UDP (game/voice) socket with address {}: {err}",
+                startup_config.udp_bind_address
+            );
+            if startup_config.udp_bind_address.port() < 1024 {
                 warn!("hint: ports below 1024 are commonly privileged and you can't use them as a regular user");
                 warn!("hint: pick a higher port number or leave it out completely to use the default port number ({DEFAULT_GAME_SERVER_PORT})");
             }
@@ -338,12 +954,26 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
+    // the default OS buffers can be too small for a busy server with lots of voice traffic
+    configure_udp_buffers(&udp_socket, &file_config);
+
     // bind the TCP socket
 
-    let tcp_socket = match TcpListener::bind(&startup_config.bind_address).await {
+    let tcp_socket = match bind_socket_with_retry(startup_config.bind_address, Type::STREAM, Protocol::TCP, reuse_port)
+        .await
+        .and_then(|socket| {
+            socket.listen(1024)?;
+            TcpListener::from_std(socket.into())
+        }) {
         Ok(x) => x,
         Err(err) => {
-            error!("Failed to bind the TCP socket with address {}: {err}", startup_config.bind_address);
+            error!(
+                "Failed to bind the TCP (control/login) socket with address {}: {err}",
+                startup_config.bind_address
+            );
+            if startup_config.bind_address.port() < 1024 {
+                warn!("hint: ports below 1024 are commonly privileged and you can't use them as a regular user");
+            }
 
             abort_misconfig();
         }
@@ -351,8 +981,44 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     // create and run the server
 
-    let server = GameServer::new(tcp_socket, udp_socket, state, bridge, standalone);
-    let server = Box::leak(Box::new(server));
+    let config = GameServerConfiguration {
+        proxy_protocol: flag_setting("GLOBED_GS_PROXY_PROTOCOL", file_config.proxy_protocol),
+        metrics_address: setting::<String>("GLOBED_GS_METRICS_ADDR", file_config.metrics_addr.clone())
+            .map(|x| parse_bind_address(&x, DEFAULT_METRICS_PORT)),
+        health_address: setting::<String>("GLOBED_GS_HEALTH_ADDR", file_config.health_addr.clone())
+            .map(|x| parse_bind_address(&x, DEFAULT_HEALTH_PORT)),
+        key_rotation_interval: setting("GLOBED_GS_KEY_ROTATION_INTERVAL", file_config.key_rotation_interval)
+            .filter(|x| *x != 0)
+            .map(Duration::from_secs),
+        message_queue_capacity: setting("GLOBED_GS_MESSAGE_QUEUE_CAPACITY", file_config.message_queue_capacity)
+            .filter(|x| *x != 0)
+            .unwrap_or(DEFAULT_MESSAGE_QUEUE_CAPACITY),
+        voice_batching: flag_setting("GLOBED_GS_VOICE_BATCHING", file_config.voice_batching),
+        voice_recording: voice_recording_config(&file_config),
+        compression: !flag_setting("GLOBED_GS_NO_COMPRESSION", file_config.no_compression),
+        audit_log: audit_log_path(&file_config),
+        require_udp_address_match: flag_setting("GLOBED_GS_REQUIRE_UDP_ADDRESS_MATCH", file_config.require_udp_address_match),
+        slow_handler_threshold: match setting("GLOBED_GS_SLOW_HANDLER_THRESHOLD", file_config.slow_handler_threshold) {
+            Some(0) => None,
+            Some(x) => Some(Duration::from_millis(x)),
+            None => Some(DEFAULT_SLOW_HANDLER_THRESHOLD),
+        },
+        max_room_size: setting("GLOBED_GS_MAX_ROOM_SIZE", file_config.max_room_size).unwrap_or(0),
+        voice_strike_limit: setting("GLOBED_GS_VOICE_STRIKE_LIMIT", file_config.voice_strike_limit).unwrap_or(DEFAULT_VOICE_STRIKE_LIMIT),
+        login_timeout: match setting("GLOBED_GS_LOGIN_TIMEOUT", file_config.login_timeout) {
+            Some(0) => None,
+            Some(x) => Some(Duration::from_secs(x)),
+            None => Some(DEFAULT_LOGIN_TIMEOUT),
+        },
+    };
+
+    if config.proxy_protocol {
+        info!("PROXY protocol is enabled, connections without a valid PROXY header will be dropped");
+    }
+
+    let server = GameServer::new(tcp_socket, udp_socket, state, bridge, standalone, config);
+    let server: &'static GameServer = Box::leak(Box::new(server));
+    let _ = running_server.set(server);
 
     Box::pin(server.run()).await;
 
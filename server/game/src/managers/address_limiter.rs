@@ -0,0 +1,102 @@
+use std::net::IpAddr;
+
+use globed_shared::{warn, IntMap, SyncMutex};
+use ipnet::IpNet;
+use rustc_hash::FxHashMap;
+
+use super::IpBanManager;
+
+#[derive(Default)]
+struct Connections {
+    /// address every counted account logged in from
+    accounts: IntMap<i32, IpAddr>,
+    /// amount of logged in accounts per address
+    addresses: FxHashMap<IpAddr, u32>,
+}
+
+impl Connections {
+    fn remove(&mut self, account_id: i32) {
+        let Some(address) = self.accounts.remove(&account_id) else {
+            return;
+        };
+
+        if let Some(count) = self.addresses.get_mut(&address) {
+            *count -= 1;
+
+            if *count == 0 {
+                self.addresses.remove(&address);
+            }
+        }
+    }
+}
+
+/// Limits how many accounts can be logged in at the same time from a single IP address,
+/// to make flooding a level with alt accounts harder.
+#[derive(Default)]
+pub struct AddressLimiter {
+    connections: SyncMutex<Connections>,
+    exempt: SyncMutex<Vec<IpNet>>,
+}
+
+impl AddressLimiter {
+    /// Replaces the list of addresses and subnets that are exempt from the limit, in the same format as the IP ban list.
+    /// Returns the amount of loaded entries.
+    pub fn load_exempt(&self, contents: &str) -> usize {
+        let entries: Vec<IpNet> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let net = IpBanManager::parse_entry(line);
+                if net.is_none() {
+                    warn!("ignoring invalid IP limit exemption: '{line}'");
+                }
+
+                net
+            })
+            .collect();
+
+        let count = entries.len();
+        *self.exempt.lock() = entries;
+        count
+    }
+
+    /// Whether the address is not limited, either because it is localhost or because it was listed in the exemptions.
+    pub fn is_exempt(&self, address: &IpAddr) -> bool {
+        let address = &address.to_canonical();
+        address.is_loopback() || self.exempt.lock().iter().any(|net| net.contains(address))
+    }
+
+    /// Counts a newly logged in account, returns `false` without counting it if the address already has `limit` accounts logged in.
+    /// A `limit` of 0 means unlimited. An account that is already counted is only counted once, at its latest address.
+    pub fn try_add(&self, account_id: i32, address: IpAddr, limit: u32) -> bool {
+        let address = address.to_canonical();
+
+        if limit == 0 || self.is_exempt(&address) {
+            return true;
+        }
+
+        let mut conns = self.connections.lock();
+
+        // logging in again (for example from another device) must not take up another slot
+        let previous = conns.accounts.get(&account_id).copied();
+        if previous == Some(address) {
+            return true;
+        }
+
+        if conns.addresses.get(&address).is_some_and(|count| *count >= limit) {
+            return false;
+        }
+
+        conns.remove(account_id);
+        *conns.addresses.entry(address).or_insert(0) += 1;
+        conns.accounts.insert(account_id, address);
+
+        true
+    }
+
+    /// Stops counting the account, must be called once they disconnect.
+    pub fn remove(&self, account_id: i32) {
+        self.connections.lock().remove(account_id);
+    }
+}
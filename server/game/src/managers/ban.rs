@@ -0,0 +1,109 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use globed_shared::{BannedUser, GameServerBootData, IntMap, SyncMutex, UserEntry};
+use serde::Deserialize;
+
+/// Ban entry in a local ban list file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LocalBan {
+    account_id: i32,
+    #[serde(default)]
+    reason: Option<String>,
+    /// seconds since unix epoch, permanent if missing
+    #[serde(default)]
+    expires_at: Option<i64>,
+}
+
+/// Keeps the list of banned accounts sent by the central server and/or loaded from a local file,
+/// so that banned users can be rejected on login without an extra round trip to the central server.
+#[derive(Default)]
+pub struct BanManager {
+    central: SyncMutex<IntMap<i32, BannedUser>>,
+    local: SyncMutex<IntMap<i32, BannedUser>>,
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn current_timestamp() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("clock went backwards").as_secs() as i64
+}
+
+fn is_expired(ban: &BannedUser, now: i64) -> bool {
+    ban.expiry.is_some_and(|expiry| now > expiry)
+}
+
+impl BanManager {
+    pub fn refresh_from(&self, gsbd: &GameServerBootData) {
+        let mut banned = self.central.lock();
+
+        banned.clear();
+        banned.extend(gsbd.banned_users.iter().map(|user| (user.account_id, user.clone())));
+    }
+
+    /// Replaces the bans loaded from a local file, which contains a JSON array of
+    /// `{ "account_id": 1234, "reason": "...", "expires_at": 1700000000 }` objects. Returns the amount of loaded bans.
+    pub fn load_local(&self, contents: &str) -> serde_json::Result<usize> {
+        let bans: Vec<LocalBan> = serde_json::from_str(contents)?;
+        let now = current_timestamp();
+
+        let mut local = self.local.lock();
+        local.clear();
+        local.extend(
+            bans.into_iter()
+                .map(|ban| BannedUser {
+                    account_id: ban.account_id,
+                    reason: ban.reason,
+                    expiry: ban.expires_at,
+                })
+                .filter(|ban| !is_expired(ban, now))
+                .map(|ban| (ban.account_id, ban)),
+        );
+
+        Ok(local.len())
+    }
+
+    /// Updates the ban state of a single user, so that changes made by moderators apply before the next refresh.
+    pub fn update_from(&self, user: &UserEntry) {
+        let mut banned = self.central.lock();
+
+        if user.is_banned {
+            banned.insert(
+                user.account_id,
+                BannedUser {
+                    account_id: user.account_id,
+                    reason: user.violation_reason.clone(),
+                    expiry: user.violation_expiry,
+                },
+            );
+        } else {
+            banned.remove(&user.account_id);
+        }
+    }
+
+    /// Returns the ban of the given account, if they are banned and the ban has not expired yet.
+    pub fn get_ban(&self, account_id: i32) -> Option<BannedUser> {
+        let now = current_timestamp();
+
+        let central = self.central.lock().get(&account_id).filter(|ban| !is_expired(ban, now)).cloned();
+        central.or_else(|| self.local.lock().get(&account_id).filter(|ban| !is_expired(ban, now)).cloned())
+    }
+
+    pub fn is_banned(&self, account_id: i32) -> bool {
+        self.get_ban(account_id).is_some()
+    }
+
+    /// Removes all bans that have expired, returns how many were removed.
+    pub fn prune_expired(&self) -> usize {
+        let now = current_timestamp();
+        let mut removed = 0;
+
+        for list in [&self.central, &self.local] {
+            let mut list = list.lock();
+            let before = list.len();
+            list.retain(|_, ban| !is_expired(ban, now));
+            removed += before - list.len();
+        }
+
+        removed
+    }
+}
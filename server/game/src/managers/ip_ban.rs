@@ -0,0 +1,96 @@
+use std::net::IpAddr;
+
+use globed_shared::{warn, GameServerBootData, SyncMutex};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use iprange::IpRange;
+
+#[derive(Default)]
+struct IpBanList {
+    range_v4: IpRange<Ipv4Net>,
+    range_v6: IpRange<Ipv6Net>,
+}
+
+/// Keeps the list of banned IP addresses and subnets, sent by the central server and/or loaded from a local file.
+#[derive(Default)]
+pub struct IpBanManager {
+    local: SyncMutex<Vec<IpNet>>,
+    central: SyncMutex<Vec<IpNet>>,
+    list: SyncMutex<IpBanList>,
+}
+
+impl IpBanManager {
+    /// Parses an entry, which can either be a single address (`1.2.3.4`) or a subnet (`1.2.3.0/24`).
+    pub fn parse_entry(entry: &str) -> Option<IpNet> {
+        let entry = entry.trim();
+
+        if let Ok(net) = entry.parse::<IpNet>() {
+            Some(net.trunc())
+        } else {
+            entry.parse::<IpAddr>().ok().map(IpNet::from)
+        }
+    }
+
+    fn parse_entries<'a>(entries: impl Iterator<Item = &'a str>) -> Vec<IpNet> {
+        entries
+            .filter_map(|entry| {
+                let entry = entry.trim();
+                if entry.is_empty() || entry.starts_with('#') {
+                    return None;
+                }
+
+                let net = Self::parse_entry(entry);
+                if net.is_none() {
+                    warn!("ignoring invalid IP ban entry: '{entry}'");
+                }
+
+                net
+            })
+            .collect()
+    }
+
+    /// Replaces the entries loaded from a local file. Empty lines and lines starting with `#` are skipped.
+    pub fn load_local(&self, contents: &str) {
+        *self.local.lock() = Self::parse_entries(contents.lines());
+        self.rebuild();
+    }
+
+    /// Replaces the entries sent by the central server.
+    pub fn refresh_from(&self, gsbd: &GameServerBootData) {
+        *self.central.lock() = Self::parse_entries(gsbd.banned_ips.iter().map(String::as_str));
+        self.rebuild();
+    }
+
+    fn rebuild(&self) {
+        let mut list = IpBanList::default();
+
+        for net in self.local.lock().iter().chain(self.central.lock().iter()) {
+            match net {
+                IpNet::V4(net) => {
+                    list.range_v4.add(*net);
+                }
+                IpNet::V6(net) => {
+                    list.range_v6.add(*net);
+                }
+            }
+        }
+
+        list.range_v4.simplify();
+        list.range_v6.simplify();
+
+        *self.list.lock() = list;
+    }
+
+    pub fn is_banned(&self, address: &IpAddr) -> bool {
+        let list = self.list.lock();
+
+        // ipv4 clients connecting to a dual-stack socket show up as ipv4-mapped ipv6 addresses
+        match address.to_canonical() {
+            IpAddr::V4(addr) => list.range_v4.contains(&addr),
+            IpAddr::V6(addr) => list.range_v6.contains(&addr),
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.local.lock().len() + self.central.lock().len()
+    }
+}
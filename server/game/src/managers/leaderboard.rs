@@ -0,0 +1,63 @@
+use crate::data::{LeaderboardEntry, LEADERBOARD_SIZE};
+
+/// Best attempts of the players on a single level, only kept while someone is on the level.
+pub struct LevelLeaderboard {
+    /// whether the results are times in milliseconds (lower is better), rather than percentages
+    pub platformer: bool,
+    /// sorted from the best result, at most `LEADERBOARD_SIZE` entries
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl LevelLeaderboard {
+    pub fn new(platformer: bool) -> Self {
+        Self {
+            platformer,
+            entries: Vec::with_capacity(LEADERBOARD_SIZE),
+        }
+    }
+
+    /// Whether the result could have been achieved at all, a percentage can't be above 100 and a time can't be zero.
+    pub const fn is_valid_result(best: u32, platformer: bool) -> bool {
+        if platformer {
+            best != 0
+        } else {
+            best <= 100
+        }
+    }
+
+    const fn is_better(&self, a: u32, b: u32) -> bool {
+        if self.platformer {
+            a < b
+        } else {
+            a > b
+        }
+    }
+
+    /// Submits a result, replacing the previous result of the same player if the new one is better.
+    /// Returns `false` if the leaderboard did not change.
+    pub fn submit(&mut self, entry: LeaderboardEntry) -> bool {
+        if let Some(index) = self.entries.iter().position(|x| x.account_id == entry.account_id) {
+            if !self.is_better(entry.best, self.entries[index].best) {
+                return false;
+            }
+
+            self.entries.remove(index);
+        }
+
+        // on a tie, whoever got the result first stays above
+        let index = self
+            .entries
+            .iter()
+            .position(|x| self.is_better(entry.best, x.best))
+            .unwrap_or(self.entries.len());
+
+        if index >= LEADERBOARD_SIZE {
+            return false;
+        }
+
+        self.entries.insert(index, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+
+        true
+    }
+}
@@ -1,8 +1,12 @@
+use std::collections::HashMap;
+
 use globed_shared::IntMap;
 
+use super::LevelLeaderboard;
+
 use crate::data::{
-    types::PlayerData, AssociatedPlayerData, AssociatedPlayerMetadata, BorrowedAssociatedPlayerData, BorrowedAssociatedPlayerMetadata, LevelId,
-    PlayerMetadata,
+    types::PlayerData, AssociatedPlayerData, AssociatedPlayerMetadata, BorrowedAssociatedPlayerData, BorrowedAssociatedPlayerMetadata,
+    LeaderboardEntry, LevelId, PlayerMetadata,
 };
 
 #[derive(Default)]
@@ -47,6 +51,10 @@ impl LevelManagerPlayer {
 pub struct LevelManager {
     pub players: IntMap<i32, LevelManagerPlayer>, // player id : associated data
     pub levels: IntMap<LevelId, Vec<i32>>,        // level id : [player id]
+    /// spectators are kept separately from the players, so they don't show up anywhere and don't count towards anything
+    pub spectators: IntMap<LevelId, Vec<i32>>, // level id : [spectator id]
+    /// classic and platformer results are kept apart, as the client is the only one that knows what kind of level it is
+    pub leaderboards: HashMap<(LevelId, bool), LevelLeaderboard>, // (level id, platformer) : leaderboard
 }
 
 impl LevelManager {
@@ -95,6 +103,11 @@ impl LevelManager {
         self.levels.get(&level_id)
     }
 
+    /// get a reference to a list of account IDs of spectators on a level given its ID
+    pub fn get_spectators(&self, level_id: LevelId) -> Option<&Vec<i32>> {
+        self.spectators.get(&level_id)
+    }
+
     /// get amount of levels in the room
     pub fn get_level_count(&self) -> usize {
         self.levels.len()
@@ -164,6 +177,45 @@ impl LevelManager {
 
         if should_remove_level {
             self.levels.remove(&level_id);
+            self.leaderboards.remove(&(level_id, false));
+            self.leaderboards.remove(&(level_id, true));
+        }
+    }
+
+    /// submit a result to the classic or platformer leaderboard of a level, returns `false` if the leaderboard did not change
+    pub fn submit_level_result(&mut self, level_id: LevelId, entry: LeaderboardEntry, platformer: bool) -> bool {
+        if !self.levels.contains_key(&level_id) {
+            return false;
+        }
+
+        self.leaderboards
+            .entry((level_id, platformer))
+            .or_insert_with(|| LevelLeaderboard::new(platformer))
+            .submit(entry)
+    }
+
+    /// get the classic or platformer leaderboard of a level given its ID, if anyone has submitted a result there
+    pub fn get_leaderboard(&self, level_id: LevelId, platformer: bool) -> Option<&LevelLeaderboard> {
+        self.leaderboards.get(&(level_id, platformer))
+    }
+
+    /// add a spectator to a level given a level ID and an account ID
+    pub fn add_spectator_to_level(&mut self, level_id: LevelId, account_id: i32) {
+        let spectators = self.spectators.entry(level_id).or_default();
+        if !spectators.contains(&account_id) {
+            spectators.push(account_id);
+        }
+    }
+
+    /// remove a spectator from a level given a level ID and an account ID
+    pub fn remove_spectator_from_level(&mut self, level_id: LevelId, account_id: i32) {
+        let should_remove_level = self.spectators.get_mut(&level_id).is_some_and(|level| {
+            level.retain(|&x| x != account_id);
+            level.is_empty()
+        });
+
+        if should_remove_level {
+            self.spectators.remove(&level_id);
         }
     }
 }
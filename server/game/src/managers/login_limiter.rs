@@ -0,0 +1,91 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use globed_shared::{IntMap, SyncMutex};
+use rustc_hash::FxHashMap;
+
+use crate::bridge::CentralBridge;
+
+/// once a map grows past this size, expired entries get removed from it
+const PRUNE_THRESHOLD: usize = 1024;
+
+pub(super) struct AttemptWindow {
+    started: Instant,
+    count: u32,
+}
+
+/// Counts login attempts in a fixed window, per account and per IP address.
+#[derive(Default)]
+pub struct LoginLimiter {
+    accounts: SyncMutex<IntMap<i32, AttemptWindow>>,
+    addresses: SyncMutex<FxHashMap<IpAddr, AttemptWindow>>,
+}
+
+impl LoginLimiter {
+    /// Records a login attempt and returns `true` if it is allowed, `false` if the account or the address is ratelimited.
+    pub fn try_attempt(&self, bridge: &CentralBridge, account_id: i32, address: IpAddr) -> bool {
+        let (limit, ip_limit, interval) = {
+            let conf = bridge.central_conf.lock();
+            (
+                conf.login_attempt_limit,
+                conf.login_attempt_ip_limit,
+                Duration::from_secs(u64::from(conf.login_attempt_interval)),
+            )
+        };
+
+        if interval.is_zero() {
+            return true;
+        }
+
+        let now = Instant::now();
+
+        // both are always counted, so a ratelimited address can't keep trying other accounts for free
+        let account_ok = tick_window(&mut self.accounts.lock(), account_id, limit, interval, now);
+        let address_ok = tick_window(&mut self.addresses.lock(), address.to_canonical(), ip_limit, interval, now);
+
+        account_ok && address_ok
+    }
+}
+
+/// Counts an attempt for `key` in a fixed window, returns `false` if it's over the limit. A `limit` of 0 means unlimited.
+pub(super) fn tick_window<K: Hash + Eq, S: BuildHasher>(
+    map: &mut HashMap<K, AttemptWindow, S>,
+    key: K,
+    limit: u32,
+    interval: Duration,
+    now: Instant,
+) -> bool {
+    if limit == 0 {
+        return true;
+    }
+
+    if map.len() > PRUNE_THRESHOLD {
+        map.retain(|_, window| now - window.started < interval);
+    }
+
+    let window = map.entry(key).or_insert(AttemptWindow { started: now, count: 0 });
+
+    if now - window.started >= interval {
+        window.started = now;
+        window.count = 0;
+    }
+
+    window.count = window.count.saturating_add(1);
+    window.count <= limit
+}
+
+/// Returns `true` if the window of `key` already reached `limit`, without counting another attempt.
+pub(super) fn window_full<K: Hash + Eq, S: BuildHasher>(
+    map: &HashMap<K, AttemptWindow, S>,
+    key: &K,
+    limit: u32,
+    interval: Duration,
+    now: Instant,
+) -> bool {
+    map.get(key)
+        .is_some_and(|window| limit != 0 && now - window.started < interval && window.count >= limit)
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use globed_shared::SyncMutex;
+
+use crate::tokio::sync::Notify;
+
+/// First in, first out queue of logins waiting for a free slot on a full server.
+#[derive(Default)]
+pub struct LoginQueue {
+    tickets: SyncMutex<VecDeque<u64>>,
+    next_ticket: AtomicU64,
+    notify: Notify,
+}
+
+impl LoginQueue {
+    /// Adds a login to the end of the queue and returns its ticket, or `None` if `limit` logins are already waiting.
+    pub fn join(&self, limit: usize) -> Option<u64> {
+        let mut tickets = self.tickets.lock();
+
+        if tickets.len() >= limit {
+            return None;
+        }
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        tickets.push_back(ticket);
+
+        Some(ticket)
+    }
+
+    /// Removes a login from the queue, everyone behind it moves up.
+    pub fn leave(&self, ticket: u64) {
+        self.tickets.lock().retain(|&x| x != ticket);
+        self.notify();
+    }
+
+    /// Returns the position of the login in the queue, starting from 1, or `None` if it is not in the queue.
+    pub fn position(&self, ticket: u64) -> Option<usize> {
+        self.tickets.lock().iter().position(|&x| x == ticket).map(|x| x + 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.lock().is_empty()
+    }
+
+    /// If the login is first in line and `take_slot` manages to take a slot, removes it from the queue and returns `true`.
+    /// `take_slot` is called with the queue locked, so only one login can get a slot at a time.
+    pub fn try_promote<F: FnOnce() -> bool>(&self, ticket: u64, take_slot: F) -> bool {
+        {
+            let mut tickets = self.tickets.lock();
+
+            if tickets.front() != Some(&ticket) || !take_slot() {
+                return false;
+            }
+
+            tickets.pop_front();
+        }
+
+        self.notify();
+        true
+    }
+
+    /// Wakes up every queued login, so they can check their position again. Should be called whenever a slot might have opened.
+    pub fn notify(&self) {
+        self.notify.notify_waiters();
+    }
+
+    /// Waits until `notify` is called. A notification that happens before this starts waiting is missed,
+    /// so the queue should also be checked periodically.
+    pub async fn changed(&self) {
+        self.notify.notified().await;
+    }
+}
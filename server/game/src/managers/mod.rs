@@ -1,7 +1,29 @@
+mod address_limiter;
+mod ban;
+mod ip_ban;
+mod leaderboard;
 mod level;
+mod login_limiter;
+mod login_queue;
+mod mute_cache;
+mod preauth_limiter;
+mod profile_cache;
 mod role;
 mod room;
+mod user_cache;
+mod whitelist;
 
+pub use address_limiter::AddressLimiter;
+pub use ban::BanManager;
+pub use ip_ban::IpBanManager;
+pub use leaderboard::LevelLeaderboard;
 pub use level::LevelManager;
+pub use login_limiter::LoginLimiter;
+pub use login_queue::LoginQueue;
+pub use mute_cache::MuteCache;
+pub use preauth_limiter::PreauthLimiter;
+pub use profile_cache::ProfileCache;
 pub use role::{ComputedRole, GameServerRole, RoleManager};
 pub use room::RoomManager;
+pub use user_cache::UserCache;
+pub use whitelist::WhitelistManager;
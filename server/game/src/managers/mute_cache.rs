@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use globed_shared::{IntMap, IntSet, SyncMutex};
+
+/// at most this many players are kept, once full the expired entries get removed, and then the oldest ones
+const CAPACITY: usize = 4096;
+/// how long the mutes of a player are kept after they leave
+const MUTE_RETENTION: Duration = Duration::from_secs(600);
+
+struct SavedMutes {
+    saved: Instant,
+    muted: IntSet<i32>,
+}
+
+/// Keeps the voice mutes of players that left for a short period of time,
+/// so that reconnecting does not unmute everyone they have muted before.
+pub struct MuteCache {
+    players: SyncMutex<IntMap<i32, SavedMutes>>,
+    retention: Duration,
+    capacity: usize,
+}
+
+impl Default for MuteCache {
+    fn default() -> Self {
+        Self::new(MUTE_RETENTION, CAPACITY)
+    }
+}
+
+impl MuteCache {
+    /// Creates a cache that keeps mutes for `retention` after a player leaves, for at most `capacity` players at once.
+    pub fn new(retention: Duration, capacity: usize) -> Self {
+        Self {
+            players: SyncMutex::new(IntMap::default()),
+            retention,
+            capacity,
+        }
+    }
+
+    /// Saves the mutes of a player that is leaving or losing their connection.
+    pub fn save(&self, account_id: i32, muted: IntSet<i32>) {
+        if muted.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut players = self.players.lock();
+
+        if players.len() >= self.capacity && !players.contains_key(&account_id) {
+            players.retain(|_, mutes| now - mutes.saved < self.retention);
+
+            if players.len() >= self.capacity {
+                let oldest = players.iter().min_by_key(|(_, mutes)| mutes.saved).map(|(id, _)| *id);
+                if let Some(oldest) = oldest {
+                    players.remove(&oldest);
+                }
+            }
+        }
+
+        players.insert(account_id, SavedMutes { saved: now, muted });
+    }
+
+    /// Takes the saved mutes of a player that is joining, empty if they have none or they expired.
+    pub fn take(&self, account_id: i32) -> IntSet<i32> {
+        self.players
+            .lock()
+            .remove(&account_id)
+            .filter(|mutes| mutes.saved.elapsed() < self.retention)
+            .map(|mutes| mutes.muted)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use globed_shared::SyncMutex;
+use rustc_hash::FxHashMap;
+
+use super::login_limiter::{tick_window, window_full, AttemptWindow};
+
+/// period of time over which the packets are counted
+const PREAUTH_WINDOW: Duration = Duration::from_secs(10);
+/// a server browser sends a single ping per refresh, so this is only reached when flooding
+const PING_LIMIT: u32 = 30;
+/// same as pings, but the response is much larger, so it is easier to abuse for amplification
+const SERVER_INFO_LIMIT: u32 = 10;
+/// a real client never sends a malformed or duplicate handshake, so after a few of those the address can't do any handshakes for a while
+const HANDSHAKE_FAILURE_LIMIT: u32 = 3;
+const HANDSHAKE_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+/// the secret key is only 32 bits, so guessing it must not be possible
+const RECOVERY_LIMIT: u32 = 5;
+
+/// Limits how often a single IP address can send packets that are handled before logging in,
+/// so that they can't be spammed to waste CPU time (especially handshakes, which are relatively expensive).
+#[derive(Default)]
+pub struct PreauthLimiter {
+    pings: SyncMutex<FxHashMap<IpAddr, AttemptWindow>>,
+    server_info: SyncMutex<FxHashMap<IpAddr, AttemptWindow>>,
+    handshakes: SyncMutex<FxHashMap<IpAddr, AttemptWindow>>,
+    handshake_failures: SyncMutex<FxHashMap<IpAddr, AttemptWindow>>,
+    recoveries: SyncMutex<FxHashMap<IpAddr, AttemptWindow>>,
+}
+
+impl PreauthLimiter {
+    /// Returns `true` if a ping from this address should be answered.
+    pub fn try_ping(&self, address: IpAddr) -> bool {
+        tick_window(&mut self.pings.lock(), address.to_canonical(), PING_LIMIT, PREAUTH_WINDOW, Instant::now())
+    }
+
+    /// Returns `true` if a server info request from this address should be answered.
+    pub fn try_server_info(&self, address: IpAddr) -> bool {
+        tick_window(
+            &mut self.server_info.lock(),
+            address.to_canonical(),
+            SERVER_INFO_LIMIT,
+            PREAUTH_WINDOW,
+            Instant::now(),
+        )
+    }
+
+    /// Returns `true` if a handshake from this address should be processed. At most `limit` handshakes are processed
+    /// per address in every window, 0 means unlimited. Addresses with too many failed handshakes are blocked regardless.
+    pub fn try_handshake(&self, address: IpAddr, limit: u32) -> bool {
+        let address = address.to_canonical();
+        let now = Instant::now();
+
+        if window_full(
+            &self.handshake_failures.lock(),
+            &address,
+            HANDSHAKE_FAILURE_LIMIT,
+            HANDSHAKE_FAILURE_WINDOW,
+            now,
+        ) {
+            return false;
+        }
+
+        tick_window(&mut self.handshakes.lock(), address, limit, PREAUTH_WINDOW, now)
+    }
+
+    /// Records a handshake from this address that failed, for example because it was malformed or the connection already did one.
+    pub fn record_handshake_failure(&self, address: IpAddr) {
+        tick_window(
+            &mut self.handshake_failures.lock(),
+            address.to_canonical(),
+            HANDSHAKE_FAILURE_LIMIT,
+            HANDSHAKE_FAILURE_WINDOW,
+            Instant::now(),
+        );
+    }
+
+    /// Returns `true` if an attempt to recover a disconnected session from this address should be processed.
+    pub fn try_recovery(&self, address: IpAddr) -> bool {
+        tick_window(
+            &mut self.recoveries.lock(),
+            address.to_canonical(),
+            RECOVERY_LIMIT,
+            PREAUTH_WINDOW,
+            Instant::now(),
+        )
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use globed_shared::{IntMap, SyncMutex};
+use rustc_hash::FxHashMap;
+
+use crate::data::{LevelId, PlayerAccountData};
+
+/// maximum amount of cached profiles, once reached the least recently used one is evicted
+const PROFILE_CACHE_CAPACITY: usize = 1024;
+/// how long the profiles of everyone on a level are reused for, so that a crowd of players loading into
+/// the same level at once only needs to gather them once
+const LEVEL_PROFILES_LIFETIME: Duration = Duration::from_millis(500);
+/// once this many levels are cached, expired ones get removed
+const LEVEL_PRUNE_THRESHOLD: usize = 64;
+
+struct CachedProfile {
+    data: PlayerAccountData,
+    last_used: u64,
+}
+
+struct CachedLevelProfiles {
+    gathered: Instant,
+    players: Arc<Vec<PlayerAccountData>>,
+}
+
+/// `None` until the profiles of the level are gathered for the first time
+type LevelProfilesEntry = Arc<SyncMutex<Option<CachedLevelProfiles>>>;
+
+#[derive(Default)]
+struct ProfileCacheInner {
+    profiles: IntMap<i32, CachedProfile>,
+    /// account IDs of the cached profiles by when they were last used, oldest first
+    usage: BTreeMap<u64, i32>,
+    clock: u64,
+    revisions: IntMap<i32, u32>,
+    last_revision: u32,
+}
+
+/// Caches the account data of connected players, so that profile requests don't have to look through every connection.
+/// Entries must be invalidated whenever the account data of a player changes, or when they disconnect.
+#[derive(Default)]
+pub struct ProfileCache {
+    inner: SyncMutex<ProfileCacheInner>,
+    /// profiles of everyone on a level, keyed by room and level ID. Every level has its own lock,
+    /// so that gathering the profiles of one level doesn't hold up requests for any other level
+    levels: SyncMutex<FxHashMap<(u32, LevelId), LevelProfilesEntry>>,
+}
+
+impl ProfileCache {
+    /// Returns the cached profile, or calls `f` to fetch it and caches the result.
+    ///
+    /// The cache stays locked while `f` runs, so that a concurrent `invalidate` can't be overwritten with outdated data.
+    pub fn get_or_fetch<F: FnOnce() -> Option<PlayerAccountData>>(&self, account_id: i32, f: F) -> Option<PlayerAccountData> {
+        let mut inner = self.inner.lock();
+        inner.clock += 1;
+        let now = inner.clock;
+
+        if let Some(profile) = inner.profiles.get_mut(&account_id) {
+            let last_used = std::mem::replace(&mut profile.last_used, now);
+            let data = profile.data.clone();
+
+            inner.usage.remove(&last_used);
+            inner.usage.insert(now, account_id);
+
+            return Some(data);
+        }
+
+        let data = f()?;
+
+        if inner.profiles.len() >= PROFILE_CACHE_CAPACITY {
+            if let Some((_, oldest)) = inner.usage.pop_first() {
+                inner.profiles.remove(&oldest);
+            }
+        }
+
+        inner.usage.insert(now, account_id);
+        inner.profiles.insert(
+            account_id,
+            CachedProfile {
+                data: data.clone(),
+                last_used: now,
+            },
+        );
+
+        Some(data)
+    }
+
+    /// Returns the profiles of everyone on a level, calling `f` to gather them unless it was done very recently.
+    ///
+    /// The result may be slightly outdated, so it is only meant for requests of all players on a level.
+    /// Concurrent requests for the same level wait for a single gather instead of each doing their own.
+    pub fn get_or_gather_level<F: FnOnce() -> Vec<PlayerAccountData>>(&self, room_id: u32, level_id: LevelId, f: F) -> Arc<Vec<PlayerAccountData>> {
+        let level = {
+            let mut levels = self.levels.lock();
+
+            if levels.len() > LEVEL_PRUNE_THRESHOLD {
+                // a locked level is being gathered right now, so it's about to be fresh
+                levels.retain(|_, level| {
+                    level
+                        .try_lock()
+                        .is_none_or(|cached| cached.as_ref().is_some_and(|cached| cached.gathered.elapsed() < LEVEL_PROFILES_LIFETIME))
+                });
+            }
+
+            levels.entry((room_id, level_id)).or_default().clone()
+        };
+
+        let mut cached = level.lock();
+
+        if let Some(cached) = cached.as_ref().filter(|cached| cached.gathered.elapsed() < LEVEL_PROFILES_LIFETIME) {
+            return cached.players.clone();
+        }
+
+        let players = Arc::new(f());
+        *cached = Some(CachedLevelProfiles {
+            gathered: Instant::now(),
+            players: players.clone(),
+        });
+
+        players
+    }
+
+    /// Returns the revision of the profile, which changes every time the profile is invalidated.
+    /// Revisions come from a single counter and are never reused, so a client that knows the current revision
+    /// of an account already has its latest profile.
+    pub fn get_revision(&self, account_id: i32) -> u32 {
+        let mut inner = self.inner.lock();
+
+        if let Some(revision) = inner.revisions.get(&account_id) {
+            return *revision;
+        }
+
+        inner.last_revision += 1;
+        let revision = inner.last_revision;
+        inner.revisions.insert(account_id, revision);
+
+        revision
+    }
+
+    pub fn invalidate(&self, account_id: i32) {
+        let mut inner = self.inner.lock();
+        if let Some(profile) = inner.profiles.remove(&account_id) {
+            inner.usage.remove(&profile.last_used);
+        }
+        inner.revisions.remove(&account_id);
+    }
+}
@@ -211,10 +211,38 @@ impl RoomManager {
         }
     }
 
+    /// Adds the player to the given room, and to the level they are currently on, so that moving between rooms
+    /// does not make them disappear from the level until they rejoin it. Spectators are only added to the level.
+    pub fn add_with_any(&self, room_id: u32, account_id: i32, level_id: LevelId, spectator: bool) {
+        self.with_any(room_id, |pm| {
+            if spectator {
+                if level_id != 0 {
+                    pm.manager.add_spectator_to_level(level_id, account_id);
+                }
+
+                return;
+            }
+
+            pm.manager.create_player(account_id);
+
+            if level_id != 0 {
+                pm.manager.add_to_level(level_id, account_id);
+            }
+        });
+    }
+
     // Removes the player from the given room, returns `true` if the player was the owner of the room,
     // and either a new owner has now been chosen, or the room has been deleted.
-    pub fn remove_with_any(&self, room_id: u32, account_id: i32, level_id: LevelId) -> bool {
+    pub fn remove_with_any(&self, room_id: u32, account_id: i32, level_id: LevelId, spectator: bool) -> bool {
         let was_owner = self.with_any(room_id, |pm| {
+            if spectator {
+                if level_id != 0 {
+                    pm.manager.remove_spectator_from_level(level_id, account_id);
+                }
+
+                return false;
+            }
+
             let was_owner = pm.remove_player(account_id);
 
             if level_id != 0 {
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use globed_shared::{IntMap, SyncMutex, UserEntry};
+
+use crate::bridge::CentralBridge;
+
+/// once the cache grows past this size, expired entries get removed from it
+const PRUNE_THRESHOLD: usize = 1024;
+
+struct CachedUser {
+    fetched: Instant,
+    entry: UserEntry,
+}
+
+/// Caches user data fetched from the central server for a short period of time,
+/// so that a client reconnecting repeatedly does not cause a request to the central server every time.
+#[derive(Default)]
+pub struct UserCache {
+    users: SyncMutex<IntMap<i32, CachedUser>>,
+}
+
+impl UserCache {
+    /// Returns the cached data of the given account, if it was fetched less than `user_data_cache_ttl` seconds ago.
+    pub fn get(&self, bridge: &CentralBridge, account_id: i32) -> Option<UserEntry> {
+        let ttl = Self::ttl(bridge)?;
+
+        let users = self.users.lock();
+        let user = users.get(&account_id)?;
+
+        (user.fetched.elapsed() < ttl).then(|| user.entry.clone())
+    }
+
+    /// Stores freshly fetched data of a user.
+    pub fn insert(&self, bridge: &CentralBridge, entry: &UserEntry) {
+        let Some(ttl) = Self::ttl(bridge) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut users = self.users.lock();
+
+        if users.len() > PRUNE_THRESHOLD {
+            users.retain(|_, user| now - user.fetched < ttl);
+        }
+
+        users.insert(
+            entry.account_id,
+            CachedUser {
+                fetched: now,
+                entry: entry.clone(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, account_id: i32) {
+        self.users.lock().remove(&account_id);
+    }
+
+    fn ttl(bridge: &CentralBridge) -> Option<Duration> {
+        let ttl = bridge.central_conf.lock().user_data_cache_ttl;
+        (ttl != 0).then(|| Duration::from_secs(u64::from(ttl)))
+    }
+}
@@ -0,0 +1,32 @@
+use globed_shared::{warn, IntSet, SyncMutex};
+
+/// Keeps the list of whitelisted accounts loaded from a local file. These are allowed in
+/// on top of accounts that are whitelisted on the central server.
+#[derive(Default)]
+pub struct WhitelistManager {
+    accounts: SyncMutex<IntSet<i32>>,
+}
+
+impl WhitelistManager {
+    /// Replaces the whitelisted accounts with the ones in `contents`, one account ID per line.
+    /// Empty lines and lines starting with `#` are skipped. Returns the amount of loaded accounts.
+    pub fn load_local(&self, contents: &str) -> usize {
+        let mut accounts = self.accounts.lock();
+        accounts.clear();
+
+        for line in contents.lines().map(str::trim).filter(|x| !x.is_empty() && !x.starts_with('#')) {
+            match line.parse::<i32>() {
+                Ok(id) if id > 0 => {
+                    accounts.insert(id);
+                }
+                _ => warn!("ignoring invalid whitelist entry: '{line}'"),
+            }
+        }
+
+        accounts.len()
+    }
+
+    pub fn contains(&self, account_id: i32) -> bool {
+        self.accounts.lock().contains(&account_id)
+    }
+}
@@ -0,0 +1,519 @@
+use std::{
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+use globed_shared::{debug, info, warn, SyncMutex};
+
+use crate::{
+    client::{ErrorClass, PacketHandlingError},
+    data::*,
+    server::GameServer,
+    tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    },
+};
+
+/// Counters that get exported on the metrics endpoint. Everything is a relaxed atomic, so updating them is nearly free.
+#[derive(Default)]
+pub struct ServerMetrics {
+    pub bytes_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    pub voice_packets: AtomicU64,
+    pub voice_packets_dropped: AtomicU64,
+    pub voice_packets_gated: AtomicU64,
+    pub voice_packets_over_bitrate: AtomicU64,
+    pub voice_recording_dropped: AtomicU64,
+    /// login tokens issued by the central server that were valid
+    pub central_verify_success: AtomicU64,
+    /// login tokens that were invalid or expired
+    pub central_verify_failure: AtomicU64,
+    /// packets dropped because their checksum did not match
+    pub corrupted_packets: AtomicU64,
+    /// amount of open connections in every `ConnectionPhase`
+    connections: [AtomicU32; ConnectionPhase::ALL.len()],
+    /// amount of errors while handling packets, for every `ErrorClass`
+    packet_errors: [AtomicU64; ErrorClass::ALL.len()],
+    pub packet_counts: PacketCounters,
+}
+
+/// IDs of every packet the server has a handler for, sorted. Anything else is counted together, so that a client
+/// sending random IDs can't create an unbounded amount of series.
+const KNOWN_PACKET_IDS: [u16; 45] = [
+    PingPacket::PACKET_ID,
+    CryptoHandshakeStartPacket::PACKET_ID,
+    KeepalivePacket::PACKET_ID,
+    LoginPacket::PACKET_ID,
+    ClaimThreadPacket::PACKET_ID,
+    DisconnectPacket::PACKET_ID,
+    KeepaliveTCPPacket::PACKET_ID,
+    KeepaliveEchoPacket::PACKET_ID,
+    RequestServerInfoPacket::PACKET_ID,
+    ConnectionTestPacket::PACKET_ID,
+    SyncIconsPacket::PACKET_ID,
+    RequestGlobalPlayerListPacket::PACKET_ID,
+    RequestLevelListPacket::PACKET_ID,
+    RequestPlayerCountPacket::PACKET_ID,
+    RequestPlayerListPacket::PACKET_ID,
+    RequestPlayerProfilesPacket::PACKET_ID,
+    LevelJoinPacket::PACKET_ID,
+    LevelLeavePacket::PACKET_ID,
+    PlayerDataPacket::PACKET_ID,
+    PlayerMetadataPacket::PACKET_ID,
+    LevelStatsPacket::PACKET_ID,
+    RequestLeaderboardPacket::PACKET_ID,
+    RequestChangedProfilesPacket::PACKET_ID,
+    VoicePacket::PACKET_ID,
+    ChatMessagePacket::PACKET_ID,
+    VoiceMutePacket::PACKET_ID,
+    VoiceSettingsPacket::PACKET_ID,
+    CreateRoomPacket::PACKET_ID,
+    JoinRoomPacket::PACKET_ID,
+    LeaveRoomPacket::PACKET_ID,
+    RequestRoomPlayerListPacket::PACKET_ID,
+    UpdateRoomSettingsPacket::PACKET_ID,
+    RoomSendInvitePacket::PACKET_ID,
+    RequestRoomListPacket::PACKET_ID,
+    AdminAuthPacket::PACKET_ID,
+    AdminSendNoticePacket::PACKET_ID,
+    AdminDisconnectPacket::PACKET_ID,
+    AdminGetUserStatePacket::PACKET_ID,
+    AdminUpdateUserPacket::PACKET_ID,
+    AdminSetMaintenancePacket::PACKET_ID,
+    AdminGetPlayerStatsPacket::PACKET_ID,
+    AdminSetLogLevelPacket::PACKET_ID,
+    AdminGetServerStatsPacket::PACKET_ID,
+    AdminFindPlayerPacket::PACKET_ID,
+    AdminForceDisconnectPacket::PACKET_ID,
+];
+
+// `packet_counter_index` does a binary search, so adding an ID in the wrong place fails the build
+const _: () = {
+    let mut i = 1;
+    while i < KNOWN_PACKET_IDS.len() {
+        assert!(
+            KNOWN_PACKET_IDS[i - 1] < KNOWN_PACKET_IDS[i],
+            "KNOWN_PACKET_IDS must be sorted and unique"
+        );
+        i += 1;
+    }
+};
+
+/// Returns the index of the counter for this packet ID, `KNOWN_PACKET_IDS.len()` being the one for unknown IDs.
+#[inline]
+fn packet_counter_index(packet_id: u16) -> usize {
+    KNOWN_PACKET_IDS.binary_search(&packet_id).unwrap_or(KNOWN_PACKET_IDS.len())
+}
+
+/// Amount of received packets for every packet ID, used both for the whole server and for every connection.
+/// This is an array of atomics, so that counting a packet never has to take a lock.
+pub struct PacketCounters {
+    /// one counter per known packet ID, plus one for the unknown IDs
+    counts: [AtomicU64; KNOWN_PACKET_IDS.len() + 1],
+}
+
+impl Default for PacketCounters {
+    fn default() -> Self {
+        Self {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl PacketCounters {
+    #[inline]
+    pub fn record(&self, packet_id: u16) {
+        self.counts[packet_counter_index(packet_id)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the amount of received packets per packet ID, sorted by the packet ID and leaving out IDs that were never received.
+    /// Packets with an ID the server has no handler for are counted under ID 0.
+    pub fn get_all(&self) -> Vec<(u16, u64)> {
+        let (known, unknown) = self.counts.split_at(KNOWN_PACKET_IDS.len());
+
+        std::iter::once((0, &unknown[0]))
+            .chain(KNOWN_PACKET_IDS.into_iter().zip(known))
+            .map(|(id, count)| (id, count.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count != 0)
+            .collect()
+    }
+}
+
+impl ServerMetrics {
+    pub fn connection_count(&self, phase: ConnectionPhase) -> u32 {
+        self.connections[phase as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn record_packet_error(&self, error: &PacketHandlingError) {
+        self.packet_errors[error.class() as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn packet_error_count(&self, class: ErrorClass) -> u64 {
+        self.packet_errors[class as usize].load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn add_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// How far a connection got, for telling real players apart from scanners and half-open connections.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConnectionPhase {
+    /// waiting for the crypto handshake
+    Handshake = 0,
+    /// handshake done, but not logged in yet
+    Unauthenticated = 1,
+    /// logged in, including players that lost connection and can still reconnect
+    Authenticated = 2,
+}
+
+impl ConnectionPhase {
+    pub const ALL: [Self; 3] = [Self::Handshake, Self::Unauthenticated, Self::Authenticated];
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::Unauthenticated => "unauthenticated",
+            Self::Authenticated => "authenticated",
+        }
+    }
+}
+
+/// Counts a connection in the phase it is currently in, until dropped. Moves along with the connection
+/// when its thread gets upgraded or downgraded.
+pub struct ConnectionPhaseTracker {
+    metrics: &'static ServerMetrics,
+    phase: AtomicU8,
+}
+
+impl ConnectionPhaseTracker {
+    pub fn new(metrics: &'static ServerMetrics) -> Self {
+        metrics.connections[ConnectionPhase::Handshake as usize].fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            metrics,
+            phase: AtomicU8::new(ConnectionPhase::Handshake as u8),
+        }
+    }
+
+    pub fn set(&self, phase: ConnectionPhase) {
+        let previous = self.phase.swap(phase as u8, Ordering::Relaxed);
+
+        if previous != phase as u8 {
+            self.metrics.connections[previous as usize].fetch_sub(1, Ordering::Relaxed);
+            self.metrics.connections[phase as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for ConnectionPhaseTracker {
+    fn drop(&mut self) {
+        let phase = self.phase.load(Ordering::Relaxed);
+        self.metrics.connections[phase as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Traffic of a single connection, so that it is possible to find out who is responsible for a spike in bandwidth.
+#[derive(Default)]
+pub struct ConnectionStats {
+    pub bytes_sent: AtomicU64,
+    pub bytes_received: AtomicU64,
+    pub packets_sent: AtomicU64,
+    pub packets_received: AtomicU64,
+    /// most recent round trip time, in milliseconds
+    pub rtt_last: AtomicU32,
+    /// smoothed round trip time in milliseconds, 0 until measured at least once
+    pub rtt_average: AtomicU32,
+    /// amount of received packets, per packet ID
+    packet_counts: PacketCounters,
+    voice_timing: SyncMutex<VoiceTiming>,
+}
+
+/// upper bounds (in milliseconds) of the buckets of the voice packet interval histogram
+pub const VOICE_INTERVAL_BUCKETS: [u64; 5] = [50, 100, 200, 400, 1000];
+
+/// Timing of the voice packets sent by a single connection.
+#[derive(Default)]
+struct VoiceTiming {
+    last_arrival: Option<Instant>,
+    last_interval: Option<Duration>,
+    /// smoothed difference between consecutive intervals, like the interarrival jitter in RTP
+    jitter: f64,
+    histogram: [u64; VOICE_INTERVAL_BUCKETS.len()],
+}
+
+impl ConnectionStats {
+    #[inline]
+    pub fn record_received(&self, packet_id: u16, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.packets_received.fetch_add(1, Ordering::Relaxed);
+        self.packet_counts.record(packet_id);
+    }
+
+    /// Records a round trip time measurement. The average is smoothed the same way TCP does it, giving the new sample 1/8 weight.
+    pub fn record_rtt(&self, rtt: u32) {
+        self.rtt_last.store(rtt, Ordering::Relaxed);
+
+        // only ever updated by the connection's own thread, so a load + store is fine
+        let average = self.rtt_average.load(Ordering::Relaxed);
+        let average = if average == 0 { rtt } else { (average * 7 + rtt) / 8 };
+        self.rtt_average.store(average, Ordering::Relaxed);
+    }
+
+    /// Records the arrival of a voice packet. Gaps longer than the last histogram bucket are assumed to be
+    /// pauses in speech rather than network issues, and aren't counted.
+    pub fn record_voice_arrival(&self) {
+        let now = Instant::now();
+        let mut timing = self.voice_timing.lock();
+        let last_arrival = timing.last_arrival.replace(now);
+
+        let Some(interval) = last_arrival.map(|last| now.duration_since(last)) else {
+            return;
+        };
+
+        let interval_ms = interval.as_millis() as u64;
+        let Some(bucket) = VOICE_INTERVAL_BUCKETS.iter().position(|bound| interval_ms < *bound) else {
+            timing.last_interval = None;
+            return;
+        };
+
+        timing.histogram[bucket] += 1;
+
+        if let Some(last_interval) = timing.last_interval.replace(interval) {
+            let difference = (interval.as_secs_f64() - last_interval.as_secs_f64()).abs() * 1000.0;
+            timing.jitter += (difference - timing.jitter) / 16.0;
+        }
+    }
+
+    /// Returns the voice jitter in milliseconds.
+    pub fn voice_jitter(&self) -> u32 {
+        self.voice_timing.lock().jitter.round() as u32
+    }
+
+    /// Returns how many voice packets arrived within each of the `VOICE_INTERVAL_BUCKETS` after the previous one.
+    pub fn voice_interval_histogram(&self) -> Vec<u64> {
+        self.voice_timing.lock().histogram.to_vec()
+    }
+
+    /// Returns the amount of received packets per packet ID, sorted by the packet ID. Unknown IDs are counted under 0.
+    pub fn packet_counts(&self) -> Vec<(u16, u64)> {
+        self.packet_counts.get_all()
+    }
+}
+
+/// how long the metrics and health check endpoints wait before accepting again after a failed accept,
+/// so that running out of file descriptors doesn't turn into a busy loop
+pub const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(250);
+/// how long a request to the metrics or health check endpoint may take, including reading the request and writing the response
+pub const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the metrics in the Prometheus text format on the given address. Every request gets the same response, regardless of the path.
+pub async fn serve(game_server: &'static GameServer, address: SocketAddr) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(x) => x,
+        Err(err) => {
+            warn!("failed to bind the metrics endpoint to {address}: {err}");
+            return;
+        }
+    };
+
+    info!("Metrics are available on http://{address}/metrics");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(x) => x,
+            Err(err) => {
+                warn!("failed to accept a metrics connection: {err}");
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            match tokio::time::timeout(HTTP_REQUEST_TIMEOUT, respond(game_server, stream)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => debug!("failed to serve metrics to {peer}: {err}"),
+                Err(_) => debug!("metrics request from {peer} timed out"),
+            }
+        });
+    }
+}
+
+async fn respond(game_server: &'static GameServer, mut stream: TcpStream) -> std::io::Result<()> {
+    // we don't care about the request itself, just wait for it to arrive
+    let mut buf = [0u8; 1024];
+    let _ = stream.read(&mut buf).await?;
+
+    let body = render(game_server);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn render(game_server: &GameServer) -> String {
+    let metrics = &game_server.state.metrics;
+
+    let authenticated = u64::from(game_server.state.get_player_count());
+    let connections = game_server.clients.lock().len() as u64;
+    let unauthenticated = game_server.unauthorized_clients.lock().len() as u64;
+    let (max_players, soft_max_players) = {
+        let conf = game_server.bridge.central_conf.lock();
+        (u64::from(conf.max_players), u64::from(conf.soft_max_players))
+    };
+
+    let mut out = String::new();
+
+    let mut write_metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = write!(out, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n");
+    };
+
+    write_metric("globed_player_count", "gauge", "Amount of logged in players", authenticated);
+    write_metric(
+        "globed_max_players",
+        "gauge",
+        "Maximum amount of players before new logins are rejected, 0 if unlimited",
+        max_players,
+    );
+    write_metric(
+        "globed_soft_max_players",
+        "gauge",
+        "Amount of players at which joining players are suggested another server, 0 if disabled",
+        soft_max_players,
+    );
+    write_metric(
+        "globed_connections",
+        "gauge",
+        "Amount of open connections, both authenticated and not",
+        connections + unauthenticated,
+    );
+    write_metric(
+        "globed_unauthenticated_connections",
+        "gauge",
+        "Amount of connections that have not logged in yet",
+        unauthenticated,
+    );
+
+    let _ = write!(
+        out,
+        "# HELP globed_connections_by_phase Amount of open connections by how far they got (handshake, unauthenticated or authenticated)\n\
+         # TYPE globed_connections_by_phase gauge\n"
+    );
+    for phase in ConnectionPhase::ALL {
+        let _ = writeln!(
+            out,
+            "globed_connections_by_phase{{phase=\"{}\"}} {}",
+            phase.label(),
+            metrics.connection_count(phase)
+        );
+    }
+
+    let _ = write!(
+        out,
+        "# HELP globed_packets_received_total Amount of packets received from clients for every packet ID, \"unknown\" for IDs without a handler\n\
+         # TYPE globed_packets_received_total counter\n"
+    );
+    for (packet_id, count) in metrics.packet_counts.get_all() {
+        if packet_id == 0 {
+            let _ = writeln!(out, "globed_packets_received_total{{id=\"unknown\"}} {count}");
+        } else {
+            let _ = writeln!(out, "globed_packets_received_total{{id=\"{packet_id}\"}} {count}");
+        }
+    }
+
+    let _ = write!(
+        out,
+        "# HELP globed_packet_errors_total Amount of errors while handling packets from clients, by the kind of error\n\
+         # TYPE globed_packet_errors_total counter\n"
+    );
+    for class in ErrorClass::ALL {
+        let _ = writeln!(
+            out,
+            "globed_packet_errors_total{{class=\"{}\"}} {}",
+            class.label(),
+            metrics.packet_error_count(class)
+        );
+    }
+
+    let mut write_metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = write!(out, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n");
+    };
+
+    write_metric(
+        "globed_voice_packets_total",
+        "counter",
+        "Amount of voice packets received from clients",
+        metrics.voice_packets.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_voice_packets_dropped_total",
+        "counter",
+        "Amount of voice packets not delivered to a client because its message queue was full",
+        metrics.voice_packets_dropped.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_voice_packets_gated_total",
+        "counter",
+        "Amount of voice packets not sent to anyone because they only contained silence",
+        metrics.voice_packets_gated.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_voice_packets_over_bitrate_total",
+        "counter",
+        "Amount of voice packets dropped because they were encoded with a higher bitrate than allowed",
+        metrics.voice_packets_over_bitrate.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_voice_recording_dropped_total",
+        "counter",
+        "Amount of voice packets missing from voice recordings because they could not be written fast enough",
+        metrics.voice_recording_dropped.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_corrupted_packets_total",
+        "counter",
+        "Amount of packets dropped because they had a checksum that did not match their contents",
+        metrics.corrupted_packets.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_bytes_sent_total",
+        "counter",
+        "Amount of bytes sent to clients",
+        metrics.bytes_sent.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_bytes_received_total",
+        "counter",
+        "Amount of bytes received from clients",
+        metrics.bytes_received.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_central_verify_success_total",
+        "counter",
+        "Amount of login tokens that were successfully verified",
+        metrics.central_verify_success.load(Ordering::Relaxed),
+    );
+    write_metric(
+        "globed_central_verify_failure_total",
+        "counter",
+        "Amount of login tokens that failed verification",
+        metrics.central_verify_failure.load(Ordering::Relaxed),
+    );
+
+    out
+}
@@ -1,16 +1,17 @@
 use std::{
     collections::VecDeque,
-    net::{SocketAddr, SocketAddrV4},
+    net::SocketAddr,
+    path::PathBuf,
     sync::{atomic::Ordering, Arc},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use globed_shared::{
-    anyhow::{self, anyhow, bail},
+    anyhow::{self, anyhow},
     crypto_box::{aead::OsRng, PublicKey, SecretKey},
     esp::ByteBufferExtWrite as _,
     logger::*,
-    SyncMutex, UserEntry,
+    IntSet, SyncMutex, UserEntry, PROTOCOL_VERSION,
 };
 use rustc_hash::FxHashMap;
 use tokio::{
@@ -31,16 +32,34 @@ use crate::{
 };
 
 use crate::{
+    audit::AuditLog,
     bridge::{self, CentralBridge},
     client::{thread::ClientThreadOutcome, unauthorized::UnauthorizedThread, ClientThread, ServerThreadMessage, UnauthorizedThreadOutcome},
     data::*,
+    health, metrics,
     state::ServerState,
+    util::{buffer_pool, proxy_protocol},
+    voice_recorder::{VoiceRecorder, VoiceRecordingConfig},
+    webhook::WebhookMessage,
 };
 
 const INLINE_BUFFER_SIZE: usize = 164;
 const MAX_UDP_PACKET_SIZE: usize = 65536;
 const LARGE_BUFFER_SIZE: usize = 2usize.pow(19); // 2^19, 0.5mb
 
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_MESSAGE_QUEUE_CAPACITY: usize = 64;
+pub const DEFAULT_VOICE_STRIKE_LIMIT: u32 = 10;
+pub const DEFAULT_SLOW_HANDLER_THRESHOLD: Duration = Duration::from_millis(50);
+pub const DEFAULT_LOGIN_TIMEOUT: Duration = Duration::from_secs(5);
+/// how many players are sent at most in a single `PlayerListPacket`
+pub const PLAYER_LIST_PAGE_SIZE: usize = 100;
+
+/// lowest accepted value for `config_refresh_interval`, so a misconfigured central server does not get flooded with requests
+const MIN_CONFIG_REFRESH_INTERVAL: u64 = 30;
+/// how often expired temporary bans are removed from memory
+const BAN_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
 const MARKER_CONN_INITIAL: u8 = 0xe0;
 const MARKER_CONN_RECOVERY: u8 = 0xe1;
 
@@ -50,25 +69,125 @@ enum EitherClientThread {
     None,
 }
 
+/// Game server settings that are not controlled by the central server.
+pub struct GameServerConfiguration {
+    /// whether incoming tcp connections start with a PROXY protocol header
+    pub proxy_protocol: bool,
+    /// address to serve prometheus metrics on, if enabled
+    pub metrics_address: Option<SocketAddr>,
+    pub health_address: Option<SocketAddr>,
+    /// how often to generate a new keypair for the handshakes, if enabled
+    pub key_rotation_interval: Option<Duration>,
+    /// how many messages can wait to be sent to a single client before voice packets for it start getting dropped
+    pub message_queue_capacity: usize,
+    /// whether consecutive queued voice packets get coalesced into a single datagram,
+    /// for clients that said they support `VoiceBroadcastBatchPacket` in the handshake
+    pub voice_batching: bool,
+    /// if set, received voice gets written to disk
+    pub voice_recording: Option<VoiceRecordingConfig>,
+    /// whether large packets can be compressed, for clients that support it
+    pub compression: bool,
+    /// file that admin actions get appended to, if enabled
+    pub audit_log: Option<PathBuf>,
+    /// whether a thread can only be claimed from a udp address with the same IP as its tcp connection.
+    /// off by default, as the addresses can differ for dual-stack users, behind CGNAT or with separate proxies
+    pub require_udp_address_match: bool,
+    /// packet handlers taking longer than this get logged, if enabled
+    pub slow_handler_threshold: Option<Duration>,
+    /// upper limit for the player limit of rooms, 0 means rooms can be of any size
+    pub max_room_size: u32,
+    /// a client gets disconnected once it has sent this many more malformed voice packets than valid ones, 0 disables it
+    pub voice_strike_limit: u32,
+    /// connections that finished the handshake but don't send a `LoginPacket` within this time are dropped, if enabled
+    pub login_timeout: Option<Duration>,
+}
+
+impl Default for GameServerConfiguration {
+    fn default() -> Self {
+        Self {
+            proxy_protocol: false,
+            metrics_address: None,
+            health_address: None,
+            key_rotation_interval: None,
+            message_queue_capacity: DEFAULT_MESSAGE_QUEUE_CAPACITY,
+            voice_batching: false,
+            voice_recording: None,
+            compression: true,
+            audit_log: None,
+            require_udp_address_match: false,
+            slow_handler_threshold: Some(DEFAULT_SLOW_HANDLER_THRESHOLD),
+            max_room_size: 0,
+            voice_strike_limit: DEFAULT_VOICE_STRIKE_LIMIT,
+            login_timeout: Some(DEFAULT_LOGIN_TIMEOUT),
+        }
+    }
+}
+
+/// Keypair used for the crypto handshake. Every connection derives its own box from the keypair that was current
+/// at the time of its handshake, so rotating it does not affect clients that are already connected.
+#[derive(Clone)]
+pub struct ServerKeys {
+    pub secret_key: SecretKey,
+    pub public_key: PublicKey,
+}
+
+impl ServerKeys {
+    fn generate() -> Self {
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let public_key = secret_key.public_key();
+
+        Self { secret_key, public_key }
+    }
+}
+
+/// a log level changed at runtime, which gets reverted once it expires
+#[derive(Clone, Copy)]
+struct LogLevelOverride {
+    /// the level from before the first change
+    original: LogLevelFilter,
+    /// incremented on every change, so that only the most recent change gets reverted
+    generation: u64,
+}
+
 pub struct GameServer {
     pub state: ServerState,
     pub tcp_socket: TcpListener,
     pub udp_socket: UdpSocket,
     /// map udp peer : thread
-    pub clients: SyncMutex<FxHashMap<SocketAddrV4, Arc<ClientThread>>>,
+    pub clients: SyncMutex<FxHashMap<SocketAddr, Arc<ClientThread>>>,
     pub unauthorized_clients: SyncMutex<VecDeque<Arc<UnauthorizedThread>>>,
     pub unclaimed_threads: SyncMutex<VecDeque<Arc<ClientThread>>>,
-    pub secret_key: SecretKey,
-    pub public_key: PublicKey,
+    keys: SyncMutex<ServerKeys>,
     pub bridge: CentralBridge,
     pub standalone: bool,
+    pub config: GameServerConfiguration,
     pub large_packet_buffer: SyncMutex<Box<[u8]>>,
+    pub voice_recorder: Option<VoiceRecorder>,
+    pub audit_log: AuditLog,
+    log_level_override: SyncMutex<Option<LogLevelOverride>>,
+    pub start_time: Instant,
 }
 
 impl GameServer {
-    pub fn new(tcp_socket: TcpListener, udp_socket: UdpSocket, state: ServerState, bridge: CentralBridge, standalone: bool) -> Self {
-        let secret_key = SecretKey::generate(&mut OsRng);
-        let public_key = secret_key.public_key();
+    pub fn new(
+        tcp_socket: TcpListener,
+        udp_socket: UdpSocket,
+        state: ServerState,
+        bridge: CentralBridge,
+        standalone: bool,
+        config: GameServerConfiguration,
+    ) -> Self {
+        let voice_recorder = config.voice_recording.clone().map(VoiceRecorder::start);
+
+        let audit_log = config.audit_log.as_ref().map_or_else(AuditLog::disabled, |path| {
+            AuditLog::open(path).unwrap_or_else(|e| {
+                warn!(
+                    "failed to open the audit log at {}, admin actions will not be recorded: {e}",
+                    path.display()
+                );
+                AuditLog::disabled()
+            })
+        });
 
         Self {
             state,
@@ -77,14 +196,64 @@ impl GameServer {
             clients: SyncMutex::new(FxHashMap::default()),
             unauthorized_clients: SyncMutex::new(VecDeque::new()),
             unclaimed_threads: SyncMutex::new(VecDeque::new()),
-            secret_key,
-            public_key,
+            keys: SyncMutex::new(ServerKeys::generate()),
             bridge,
             standalone,
+            config,
             large_packet_buffer: SyncMutex::new(vec![0; LARGE_BUFFER_SIZE].into_boxed_slice()),
+            voice_recorder,
+            audit_log,
+            log_level_override: SyncMutex::new(None),
+            start_time: Instant::now(),
         }
     }
 
+    /// the keypair that new handshakes should use
+    pub fn current_keys(&self) -> ServerKeys {
+        self.keys.lock().clone()
+    }
+
+    /// Changes the log level for `duration`, after which the level that was used before the first change is restored.
+    /// Changing it again before then replaces the previous change, including its duration.
+    pub fn set_temporary_log_level(&'static self, level: LogLevelFilter, duration: Duration) {
+        let generation = {
+            let mut ovr = self.log_level_override.lock();
+
+            let new = LogLevelOverride {
+                original: ovr.map_or_else(log::max_level, |o| o.original),
+                generation: ovr.map_or(0, |o| o.generation + 1),
+            };
+
+            *ovr = Some(new);
+            new.generation
+        };
+
+        log::set_max_level(level);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+
+            let mut ovr = self.log_level_override.lock();
+            if let Some(o) = ovr.filter(|o| o.generation == generation) {
+                log::set_max_level(o.original);
+                *ovr = None;
+
+                warn!("Log level changed by an admin has expired, restored it to {}", o.original);
+            }
+        });
+    }
+
+    /// how long the server has been running for, in seconds
+    pub fn uptime(&self) -> u64 {
+        self.start_time.elapsed().as_secs()
+    }
+
+    /// same as `uptime`, but in milliseconds
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn uptime_millis(&self) -> u64 {
+        self.start_time.elapsed().as_millis() as u64
+    }
+
     pub async fn run(&'static self) -> ! {
         info!(
             "Server launched on {} (version: {})",
@@ -94,18 +263,32 @@ impl GameServer {
 
         self.state.room_manager.set_game_server(self);
 
-        // spawn central conf refresher (runs every 5 minutes)
+        // spawn central conf refresher (runs every 5 minutes by default)
         if !self.standalone {
             tokio::spawn(async {
-                let mut interval = tokio::time::interval(Duration::from_mins(5));
-                interval.tick().await;
-
                 loop {
-                    interval.tick().await;
-                    match self.refresh_bootdata().await {
-                        Ok(()) => debug!("refreshed central server configuration"),
-                        Err(e) => error!("failed to refresh configuration from the central server: {e}"),
+                    // re-read every time, as the interval itself can change with a refresh
+                    let interval = self.bridge.central_conf.lock().config_refresh_interval.max(MIN_CONFIG_REFRESH_INTERVAL);
+                    tokio::time::sleep(Duration::from_secs(interval)).await;
+
+                    self.refresh_bootdata_logged().await;
+                }
+            });
+
+            // also refresh when receiving SIGHUP, so that changes (like a new admin key) can be applied right away
+            #[cfg(unix)]
+            tokio::spawn(async {
+                let mut signal = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        warn!("failed to set up the SIGHUP handler: {e}");
+                        return;
                     }
+                };
+
+                while signal.recv().await.is_some() {
+                    info!("SIGHUP received, refreshing the configuration from the central server");
+                    self.refresh_bootdata_logged().await;
                 }
             });
 
@@ -122,6 +305,41 @@ impl GameServer {
             });
         }
 
+        // temporary bans run out on their own, so forget about them once they do
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(BAN_PRUNE_INTERVAL);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                let removed = self.state.ban_manager.prune_expired();
+                if removed != 0 {
+                    debug!("removed {removed} expired bans");
+                }
+            }
+        });
+
+        if let Some(interval) = self.config.key_rotation_interval {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(interval);
+                interval.tick().await;
+
+                loop {
+                    interval.tick().await;
+                    *self.keys.lock() = ServerKeys::generate();
+                    debug!("generated a new keypair for handshakes");
+                }
+            });
+        }
+
+        if let Some(address) = self.config.metrics_address {
+            tokio::spawn(metrics::serve(self, address));
+        }
+
+        if let Some(address) = self.config.health_address {
+            tokio::spawn(health::serve(self, address));
+        }
+
         // print some useful stats every once in a bit
         let interval = self.bridge.central_conf.lock().status_print_interval;
 
@@ -171,10 +389,11 @@ impl GameServer {
     async fn accept_connection(&'static self) -> anyhow::Result<()> {
         let (socket, peer) = self.tcp_socket.accept().await?;
 
-        let peer = match peer {
-            SocketAddr::V4(x) => x,
-            SocketAddr::V6(_) => bail!("rejecting request from ipv6 host"),
-        };
+        // when behind a proxy, the real address is only known after reading the header, so the check is done later
+        if !self.config.proxy_protocol && self.state.ip_ban_manager.is_banned(&peer.ip()) {
+            debug!("rejecting connection from banned address {peer}");
+            return Ok(());
+        }
 
         debug!("accepting tcp connection from {peer}");
 
@@ -184,7 +403,32 @@ impl GameServer {
     }
 
     #[allow(clippy::manual_let_else, clippy::too_many_lines)]
-    async fn client_loop(&'static self, mut socket: TcpStream, peer: SocketAddrV4) {
+    async fn client_loop(&'static self, mut socket: TcpStream, mut peer: SocketAddr) {
+        // if we are behind a proxy, the real address of the client is sent in a header before anything else.
+        // fail closed if the header is missing or malformed, as otherwise the client could pretend to be anyone.
+        if self.config.proxy_protocol {
+            match tokio::time::timeout(PROXY_HEADER_TIMEOUT, proxy_protocol::read_proxy_header(&mut socket, peer)).await {
+                Ok(Ok(addr)) => {
+                    #[cfg(debug_assertions)]
+                    debug!("proxied connection from {addr} (via {peer})");
+                    peer = addr;
+
+                    if self.state.ip_ban_manager.is_banned(&peer.ip()) {
+                        debug!("rejecting connection from banned address {peer}");
+                        return;
+                    }
+                }
+                Ok(Err(e)) => {
+                    warn!("dropping connection from {peer}: {e}");
+                    return;
+                }
+                Err(_) => {
+                    warn!("dropping connection from {peer}: timed out waiting for the PROXY protocol header");
+                    return;
+                }
+            }
+        }
+
         // wait for incoming data, client should tell us whether it's an initial login or a recovery.
         let result: crate::client::Result<bool> = async {
             match socket.read_u8().await? {
@@ -214,13 +458,18 @@ impl GameServer {
                 .await;
 
                 match result {
+                    Ok(_) if !self.state.preauth_limiter.try_recovery(peer.ip()) => {
+                        debug!("dropping recovery attempt from {peer}, too many attempts");
+                        return;
+                    }
+
                     Ok((account_id, secret_key)) => {
                         // lets try to recover the situation.
                         let thread = self
                             .unauthorized_clients
                             .lock()
                             .iter()
-                            .find(|thr| thr.secret_key == secret_key && thr.account_id.load(Ordering::Relaxed) == account_id)
+                            .find(|thr| thr.secret_key.load(Ordering::Relaxed) == secret_key && thr.account_id.load(Ordering::Relaxed) == account_id)
                             .cloned();
 
                         let thread = if let Some(thread) = thread {
@@ -391,11 +640,12 @@ impl GameServer {
 
     async fn recv_and_handle_udp(&self, buf: &mut [u8]) -> anyhow::Result<()> {
         let (len, peer) = self.udp_socket.recv_from(buf).await?;
+        self.state.metrics.add_received(len);
 
-        let peer = match peer {
-            SocketAddr::V4(x) => x,
-            SocketAddr::V6(_) => bail!("rejecting request from ipv6 host"),
-        };
+        if len > self.bridge.max_packet_size() {
+            debug!("dropping udp packet from {peer}, too long ({len} bytes)");
+            return Ok(());
+        }
 
         // if it's a ping packet, we can handle it here. otherwise we send it to the appropriate thread.
         if !self.try_udp_handle(&buf[..len], peer).await? {
@@ -408,7 +658,7 @@ impl GameServer {
 
                         ServerThreadMessage::SmallPacket((inline_buf, len))
                     } else {
-                        ServerThreadMessage::Packet(buf[..len].to_vec())
+                        ServerThreadMessage::Packet(buffer_pool::take_copy(&buf[..len]))
                     })
                     .await;
             }
@@ -419,20 +669,91 @@ impl GameServer {
 
     /* various calls for other threads */
 
-    pub fn claim_thread(&self, udp_addr: SocketAddrV4, secret_key: u32) -> bool {
-        let thread = self.unauthorized_clients.lock().iter().find(|x| x.secret_key == secret_key).cloned();
+    /// Returns `false` if there is no thread with this key, or it can't be claimed from this address.
+    pub fn claim_thread(&self, udp_addr: SocketAddr, secret_key: u32) -> bool {
+        let thread = self
+            .unauthorized_clients
+            .lock()
+            .iter()
+            .find(|x| x.secret_key.load(Ordering::Relaxed) == secret_key)
+            .cloned();
 
-        if let Some(thread) = thread {
-            thread.claim(udp_addr);
-            true
-        } else {
-            false
+        let Some(thread) = thread else {
+            warn!("udp peer {udp_addr} tried to claim an invalid thread (with key {secret_key})");
+            return false;
+        };
+
+        // udp packets are routed to the thread by their source address, so whoever claims the thread receives
+        // and can send all of its udp traffic. optionally only accept it from the machine that logged in over tcp,
+        // a recovered connection claims the thread again with a new key
+        if self.config.require_udp_address_match {
+            let tcp_ip = *thread.tcp_ip.lock();
+            if tcp_ip.to_canonical() != udp_addr.ip().to_canonical() {
+                debug!("refusing claim of the thread of {tcp_ip} from {udp_addr}, the addresses don't match");
+                return false;
+            }
         }
+
+        thread.claim(udp_addr);
+        true
     }
 
-    pub async fn broadcast_voice_packet(&self, vpkt: &Arc<VoiceBroadcastPacket>, level_id: LevelId, room_id: u32) {
-        self.broadcast_user_message(&ServerThreadMessage::BroadcastVoice(vpkt.clone()), vpkt.player_id, level_id, room_id)
-            .await;
+    pub async fn broadcast_voice_packet(&self, vpkt: &Arc<EncodedVoiceBroadcastPacket>, level_id: LevelId, room_id: u32) {
+        let radius = self.bridge.voice_proximity_radius.load(Ordering::Relaxed);
+        let msg = ServerThreadMessage::BroadcastVoice(vpkt.clone());
+
+        // there are no positions outside of levels
+        if radius == 0 || level_id == 0 {
+            let threads = self.get_user_message_targets(vpkt.player_id, level_id, room_id);
+
+            for thread in threads.iter().filter(|thr| thr.accepts_voice_from(vpkt.player_id)) {
+                thread.push_new_message(msg.clone()).await;
+            }
+
+            return;
+        }
+
+        // only send the voice to the players that are close enough to the speaker
+        let radius_squared = (radius as f32) * (radius as f32);
+
+        let threads: Vec<_> = self.state.room_manager.with_any(room_id, |pm| {
+            let Some(origin) = pm.manager.get_player_data(vpkt.player_id) else {
+                return Vec::new();
+            };
+
+            let Some(players) = pm.manager.get_level(level_id) else {
+                return Vec::new();
+            };
+
+            let origin_pos = &origin.data.player1.position;
+            let mut nearby: IntSet<i32> = players
+                .iter()
+                .copied()
+                .filter(|id| {
+                    *id != vpkt.player_id
+                        && pm
+                            .manager
+                            .get_player_data(*id)
+                            .is_some_and(|p| p.data.player1.position.distance_squared(origin_pos) <= radius_squared)
+                })
+                .collect();
+
+            // spectators have no position, so they hear everyone on the level
+            if let Some(spectators) = pm.manager.get_spectators(level_id) {
+                nearby.extend(spectators.iter().copied());
+            }
+
+            self.clients
+                .lock()
+                .values()
+                .filter(|thread| nearby.contains(&thread.account_id.load(Ordering::Relaxed)))
+                .cloned()
+                .collect()
+        });
+
+        for thread in threads.iter().filter(|thr| thr.accepts_voice_from(vpkt.player_id)) {
+            thread.push_new_message(msg.clone()).await;
+        }
     }
 
     pub async fn broadcast_chat_packet(&self, tpkt: &ChatMessageBroadcastPacket, level_id: LevelId, room_id: u32) {
@@ -463,7 +784,7 @@ impl GameServer {
         self.clients
             .lock()
             .values()
-            .filter(|thr| thr.authenticated())
+            .filter(|thr| thr.is_visible())
             .map(|thread| thread.account_data.lock().make_preview())
             .fold(0, |count, preview| count + usize::from(f(&preview, count, additional)))
     }
@@ -476,7 +797,7 @@ impl GameServer {
         self.clients
             .lock()
             .values()
-            .filter(|thr| thr.authenticated() && thr.room_id.load(Ordering::Relaxed) == room_id)
+            .filter(|thr| thr.is_visible() && thr.room_id.load(Ordering::Relaxed) == room_id)
             .map(|thread| thread.account_data.lock().make_preview())
             .fold(0, |count, preview| count + usize::from(f(&preview, count, additional)))
     }
@@ -489,7 +810,7 @@ impl GameServer {
         self.clients
             .lock()
             .values()
-            .filter(|thr| thr.authenticated() && thr.room_id.load(Ordering::Relaxed) == room_id)
+            .filter(|thr| thr.is_visible() && thr.room_id.load(Ordering::Relaxed) == room_id)
             .map(|thread| {
                 let mut level_id = thread.level_id.load(Ordering::Relaxed);
 
@@ -558,6 +879,33 @@ impl GameServer {
         vec
     }
 
+    /// Returns the total amount of matching players and the requested page of them, ordered by account ID
+    /// so that pages stay consistent between requests. If `level_id` is set, only players on that level are included.
+    pub fn get_player_list_page(&self, room_id: u32, level_id: Option<LevelId>, page: usize) -> (usize, Vec<PlayerPreviewAccountData>) {
+        let mut players: Vec<_> = self
+            .clients
+            .lock()
+            .values()
+            .filter(|thr| {
+                thr.is_visible()
+                    && thr.room_id.load(Ordering::Relaxed) == room_id
+                    && level_id.is_none_or(|id| thr.level_id.load(Ordering::Relaxed) == id)
+            })
+            .map(|thread| thread.account_data.lock().make_preview())
+            .collect();
+
+        players.sort_unstable_by_key(|p| p.account_id);
+
+        let total = players.len();
+        let page = players
+            .into_iter()
+            .skip(page.saturating_mul(PLAYER_LIST_PAGE_SIZE))
+            .take(PLAYER_LIST_PAGE_SIZE)
+            .collect();
+
+        (total, page)
+    }
+
     #[inline]
     pub fn for_every_public_room<F, A>(&self, f: F, additional: &mut A) -> usize
     where
@@ -570,11 +918,13 @@ impl GameServer {
 
     #[inline]
     pub fn get_player_account_data(&self, account_id: i32) -> Option<PlayerAccountData> {
-        self.clients
-            .lock()
-            .values()
-            .find(|thr| thr.account_id.load(Ordering::Relaxed) == account_id)
-            .map(|thr| thr.account_data.lock().clone())
+        self.state.profile_cache.get_or_fetch(account_id, || {
+            self.clients
+                .lock()
+                .values()
+                .find(|thr| thr.account_id.load(Ordering::Relaxed) == account_id && !thr.is_spectator())
+                .map(|thr| thr.account_data.lock().clone())
+        })
     }
 
     #[inline]
@@ -603,9 +953,10 @@ impl GameServer {
             clients.values().find(|thr| thr.account_id.load(Ordering::Relaxed) == account_id).cloned()
         } {
             thread
-                .push_new_message(ServerThreadMessage::TerminationNotice(FastString::new(
-                    "Someone logged into the same account from a different place.",
-                )))
+                .push_new_message(ServerThreadMessage::TerminationNotice(
+                    DisconnectReason::DuplicateLogin,
+                    FastString::new("Someone logged into the same account from a different place."),
+                ))
                 .await;
 
             let destruction_notify = thread.destruction_notify.clone();
@@ -638,6 +989,27 @@ impl GameServer {
             .cloned()
     }
 
+    /// Returns up to `limit` players whose name contains `query`, ignoring case. Goes through every connected player.
+    pub fn search_players(&self, query: &str, limit: usize) -> Vec<AdminFoundPlayer> {
+        let query = query.to_ascii_lowercase();
+
+        self.clients
+            .lock()
+            .iter()
+            .filter(|(_, thread)| thread.authenticated())
+            .filter_map(|(peer, thread)| {
+                let data = thread.account_data.lock();
+                data.name.to_ascii_lowercase().contains(&query).then(|| AdminFoundPlayer {
+                    account_id: thread.account_id.load(Ordering::Relaxed),
+                    name: data.name.clone(),
+                    peer: FastString::new(&peer.to_string()),
+                    level_id: thread.level_id.load(Ordering::Relaxed),
+                })
+            })
+            .take(limit)
+            .collect()
+    }
+
     /// If the passed string is numeric, tries to find a user by account ID, else by their account name.
     pub fn find_user(&self, name: &str) -> Option<Arc<ClientThread>> {
         self.clients
@@ -683,27 +1055,52 @@ impl GameServer {
 
     /// broadcast a message to all people on the level
     async fn broadcast_user_message(&self, msg: &ServerThreadMessage, origin_id: i32, level_id: LevelId, room_id: u32) {
-        let threads = self.state.room_manager.with_any(room_id, |pm| {
+        let threads = self.get_user_message_targets(origin_id, level_id, room_id);
+
+        for thread in threads {
+            thread.push_new_message(msg.clone()).await;
+        }
+    }
+
+    /// get all people on the same level as the sender, except for the sender themselves
+    fn get_user_message_targets(&self, origin_id: i32, level_id: LevelId, room_id: u32) -> Vec<Arc<ClientThread>> {
+        // players that are not in any level are all in one lobby bucket, per room
+        if level_id == 0 {
+            return self
+                .clients
+                .lock()
+                .values()
+                .filter(|thread| {
+                    let account_id = thread.account_id.load(Ordering::Relaxed);
+                    account_id != 0
+                        && account_id != origin_id
+                        && thread.level_id.load(Ordering::Relaxed) == 0
+                        && thread.room_id.load(Ordering::Relaxed) == room_id
+                })
+                .cloned()
+                .collect();
+        }
+
+        self.state.room_manager.with_any(room_id, |pm| {
             let players = pm.manager.get_level(level_id);
 
             if let Some(players) = players {
+                let spectators = pm.manager.get_spectators(level_id);
+
                 self.clients
                     .lock()
                     .values()
                     .filter(|thread| {
                         let account_id = thread.account_id.load(Ordering::Relaxed);
-                        account_id != origin_id && players.contains(&account_id)
+                        account_id != origin_id
+                            && (players.contains(&account_id) || spectators.is_some_and(|spectators| spectators.contains(&account_id)))
                     })
                     .cloned()
                     .collect()
             } else {
                 Vec::new()
             }
-        });
-
-        for thread in threads {
-            thread.push_new_message(msg.clone()).await;
-        }
+        })
     }
 
     /// broadcast a message to all people in a room
@@ -724,6 +1121,26 @@ impl GameServer {
         }
     }
 
+    /// notify everyone else on the level that a player has joined it
+    pub async fn broadcast_level_join(&self, account_id: i32, level_id: LevelId, room_id: u32) {
+        if level_id == 0 {
+            return;
+        }
+
+        let msg = ServerThreadMessage::BroadcastLevelJoin(LevelPlayerJoinedPacket { account_id });
+        self.broadcast_user_message(&msg, account_id, level_id, room_id).await;
+    }
+
+    /// notify everyone remaining on the level that a player has left it. must be called after the player is removed from the level.
+    pub async fn broadcast_level_leave(&self, account_id: i32, level_id: LevelId, room_id: u32) {
+        if level_id == 0 {
+            return;
+        }
+
+        let msg = ServerThreadMessage::BroadcastLevelLeave(LevelPlayerLeftPacket { account_id });
+        self.broadcast_user_message(&msg, account_id, level_id, room_id).await;
+    }
+
     /// send `RoomInfoPacket` to all players in a room
     pub async fn broadcast_room_info(&self, room_id: u32) {
         if room_id == 0 {
@@ -744,16 +1161,31 @@ impl GameServer {
     }
 
     /// Try to handle a packet that is not addressed to a specific thread, but to the game server.
-    async fn try_udp_handle(&self, data: &[u8], peer: SocketAddrV4) -> anyhow::Result<bool> {
-        let mut byte_reader = ByteReader::from_bytes(data);
-        let header = byte_reader.read_packet_header().map_err(|e| anyhow!("{e}"))?;
+    async fn try_udp_handle(&self, data: &[u8], peer: SocketAddr) -> anyhow::Result<bool> {
+        let header = ByteReader::from_bytes(data).read_packet_header().map_err(|e| anyhow!("{e}"))?;
+
+        // a corrupted datagram would only cause confusing errors later on, so drop it right away
+        let Some(len) = header.verify_checksum(data) else {
+            self.state.metrics.corrupted_packets.fetch_add(1, Ordering::Relaxed);
+            return Ok(true);
+        };
+
+        let mut byte_reader = ByteReader::from_bytes(&data[..len]);
+        byte_reader.skip(PacketHeader::SIZE);
 
         match header.packet_id {
             PingPacket::PACKET_ID => {
+                // silently drop pings from someone flooding us. connected players are never limited,
+                // so that many people behind the same NAT don't affect each other
+                if !self.clients.lock().contains_key(&peer) && !self.state.preauth_limiter.try_ping(peer.ip()) {
+                    return Ok(true);
+                }
+
                 let pkt = PingPacket::decode_from_reader(&mut byte_reader).map_err(|e| anyhow!("{e}"))?;
                 let response = PingResponsePacket {
                     id: pkt.id,
                     player_count: self.state.get_player_count(),
+                    uptime: self.uptime(),
                 };
 
                 let mut buf_array = [0u8; PacketHeader::SIZE + PingResponsePacket::ENCODED_SIZE];
@@ -768,11 +1200,38 @@ impl GameServer {
                 Ok(true)
             }
 
+            RequestServerInfoPacket::PACKET_ID => {
+                // the response is much larger than the request, so throttle it to avoid being used for amplification
+                if !self.clients.lock().contains_key(&peer) && !self.state.preauth_limiter.try_server_info(peer.ip()) {
+                    return Ok(true);
+                }
+
+                let mut buf = ByteBuffer::new();
+
+                {
+                    let conf = self.bridge.central_conf.lock();
+
+                    buf.write_packet_header::<ServerInfoPacket>();
+                    buf.write_value(&ServerInfoPacket {
+                        name: &conf.server_name,
+                        motd: &conf.motd,
+                        version: env!("CARGO_PKG_VERSION"),
+                        protocol: PROTOCOL_VERSION,
+                        player_count: self.state.get_player_count(),
+                        max_players: conf.max_players,
+                        uptime: self.uptime(),
+                        maintenance: self.bridge.is_maintenance(),
+                    });
+                }
+
+                self.udp_socket.send_to(buf.as_bytes(), peer).await?;
+
+                Ok(true)
+            }
+
             ClaimThreadPacket::PACKET_ID => {
                 let pkt = ClaimThreadPacket::decode_from_reader(&mut byte_reader).map_err(|e| anyhow!("{e}"))?;
                 if !self.claim_thread(peer, pkt.secret_key) {
-                    warn!("udp peer {peer} tried to claim an invalid thread (with key {})", pkt.secret_key);
-
                     // send a ClaimThreadFailedPacket
                     let mut buf_array = [0u8; PacketHeader::SIZE];
                     let mut buf = FastByteBuffer::new(&mut buf_array);
@@ -790,15 +1249,19 @@ impl GameServer {
         }
     }
 
-    async fn post_disconnect_cleanup(&self, thread: EitherClientThread) {
-        let (account_id, level_id, room_id) = match thread {
+    async fn post_disconnect_cleanup(&'static self, thread: EitherClientThread) {
+        let (account_id, level_id, room_id, spectator, name) = match thread {
             EitherClientThread::Authorized(thread) => {
+                // save first, whoever is waiting for the thread to go away may log in again and load them
+                thread.save_voice_mutes();
                 thread.destruction_notify.notify_one();
 
                 (
                     thread.account_id.load(Ordering::Relaxed),
                     thread.level_id.load(Ordering::Relaxed),
                     thread.room_id.load(Ordering::Relaxed),
+                    thread.spectator,
+                    thread.account_data.lock().name.clone(),
                 )
             }
             EitherClientThread::Unauthorized(thread) => {
@@ -808,6 +1271,8 @@ impl GameServer {
                     thread.account_id.load(Ordering::Relaxed),
                     thread.level_id.load(Ordering::Relaxed),
                     thread.room_id.load(Ordering::Relaxed),
+                    thread.spectator.load(Ordering::Relaxed),
+                    thread.account_data.lock().name.clone(),
                 )
             }
             EitherClientThread::None => unreachable!(),
@@ -817,11 +1282,28 @@ impl GameServer {
             return;
         }
 
+        self.state.profile_cache.invalidate(account_id);
+
+        if spectator {
+            self.state.room_manager.remove_with_any(room_id, account_id, level_id, true);
+            return;
+        }
+
         // decrement player count
         self.state.dec_player_count();
+        self.state.address_limiter.remove(account_id);
+
+        if self.bridge.has_join_leave_webhook() {
+            self.bridge.queue_webhook_message(WebhookMessage::PlayerLeft(
+                name.try_to_string(),
+                account_id,
+                self.state.get_player_count(),
+            ));
+        }
 
         // remove from the player manager and the level if they are on one
-        let was_owner = self.state.room_manager.remove_with_any(room_id, account_id, level_id);
+        let was_owner = self.state.room_manager.remove_with_any(room_id, account_id, level_id, false);
+        self.broadcast_level_leave(account_id, level_id, room_id).await;
 
         // also send room update i guess
         if was_owner && room_id != 0 {
@@ -838,6 +1320,12 @@ impl GameServer {
             self.unclaimed_threads.lock().len(),
         );
         info!("Amount of rooms: {}", self.state.room_manager.get_rooms().len());
+        info!(
+            "Voice packets: {} (dropped: {}, gated as silence: {})",
+            self.state.metrics.voice_packets.load(Ordering::Relaxed),
+            self.state.metrics.voice_packets_dropped.load(Ordering::Relaxed),
+            self.state.metrics.voice_packets_gated.load(Ordering::Relaxed),
+        );
         info!(
             "People in the global room: {}",
             self.state.room_manager.get_global().manager.get_total_player_count()
@@ -845,21 +1333,71 @@ impl GameServer {
         info!("-------------------------------------------");
     }
 
-    async fn refresh_bootdata(&self) -> bridge::Result<()> {
-        self.bridge.refresh_boot_data().await?;
+    /// Same as `refresh_bootdata`, but logs the outcome. Returns whether the refresh succeeded.
+    pub async fn refresh_bootdata_logged(&self) -> bool {
+        match self.refresh_bootdata().await {
+            Ok(changed) if changed.is_empty() => debug!("refreshed central server configuration, nothing changed"),
+            Ok(changed) => info!("refreshed central server configuration, changed: {}", changed.join(", ")),
+            Err(e) => {
+                error!("failed to refresh configuration from the central server, keeping the current one: {e}");
+                return false;
+            }
+        }
+
+        true
+    }
 
-        // if we are now under maintenance, disconnect everyone who's still connected
-        if self.bridge.is_maintenance() {
+    async fn refresh_bootdata(&self) -> bridge::Result<Vec<&'static str>> {
+        let changed = self.bridge.refresh_boot_data().await?;
+
+        // whoever logged in with the old admin key should not keep their permissions
+        if changed.contains(&"admin_key") {
             let threads: Vec<_> = self.clients.lock().values().cloned().collect();
+            for thread in threads {
+                thread.revoke_global_admin();
+            }
+        }
+
+        // refresh the ban list, and disconnect anyone who got banned in the meantime
+        {
+            let gsbd = self.bridge.central_conf.lock();
+            self.state.ban_manager.refresh_from(&gsbd);
+            self.state.ip_ban_manager.refresh_from(&gsbd);
+        }
+
+        let banned_threads: Vec<_> = self
+            .clients
+            .lock()
+            .values()
+            .filter(|thread| self.state.ban_manager.is_banned(thread.account_id.load(Ordering::Relaxed)))
+            .cloned()
+            .collect();
+
+        for thread in banned_threads {
+            if let Some(ban) = self.state.ban_manager.get_ban(thread.account_id.load(Ordering::Relaxed)) {
+                thread
+                    .push_new_message(ServerThreadMessage::BroadcastBan(ServerBannedPacket {
+                        message: FastString::new(&ban.reason.unwrap_or_default()),
+                        timestamp: ban.expiry.unwrap_or(0),
+                    }))
+                    .await;
+            }
+        }
+
+        // if we are now under maintenance, disconnect everyone who's still connected (except admins).
+        // maintenance enabled locally by an admin is not checked here, as they get to choose whether to kick everyone
+        if self.bridge.central_conf.lock().maintenance {
+            let threads: Vec<_> = self.clients.lock().values().filter(|thr| !thr.user_role.lock().admin).cloned().collect();
             for thread in threads {
                 thread
-                    .push_new_message(ServerThreadMessage::TerminationNotice(FastString::new(
-                        "The server is now under maintenance, please try connecting again later",
-                    )))
+                    .push_new_message(ServerThreadMessage::TerminationNotice(
+                        DisconnectReason::Maintenance,
+                        FastString::new("The server is now under maintenance, please try connecting again later"),
+                    ))
                     .await;
             }
         }
 
-        Ok(())
+        Ok(changed)
     }
 }
@@ -1,5 +1,9 @@
 use crate::{
-    managers::{RoleManager, RoomManager},
+    managers::{
+        AddressLimiter, BanManager, IpBanManager, LoginLimiter, LoginQueue, MuteCache, PreauthLimiter, ProfileCache, RoleManager, RoomManager,
+        UserCache, WhitelistManager,
+    },
+    metrics::ServerMetrics,
     util::WordFilter,
 };
 use std::sync::atomic::{AtomicU32, Ordering};
@@ -9,7 +13,18 @@ pub struct ServerState {
     pub player_count: AtomicU32,
     pub room_manager: RoomManager,
     pub role_manager: RoleManager,
+    pub ban_manager: BanManager,
+    pub ip_ban_manager: IpBanManager,
+    pub whitelist_manager: WhitelistManager,
+    pub login_limiter: LoginLimiter,
+    pub login_queue: LoginQueue,
+    pub preauth_limiter: PreauthLimiter,
+    pub address_limiter: AddressLimiter,
+    pub user_cache: UserCache,
+    pub profile_cache: ProfileCache,
+    pub mute_cache: MuteCache,
     pub filter: WordFilter,
+    pub metrics: ServerMetrics,
 }
 
 impl ServerState {
@@ -30,5 +45,6 @@ impl ServerState {
 
     pub fn dec_player_count(&self) {
         self.player_count.fetch_sub(1, Ordering::SeqCst);
+        self.login_queue.notify();
     }
 }
@@ -0,0 +1,40 @@
+use std::cell::RefCell;
+
+/// how many buffers every thread keeps around for reuse
+const MAX_BUFFERS: usize = 64;
+/// buffers that grew larger than this are freed instead, so that a single huge packet doesn't keep its memory around forever
+const MAX_CAPACITY: usize = 2usize.pow(16);
+
+thread_local! {
+    /// Pool of reusable byte buffers, so that packets which don't fit on the stack don't need a fresh allocation every time.
+    /// Every thread has its own pool, so taking and returning buffers never needs a lock. A buffer taken on one thread
+    /// may be returned on another one, it simply ends up in that thread's pool.
+    static BUFFERS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Takes an empty buffer out of the pool, or allocates a new one if the pool is empty.
+pub fn take() -> Vec<u8> {
+    BUFFERS.with_borrow_mut(Vec::pop).unwrap_or_default()
+}
+
+/// Takes a buffer out of the pool and fills it with a copy of `data`.
+pub fn take_copy(data: &[u8]) -> Vec<u8> {
+    let mut buf = take();
+    buf.extend_from_slice(data);
+    buf
+}
+
+/// Returns a buffer to the pool, so that it can be reused.
+pub fn put(mut buf: Vec<u8>) {
+    if buf.capacity() > MAX_CAPACITY {
+        return;
+    }
+
+    buf.clear();
+
+    BUFFERS.with_borrow_mut(|buffers| {
+        if buffers.len() < MAX_BUFFERS {
+            buffers.push(buf);
+        }
+    });
+}
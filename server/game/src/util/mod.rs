@@ -1,9 +1,11 @@
+pub mod buffer_pool;
 pub mod channel;
 pub mod lockfreemutcell;
+pub mod proxy_protocol;
 pub mod rate_limiter;
 pub mod word_filter;
 
 pub use channel::{SenderDropped, TokioChannel};
 pub use lockfreemutcell::LockfreeMutCell;
-pub use rate_limiter::SimpleRateLimiter;
+pub use rate_limiter::{SimpleRateLimiter, ThroughputLimiter};
 pub use word_filter::WordFilter;
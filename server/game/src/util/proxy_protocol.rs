@@ -0,0 +1,112 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use globed_shared::anyhow::{self, anyhow, bail};
+
+use crate::tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Signature that every PROXY protocol v2 header starts with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// Maximum length of a v1 header, including the trailing CRLF.
+const V1_MAX_LENGTH: usize = 107;
+
+const V2_CMD_LOCAL: u8 = 0x0;
+const V2_CMD_PROXY: u8 = 0x1;
+const V2_FAMILY_INET: u8 = 0x1;
+const V2_FAMILY_INET6: u8 = 0x2;
+
+/// Reads a PROXY protocol (v1 or v2) header from the stream and returns the source address declared in it.
+/// If the header does not carry an address (`UNKNOWN` in v1, `LOCAL` in v2), `peer` is returned as-is.
+///
+/// Nothing past the end of the header is consumed, so the stream can be used normally afterwards.
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(stream: &mut R, peer: SocketAddr) -> anyhow::Result<SocketAddr> {
+    // the shortest valid header, "PROXY UNKNOWN\r\n", is longer than the v2 signature
+    let mut prefix = [0u8; V2_SIGNATURE.len()];
+    stream.read_exact(&mut prefix).await?;
+
+    if prefix == V2_SIGNATURE {
+        read_v2(stream, peer).await
+    } else if prefix.starts_with(b"PROXY ") {
+        read_v1(stream, &prefix, peer).await
+    } else {
+        bail!("missing PROXY protocol header")
+    }
+}
+
+async fn read_v1<R: AsyncRead + Unpin>(stream: &mut R, prefix: &[u8], peer: SocketAddr) -> anyhow::Result<SocketAddr> {
+    let mut header = Vec::with_capacity(V1_MAX_LENGTH);
+    header.extend_from_slice(prefix);
+
+    // read byte by byte, to not consume any data that comes after the header
+    while !header.ends_with(b"\r\n") {
+        if header.len() >= V1_MAX_LENGTH {
+            bail!("PROXY v1 header is too long");
+        }
+
+        header.push(stream.read_u8().await?);
+    }
+
+    let header = std::str::from_utf8(&header[..header.len() - 2]).map_err(|_| anyhow!("PROXY v1 header is not valid ASCII"))?;
+
+    parse_v1(header, peer)
+}
+
+fn parse_v1(header: &str, peer: SocketAddr) -> anyhow::Result<SocketAddr> {
+    let mut parts = header.split(' ');
+
+    if parts.next() != Some("PROXY") {
+        bail!("malformed PROXY v1 header");
+    }
+
+    match parts.next() {
+        Some("TCP4" | "TCP6") => {}
+        Some("UNKNOWN") => return Ok(peer),
+        _ => bail!("unsupported protocol in PROXY v1 header"),
+    }
+
+    let (Some(src_addr), Some(_dst_addr), Some(src_port), Some(_dst_port), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        bail!("malformed PROXY v1 header");
+    };
+
+    let ip = src_addr.parse::<IpAddr>().map_err(|_| anyhow!("invalid source address in PROXY v1 header"))?;
+    let port = src_port.parse::<u16>().map_err(|_| anyhow!("invalid source port in PROXY v1 header"))?;
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(stream: &mut R, peer: SocketAddr) -> anyhow::Result<SocketAddr> {
+    let ver_cmd = stream.read_u8().await?;
+    let family = stream.read_u8().await?;
+    let length = stream.read_u16().await? as usize;
+
+    if ver_cmd >> 4 != 2 {
+        bail!("unsupported PROXY protocol version in v2 header");
+    }
+
+    // we always have to consume the entire address block, even if we don't end up using it
+    let mut data = vec![0u8; length];
+    stream.read_exact(&mut data).await?;
+
+    match ver_cmd & 0xf {
+        V2_CMD_LOCAL => return Ok(peer),
+        V2_CMD_PROXY => {}
+        _ => bail!("unknown command in PROXY v2 header"),
+    }
+
+    match family >> 4 {
+        V2_FAMILY_INET if data.len() >= 12 => {
+            let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+            let port = u16::from_be_bytes([data[8], data[9]]);
+            Ok(SocketAddr::new(IpAddr::V4(ip), port))
+        }
+        V2_FAMILY_INET6 if data.len() >= 36 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&data[..16]).unwrap());
+            let port = u16::from_be_bytes([data[32], data[33]]);
+            Ok(SocketAddr::new(IpAddr::V6(ip), port))
+        }
+        // AF_UNSPEC carries no address, same as LOCAL
+        0 => Ok(peer),
+        _ => bail!("unsupported or truncated address in PROXY v2 header"),
+    }
+}
@@ -35,8 +35,70 @@ impl SimpleRateLimiter {
         }
     }
 
+    /// Returns how long until `try_tick` succeeds again, zero if it would succeed right now.
+    pub fn time_until_refill(&self) -> Duration {
+        if self.count > 0 {
+            Duration::ZERO
+        } else {
+            self.period.saturating_sub(self.last_refill.elapsed())
+        }
+    }
+
     /// Returns the amount of time since last refill
     pub fn since_last_refill(&self) -> Duration {
         self.last_refill.elapsed()
     }
 }
+
+/// Limits the amount of bytes that can pass through in a period of time. The budget is refilled continuously,
+/// so a single delayed packet does not look like a huge spike, and short bursts up to `window` worth of data are allowed.
+/// Like `SimpleRateLimiter`, not thread safe on its own.
+pub struct ThroughputLimiter {
+    bytes_per_sec: f64,
+    window: Duration,
+    capacity: f64,
+    available: f64,
+    last_refill: Instant,
+}
+
+impl ThroughputLimiter {
+    pub fn new(bytes_per_sec: usize, window: Duration) -> Self {
+        let bytes_per_sec = bytes_per_sec as f64;
+        let capacity = bytes_per_sec * window.as_secs_f64();
+
+        Self {
+            bytes_per_sec,
+            window,
+            capacity,
+            available: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Returns `true` and consumes `bytes` from the budget if we are not ratelimited, `false` if we are.
+    pub fn try_consume(&mut self, bytes: usize) -> bool {
+        let now = Instant::now();
+        let elapsed = (now - self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        self.available = (self.available + elapsed * self.bytes_per_sec).min(self.capacity);
+
+        let bytes = bytes as f64;
+        if bytes <= self.available {
+            self.available -= bytes;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Changes the amount of bytes allowed per second. Takes effect immediately, a lower limit also shrinks the current budget.
+    pub fn set_rate(&mut self, bytes_per_sec: usize) {
+        let bytes_per_sec = bytes_per_sec as f64;
+        if bytes_per_sec != self.bytes_per_sec {
+            self.bytes_per_sec = bytes_per_sec;
+            self.capacity = bytes_per_sec * self.window.as_secs_f64();
+            self.available = self.available.min(self.capacity);
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use esp::{size_of_types, StaticSize};
+use globed_shared::{info, rand, warn, IntMap, SyncMutex};
+
+use crate::data::{opus_packet_samples, scan_opus_frames, EncodedVoiceBroadcastPacket, LevelId};
+
+/// how many voice packets can wait to be written, once full new packets are dropped instead of slowing down voice
+const QUEUE_CAPACITY: usize = 1024;
+/// a new file is started once the player has been silent for this long, or the current file gets this long
+const MAX_SILENCE: Duration = Duration::from_secs(30);
+const MAX_RECORDING_LENGTH: Duration = Duration::from_secs(3600);
+/// pauses shorter than this are assumed to be network jitter rather than the player not talking, and are not kept in the recording
+const MIN_RECORDED_PAUSE: Duration = Duration::from_millis(100);
+/// how often recordings older than the retention window are deleted
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Settings for recording voice chat to disk, for moderation purposes.
+#[derive(Clone)]
+pub struct VoiceRecordingConfig {
+    /// recordings are stored as `<directory>/<account id>/<unix timestamp>-<level id>.ogg`
+    pub directory: PathBuf,
+    /// only record voice on these levels, or everywhere if `None`
+    pub levels: Option<Vec<LevelId>>,
+    /// recordings older than this are deleted
+    pub retention: Duration,
+}
+
+struct RecordedPacket {
+    level_id: LevelId,
+    packet: Arc<EncodedVoiceBroadcastPacket>,
+}
+
+enum RecorderMessage {
+    Packet(RecordedPacket),
+    /// finish every open recording and stop the thread
+    Shutdown,
+}
+
+/// Writes received voice to Ogg Opus files. All file operations happen on a separate thread,
+/// the voice handler only hands the already encoded packet over to it.
+pub struct VoiceRecorder {
+    levels: Option<Vec<LevelId>>,
+    sender: SyncSender<RecorderMessage>,
+    thread: SyncMutex<Option<JoinHandle<()>>>,
+}
+
+impl VoiceRecorder {
+    pub fn start(config: VoiceRecordingConfig) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let levels = config.levels.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("voice-recorder".to_owned())
+            .spawn(move || RecorderThread::new(config).run(&receiver))
+            .expect("failed to spawn the voice recorder thread");
+
+        Self {
+            levels,
+            sender,
+            thread: SyncMutex::new(Some(thread)),
+        }
+    }
+
+    /// Writes everything that is still queued, finishes every open recording so that the files are complete, and stops the thread.
+    /// Blocks until all of that is done, anything recorded afterwards is dropped.
+    pub fn shutdown(&self) {
+        let Some(thread) = self.thread.lock().take() else {
+            return;
+        };
+
+        if self.sender.send(RecorderMessage::Shutdown).is_ok() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Queues a voice packet to be written, if voice on the given level is being recorded. Never blocks,
+    /// returns `false` if the packet had to be dropped because too many packets are already waiting.
+    pub fn record(&self, packet: &Arc<EncodedVoiceBroadcastPacket>, level_id: LevelId) -> bool {
+        if self.levels.as_ref().is_some_and(|levels| !levels.contains(&level_id)) {
+            return true;
+        }
+
+        let recorded = RecordedPacket {
+            level_id,
+            packet: packet.clone(),
+        };
+
+        !matches!(self.sender.try_send(RecorderMessage::Packet(recorded)), Err(TrySendError::Full(_)))
+    }
+}
+
+struct RecorderThread {
+    config: VoiceRecordingConfig,
+    recordings: IntMap<i32, OggOpusWriter>,
+    last_cleanup: Option<Instant>,
+}
+
+impl RecorderThread {
+    fn new(config: VoiceRecordingConfig) -> Self {
+        Self {
+            config,
+            recordings: IntMap::default(),
+            last_cleanup: None,
+        }
+    }
+
+    fn run(mut self, receiver: &mpsc::Receiver<RecorderMessage>) {
+        loop {
+            match receiver.recv_timeout(Duration::from_secs(5)) {
+                Ok(RecorderMessage::Packet(recorded)) => self.write(&recorded),
+                Ok(RecorderMessage::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            // finish the recordings of people who stopped talking
+            let silent: Vec<i32> = self
+                .recordings
+                .iter()
+                .filter(|(_, rec)| rec.last_write.elapsed() >= MAX_SILENCE)
+                .map(|(account_id, _)| *account_id)
+                .collect();
+
+            for account_id in silent {
+                self.finish(account_id);
+            }
+
+            if self.last_cleanup.is_none_or(|x| x.elapsed() > CLEANUP_INTERVAL) {
+                self.last_cleanup = Some(Instant::now());
+
+                let removed = delete_old_recordings(&self.config.directory, self.config.retention);
+                if removed != 0 {
+                    info!("deleted {removed} voice recordings older than the retention window");
+                }
+            }
+        }
+
+        let account_ids: Vec<i32> = self.recordings.keys().copied().collect();
+        for account_id in account_ids {
+            self.finish(account_id);
+        }
+    }
+
+    /// Ends the recording of the given player, if there is one.
+    fn finish(&mut self, account_id: i32) {
+        let Some(rec) = self.recordings.remove(&account_id) else {
+            return;
+        };
+
+        if let Err(e) = rec.finish() {
+            warn!("failed to finish a voice recording for {account_id}: {e}");
+        }
+    }
+
+    fn write(&mut self, recorded: &RecordedPacket) {
+        let account_id = recorded.packet.player_id;
+
+        // changing levels also starts a new file, so every recording belongs to exactly one level
+        if self
+            .recordings
+            .get(&account_id)
+            .is_some_and(|rec| rec.level_id != recorded.level_id || rec.started.elapsed() > MAX_RECORDING_LENGTH)
+        {
+            self.finish(account_id);
+        }
+
+        if !self.recordings.contains_key(&account_id) {
+            match OggOpusWriter::create(&self.config.directory, account_id, recorded.level_id) {
+                Ok(rec) => {
+                    self.recordings.insert(account_id, rec);
+                }
+                Err(e) => {
+                    warn!("failed to create a voice recording for {account_id}: {e}");
+                    return;
+                }
+            }
+        }
+
+        let rec = self.recordings.get_mut(&account_id).unwrap();
+
+        // the data starts with the account ID of the speaker, followed by the audio frame
+        let mut result = Ok(());
+        scan_opus_frames(&recorded.packet.data[size_of_types!(i32)..], |frame| {
+            if result.is_ok() {
+                result = rec.write_packet(frame);
+            }
+        });
+
+        if let Err(e) = result {
+            warn!("failed to write a voice recording for {account_id}: {e}");
+            self.recordings.remove(&account_id);
+        }
+    }
+}
+
+/// Deletes recordings that were last modified longer than `retention` ago, returns how many were deleted.
+fn delete_old_recordings(directory: &Path, retention: Duration) -> usize {
+    let Ok(accounts) = std::fs::read_dir(directory) else {
+        return 0;
+    };
+
+    let mut removed = 0;
+
+    for account_dir in accounts.flatten().map(|x| x.path()).filter(|x| x.is_dir()) {
+        let Ok(files) = std::fs::read_dir(&account_dir) else {
+            continue;
+        };
+
+        for file in files.flatten() {
+            let path = file.path();
+            let expired = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > retention));
+
+            if path.extension().is_some_and(|ext| ext == "ogg") && expired && std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+
+        // only succeeds if the directory is empty now
+        let _ = std::fs::remove_dir(&account_dir);
+    }
+
+    removed
+}
+
+/* minimal Ogg Opus muxer (RFC 7845), one opus packet per ogg page */
+
+const OGG_FLAG_BOS: u8 = 0x02;
+const OGG_FLAG_EOS: u8 = 0x04;
+
+struct OggOpusWriter {
+    writer: BufWriter<File>,
+    serial: u32,
+    sequence: u32,
+    /// position in 48 kHz samples, as required by the spec regardless of the actual sample rate
+    granule: u64,
+    /// when the audio written so far would have finished playing, if it was played back as it arrived
+    audio_end: Instant,
+    level_id: LevelId,
+    started: Instant,
+    last_write: Instant,
+}
+
+impl OggOpusWriter {
+    fn create(directory: &Path, account_id: i32, level_id: LevelId) -> std::io::Result<Self> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+        let directory = directory.join(account_id.to_string());
+        std::fs::create_dir_all(&directory)?;
+
+        let file = File::create(directory.join(format!("{timestamp}-{level_id}.ogg")))?;
+
+        let mut writer = Self {
+            writer: BufWriter::new(file),
+            serial: rand::random(),
+            sequence: 0,
+            granule: 0,
+            audio_end: Instant::now(),
+            level_id,
+            started: Instant::now(),
+            last_write: Instant::now(),
+        };
+
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1); // version
+        head.push(1); // channels, voice is always mono
+        head.extend_from_slice(&0u16.to_le_bytes()); // pre-skip
+        head.extend_from_slice(&24_000u32.to_le_bytes()); // original sample rate, informational only
+        head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+        head.push(0); // channel mapping family
+        writer.write_page(&head, OGG_FLAG_BOS)?;
+
+        let vendor = concat!("globed-game-server ", env!("CARGO_PKG_VERSION"));
+        let comment = format!("GLOBED_ACCOUNT_ID={account_id}");
+
+        let mut tags = Vec::new();
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&1u32.to_le_bytes());
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+        writer.write_page(&tags, 0)?;
+
+        Ok(writer)
+    }
+
+    fn write_packet(&mut self, packet: &[u8]) -> std::io::Result<()> {
+        let now = Instant::now();
+
+        // keep the pauses between packets, so that the recording plays back with the same timing as it was spoken
+        let pause = now.saturating_duration_since(self.audio_end);
+        if pause >= MIN_RECORDED_PAUSE {
+            self.granule += duration_to_samples(pause.min(MAX_SILENCE));
+            self.audio_end = now;
+        }
+
+        let samples = opus_packet_samples(packet);
+        self.granule += samples;
+        self.audio_end += Duration::from_micros(samples * 1000 / 48);
+        self.last_write = now;
+        self.write_page(packet, 0)
+    }
+
+    /// Marks the end of the stream and flushes everything to the file.
+    fn finish(mut self) -> std::io::Result<()> {
+        // the last page has to have the EOS flag, since we never know which packet is the last one, it gets an empty page
+        self.write_raw_page(&[], &[], OGG_FLAG_EOS)?;
+        self.writer.flush()
+    }
+
+    fn write_page(&mut self, packet: &[u8], flags: u8) -> std::io::Result<()> {
+        // a packet is split into segments of 255 bytes, a shorter one (possibly empty) marks its end
+        let full_segments = packet.len() / 255;
+
+        let mut lacing = vec![255u8; full_segments];
+        lacing.push((packet.len() % 255) as u8);
+
+        self.write_raw_page(&lacing, packet, flags)
+    }
+
+    fn write_raw_page(&mut self, lacing: &[u8], packet: &[u8], flags: u8) -> std::io::Result<()> {
+        let mut page = Vec::with_capacity(27 + lacing.len() + packet.len());
+
+        page.extend_from_slice(b"OggS");
+        page.push(0); // version
+        page.push(flags);
+        page.extend_from_slice(&self.granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes()); // checksum, filled in below
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(lacing);
+        page.extend_from_slice(packet);
+
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+
+        self.sequence += 1;
+        self.writer.write_all(&page)
+    }
+}
+
+/// Converts a duration into a number of 48 kHz samples.
+fn duration_to_samples(duration: Duration) -> u64 {
+    (duration.as_micros() * 48 / 1000) as u64
+}
+
+const OGG_CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 == 0 { crc << 1 } else { (crc << 1) ^ 0x04c1_1db7 };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter()
+        .fold(0u32, |crc, &byte| (crc << 8) ^ OGG_CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize])
+}
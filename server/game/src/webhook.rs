@@ -21,6 +21,8 @@ pub enum WebhookMessage {
     UserViolationMetaChanged(String, String, bool, bool, Option<i64>, Option<String>), // mod username, username, is_banned, is_muted, expiry, reason
     UserRolesChanged(String, String, Vec<String>, Vec<String>),                        // mod username, username, old roles, new roles
     UserNameColorChanged(String, String, Option<String>, Option<String>),              // mod username, username, old color, new color
+    PlayerJoined(String, i32, u32),                                                    // username, account id, player count
+    PlayerLeft(String, i32, u32),                                                      // username, account id, player count
 }
 
 #[derive(Serialize)]
@@ -314,6 +316,36 @@ pub fn embed_for_message(message: &WebhookMessage) -> Option<WebhookEmbed> {
                 },
             ],
         }),
+        WebhookMessage::PlayerJoined(user_name, account_id, player_count) => Some(WebhookEmbed {
+            title: "Player joined".to_owned(),
+            color: hex_color_to_decimal("#31bd31"),
+            author: Some(WebhookAuthor {
+                name: format!("{user_name} ({account_id})"),
+                icon_url: None,
+            }),
+            description: None,
+            footer: None,
+            fields: vec![WebhookField {
+                name: "Players online",
+                value: player_count.to_string(),
+                inline: Some(true),
+            }],
+        }),
+        WebhookMessage::PlayerLeft(user_name, account_id, player_count) => Some(WebhookEmbed {
+            title: "Player left".to_owned(),
+            color: hex_color_to_decimal("#8c8c8c"),
+            author: Some(WebhookAuthor {
+                name: format!("{user_name} ({account_id})"),
+                icon_url: None,
+            }),
+            description: None,
+            footer: None,
+            fields: vec![WebhookField {
+                name: "Players online",
+                value: player_count.to_string(),
+                inline: Some(true),
+            }],
+        }),
     }
 }
 
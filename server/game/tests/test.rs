@@ -1,7 +1,10 @@
 // this doc is mostly for flamegraphs
 #![allow(clippy::wildcard_imports, clippy::cast_possible_truncation)]
 use esp::{ByteBuffer, ByteReader};
-use globed_game_server::{data::*, managers::LevelManager};
+use globed_game_server::{
+    data::*,
+    managers::{LevelLeaderboard, LevelManager},
+};
 use std::hint::black_box;
 
 const ITERS: usize = 500_000;
@@ -133,3 +136,1047 @@ fn test_player_manager() {
         }
     }
 }
+
+#[test]
+fn test_level_leaderboard() {
+    let mut manager = LevelManager::new();
+
+    let entry = |account_id: i32, best: u32| LeaderboardEntry {
+        account_id,
+        name: InlineString::default(),
+        best,
+    };
+
+    // nobody is on the level yet
+    assert!(!manager.submit_level_result(1, entry(1, 50), false));
+
+    for account_id in 0..20 {
+        manager.add_to_level(1, account_id);
+        assert!(manager.submit_level_result(1, entry(account_id, account_id as u32 * 5), false));
+    }
+
+    // a worse result than before is ignored
+    assert!(!manager.submit_level_result(1, entry(19, 10), false));
+    assert!(manager.submit_level_result(1, entry(0, 100), false));
+
+    let board = manager.get_leaderboard(1, false).unwrap();
+    let ids: Vec<i32> = board.entries.iter().map(|x| x.account_id).collect();
+    assert_eq!(ids, [0, 19, 18, 17, 16, 15, 14, 13, 12, 11]);
+
+    // platformer results go to their own leaderboard, no matter who submitted first
+    assert!(manager.submit_level_result(1, entry(3, 100), true));
+    let board = manager.get_leaderboard(1, true).unwrap();
+    assert!(board.platformer);
+    assert_eq!(board.entries.len(), 1);
+    assert_eq!(manager.get_leaderboard(1, false).unwrap().entries.len(), LEADERBOARD_SIZE);
+
+    assert!(!LevelLeaderboard::is_valid_result(101, false));
+    assert!(!LevelLeaderboard::is_valid_result(0, true));
+
+    // the leaderboard is gone once the level empties
+    for account_id in 0..20 {
+        manager.remove_from_level(1, account_id);
+    }
+
+    assert!(manager.get_leaderboard(1, false).is_none());
+    assert!(manager.get_leaderboard(1, true).is_none());
+}
+
+#[test]
+fn test_profile_revisions() {
+    use globed_game_server::managers::ProfileCache;
+
+    let cache = ProfileCache::default();
+
+    let first = cache.get_revision(1);
+    assert_eq!(cache.get_revision(1), first);
+    assert_ne!(cache.get_revision(2), first);
+
+    // changing the profile gives it a revision that was never used before
+    cache.invalidate(1);
+    let second = cache.get_revision(1);
+    assert_ne!(second, first);
+    assert_ne!(second, cache.get_revision(2));
+}
+
+#[test]
+fn test_profile_cache_eviction() {
+    use globed_game_server::managers::ProfileCache;
+
+    let cache = ProfileCache::default();
+    let profile = |id| PlayerAccountData {
+        account_id: id,
+        user_id: id,
+        name: InlineString::new("some player"),
+        icons: PlayerIconData::default(),
+        special_user_data: SpecialUserData { roles: None },
+    };
+
+    for id in 0..1024 {
+        cache.get_or_fetch(id, || Some(profile(id)));
+    }
+
+    // using a profile makes it the most recently used one, so the next one gets evicted instead
+    assert!(cache.get_or_fetch(0, || None).is_some());
+    cache.get_or_fetch(1024, || Some(profile(1024)));
+
+    assert!(cache.get_or_fetch(0, || None).is_some());
+    assert!(cache.get_or_fetch(1, || None).is_none());
+    assert!(cache.get_or_fetch(2, || None).is_some());
+
+    // invalidated profiles don't take up space
+    cache.invalidate(2);
+    cache.get_or_fetch(1025, || Some(profile(1025)));
+    assert!(cache.get_or_fetch(3, || None).is_some());
+}
+
+#[test]
+fn test_level_profile_gathering() {
+    use globed_game_server::managers::ProfileCache;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Barrier,
+    };
+
+    let cache = Arc::new(ProfileCache::default());
+    let gathers = Arc::new(AtomicU32::new(0));
+    let barrier = Arc::new(Barrier::new(2));
+
+    // a slow gather on one level doesn't hold up another level
+    let slow = {
+        let (cache, gathers, barrier) = (cache.clone(), gathers.clone(), barrier.clone());
+        std::thread::spawn(move || {
+            cache.get_or_gather_level(0, 1, || {
+                gathers.fetch_add(1, Ordering::Relaxed);
+                barrier.wait();
+                barrier.wait();
+                Vec::new()
+            })
+        })
+    };
+
+    barrier.wait();
+    cache.get_or_gather_level(0, 2, || {
+        gathers.fetch_add(1, Ordering::Relaxed);
+        Vec::new()
+    });
+    barrier.wait();
+    slow.join().unwrap();
+
+    // and a recent result is reused
+    cache.get_or_gather_level(0, 1, || {
+        gathers.fetch_add(1, Ordering::Relaxed);
+        Vec::new()
+    });
+
+    assert_eq!(gathers.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_rate_limiter_retry_after() {
+    use globed_game_server::util::SimpleRateLimiter;
+    use std::time::Duration;
+
+    let mut limiter = SimpleRateLimiter::new(2, Duration::from_millis(300));
+    assert_eq!(limiter.time_until_refill(), Duration::ZERO);
+
+    assert!(limiter.try_tick());
+    assert!(limiter.try_tick());
+    assert!(!limiter.try_tick());
+
+    let retry_after = limiter.time_until_refill();
+    assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_millis(300));
+
+    std::thread::sleep(retry_after + Duration::from_millis(10));
+    assert!(limiter.try_tick());
+}
+
+#[test]
+fn test_throughput_limiter() {
+    use globed_game_server::util::ThroughputLimiter;
+    use std::time::Duration;
+
+    let mut limiter = ThroughputLimiter::new(1000, Duration::from_secs(1));
+
+    // a full window can be used at once, but not more
+    assert!(limiter.try_consume(1000));
+    assert!(!limiter.try_consume(100));
+
+    // the budget comes back gradually, not all at once at the end of a window
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(limiter.try_consume(100));
+
+    // after a long pause the first packets are accepted, but the budget never grows past the window
+    std::thread::sleep(Duration::from_millis(1500));
+    assert!(limiter.try_consume(1000));
+    assert!(!limiter.try_consume(200));
+
+    // a higher limit lets the budget grow further, a lower one cuts it down right away
+    limiter.set_rate(2000);
+    std::thread::sleep(Duration::from_millis(1100));
+    assert!(limiter.try_consume(1500));
+    limiter.set_rate(100);
+    assert!(!limiter.try_consume(150));
+}
+
+#[test]
+fn test_profile_sanitization() {
+    assert_eq!(sanitize_player_name("  dankmeme01 ").try_to_str(), "dankmeme01");
+    assert_eq!(sanitize_player_name("evil\nname\u{7}").try_to_str(), "evilname");
+    assert_eq!(sanitize_player_name("\t\r\n").try_to_str(), "Player");
+
+    // cut off without splitting the multi-byte character
+    let long = sanitize_player_name(&"é".repeat(MAX_NAME_SIZE));
+    assert!(long.len() < MAX_NAME_SIZE);
+    assert!(long.to_str().is_ok_and(|x| x.chars().all(|c| c == 'é')));
+
+    let icons = PlayerIconData {
+        cube: 30000,
+        ship: 0,
+        color1: 200,
+        ..Default::default()
+    };
+    assert!(!icons.is_valid());
+
+    let icons = icons.sanitized();
+    assert!(icons.is_valid());
+    assert_eq!(icons.cube, 1);
+    assert_eq!(icons.color1, 1);
+}
+
+#[test]
+fn test_local_bans() {
+    use globed_game_server::managers::BanManager;
+
+    let manager = BanManager::default();
+    let loaded = manager
+        .load_local(r#"[{"account_id": 1, "reason": "spam"}, {"account_id": 2, "expires_at": 1}, {"account_id": 3, "expires_at": 99999999999}]"#)
+        .unwrap();
+
+    // the already expired ban is not loaded at all
+    assert_eq!(loaded, 2);
+    assert_eq!(manager.get_ban(1).and_then(|ban| ban.reason).as_deref(), Some("spam"));
+    assert!(!manager.is_banned(2));
+    assert!(manager.is_banned(3));
+    assert!(!manager.is_banned(4));
+
+    assert!(manager.load_local(r#"[{"account": 1}]"#).is_err());
+}
+
+#[test]
+fn test_ip_bans() {
+    use globed_game_server::managers::IpBanManager;
+    use globed_shared::GameServerBootData;
+    use std::net::IpAddr;
+
+    let banned = |manager: &IpBanManager, addr: &str| manager.is_banned(&addr.parse::<IpAddr>().unwrap());
+
+    let manager = IpBanManager::default();
+    manager.load_local(
+        "# subnets
+10.1.2.77/24
+
+192.168.0.5
+2001:db8::/32
+not an address
+",
+    );
+    assert_eq!(manager.entry_count(), 3);
+
+    // subnets are truncated to their network address
+    assert!(banned(&manager, "10.1.2.0"));
+    assert!(banned(&manager, "10.1.2.255"));
+    assert!(!banned(&manager, "10.1.3.0"));
+    assert!(banned(&manager, "192.168.0.5"));
+    assert!(!banned(&manager, "192.168.0.6"));
+    assert!(banned(&manager, "2001:db8:ffff::1"));
+    assert!(!banned(&manager, "2001:db9::1"));
+
+    // ipv4-mapped addresses from a dual-stack socket match the ipv4 entries
+    assert!(banned(&manager, "::ffff:10.1.2.3"));
+    assert!(banned(&manager, "::ffff:192.168.0.5"));
+    assert!(!banned(&manager, "::ffff:192.168.0.6"));
+
+    // central entries are added on top of the local ones, and replaced on every refresh
+    manager.refresh_from(&GameServerBootData {
+        banned_ips: vec!["172.16.0.0/12".to_owned()],
+        ..Default::default()
+    });
+    assert!(banned(&manager, "172.31.255.255"));
+    assert!(banned(&manager, "10.1.2.3"));
+
+    manager.refresh_from(&GameServerBootData::default());
+    assert!(!banned(&manager, "172.31.255.255"));
+    assert!(banned(&manager, "10.1.2.3"));
+}
+
+#[test]
+fn test_login_limiter() {
+    use globed_game_server::{
+        bridge::{CentralBridge, CentralTlsConfig},
+        managers::LoginLimiter,
+    };
+    use globed_shared::GameServerBootData;
+    use std::net::IpAddr;
+
+    let bridge = CentralBridge::new(Vec::new(), None, CentralTlsConfig::default());
+    bridge.set_boot_data(GameServerBootData {
+        login_attempt_limit: 2,
+        login_attempt_ip_limit: 3,
+        login_attempt_interval: 1,
+        ..Default::default()
+    });
+
+    let limiter = LoginLimiter::default();
+    let first: IpAddr = "1.2.3.4".parse().unwrap();
+    let second: IpAddr = "5.6.7.8".parse().unwrap();
+
+    // per account limit, the same account is limited from any address
+    assert!(limiter.try_attempt(&bridge, 1, first));
+    assert!(limiter.try_attempt(&bridge, 1, first));
+    assert!(!limiter.try_attempt(&bridge, 1, second));
+
+    // per address limit, also counting the attempts that were already rejected, and the ipv4-mapped form of the address
+    assert!(limiter.try_attempt(&bridge, 2, first));
+    assert!(!limiter.try_attempt(&bridge, 3, "::ffff:1.2.3.4".parse().unwrap()));
+    assert!(limiter.try_attempt(&bridge, 4, second));
+
+    // both windows start over once the interval passes
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert!(limiter.try_attempt(&bridge, 1, first));
+    assert!(limiter.try_attempt(&bridge, 2, first));
+
+    // an interval of 0 disables the limiter entirely
+    bridge.set_boot_data(GameServerBootData {
+        login_attempt_limit: 1,
+        login_attempt_interval: 0,
+        ..Default::default()
+    });
+    assert!((0..10).all(|_| limiter.try_attempt(&bridge, 1, first)));
+}
+
+#[test]
+fn test_mute_cache() {
+    use globed_game_server::managers::MuteCache;
+    use globed_shared::IntSet;
+    use std::time::Duration;
+
+    let muted = |ids: &[i32]| ids.iter().copied().collect::<IntSet<i32>>();
+
+    let cache = MuteCache::new(Duration::from_millis(200), 2);
+
+    // taking removes the entry, and players without mutes are not stored at all
+    cache.save(1, muted(&[10, 11]));
+    cache.save(2, IntSet::default());
+    assert_eq!(cache.take(1), muted(&[10, 11]));
+    assert!(cache.take(1).is_empty());
+    assert!(cache.take(2).is_empty());
+
+    // expired entries are not returned
+    cache.save(1, muted(&[10]));
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(cache.take(1).is_empty());
+
+    // once full, the oldest entry makes room for the new one
+    cache.save(1, muted(&[10]));
+    std::thread::sleep(Duration::from_millis(5));
+    cache.save(2, muted(&[20]));
+    cache.save(3, muted(&[30]));
+    assert!(cache.take(1).is_empty());
+    assert_eq!(cache.take(2), muted(&[20]));
+    assert_eq!(cache.take(3), muted(&[30]));
+
+    // saving again for a player that is already stored doesn't evict anyone
+    cache.save(1, muted(&[10]));
+    cache.save(2, muted(&[20]));
+    cache.save(2, muted(&[21]));
+    assert_eq!(cache.take(1), muted(&[10]));
+    assert_eq!(cache.take(2), muted(&[21]));
+}
+
+#[test]
+fn test_local_whitelist() {
+    use globed_game_server::managers::WhitelistManager;
+
+    let manager = WhitelistManager::default();
+    assert_eq!(manager.load_local("# friends\n1\n\n  2  \n-3\nabc\n"), 2);
+    assert!(manager.contains(1));
+    assert!(manager.contains(2));
+    assert!(!manager.contains(-3));
+    assert!(!manager.contains(4));
+
+    // reloading replaces the old list
+    assert_eq!(manager.load_local("4"), 1);
+    assert!(!manager.contains(1));
+    assert!(manager.contains(4));
+}
+
+#[test]
+fn test_address_limiter() {
+    use globed_game_server::managers::AddressLimiter;
+
+    let limiter = AddressLimiter::default();
+    assert_eq!(limiter.load_exempt("# school\n10.0.0.0/8\ninvalid\n"), 1);
+
+    let addr = "1.2.3.4".parse().unwrap();
+    assert!(limiter.try_add(1, addr, 2));
+    assert!(limiter.try_add(2, addr, 2));
+    assert!(!limiter.try_add(3, addr, 2));
+
+    // logging in again from the same address doesn't count twice
+    assert!(limiter.try_add(2, addr, 2));
+
+    limiter.remove(1);
+    assert!(limiter.try_add(3, addr, 2));
+
+    // moving to another address frees up the old slot
+    assert!(limiter.try_add(3, "5.6.7.8".parse().unwrap(), 2));
+    assert!(limiter.try_add(4, addr, 2));
+    assert!(!limiter.try_add(5, addr, 2));
+
+    for id in 10..20 {
+        assert!(limiter.try_add(id, "127.0.0.1".parse().unwrap(), 2));
+        assert!(limiter.try_add(id + 100, "10.1.2.3".parse().unwrap(), 2));
+    }
+
+    // also used to exempt addresses from the handshake limit
+    assert!(limiter.is_exempt(&"::ffff:10.1.2.3".parse().unwrap()));
+    assert!(!limiter.is_exempt(&addr));
+}
+
+#[test]
+fn test_login_queue() {
+    use globed_game_server::managers::LoginQueue;
+
+    let queue = LoginQueue::default();
+    let first = queue.join(2).unwrap();
+    let second = queue.join(2).unwrap();
+    assert!(queue.join(2).is_none());
+    assert_eq!(queue.position(second), Some(2));
+
+    // only the front of the queue can take a slot, and only if there is one
+    assert!(!queue.try_promote(second, || true));
+    assert!(!queue.try_promote(first, || false));
+    assert!(queue.try_promote(first, || true));
+    assert_eq!(queue.position(first), None);
+    assert_eq!(queue.position(second), Some(1));
+
+    let third = queue.join(2).unwrap();
+    queue.leave(second);
+    assert_eq!(queue.position(third), Some(1));
+    assert_eq!(queue.len(), 1);
+}
+
+#[tokio::test]
+async fn test_tcp_framing_split_reads() {
+    use globed_game_server::client::framing;
+    use tokio::io::AsyncWriteExt;
+
+    let packet: Vec<u8> = (0..1000u32).map(|x| x as u8).collect();
+
+    let mut framed = (packet.len() as u32).to_be_bytes().to_vec();
+    framed.extend_from_slice(&packet);
+
+    let (mut client, mut server) = tokio::io::duplex(4096);
+
+    let reader = tokio::spawn(async move {
+        let mut inline_buf = [0u8; 164];
+        let mut heap_buf = Vec::new();
+
+        let len = framing::read_frame_header(&mut server).await.unwrap();
+        framing::read_frame_body(&mut server, len, &mut inline_buf, &mut heap_buf)
+            .await
+            .unwrap()
+            .to_vec()
+    });
+
+    // split the packet in the middle, so that the reader has to wait for the second half
+    let (first, second) = framed.split_at(framed.len() / 2);
+    client.write_all(first).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    client.write_all(second).await.unwrap();
+
+    assert_eq!(reader.await.unwrap(), packet);
+}
+
+#[tokio::test]
+async fn test_token_secret_rotation() {
+    use globed_game_server::bridge::{CentralBridge, CentralTlsConfig};
+    use globed_shared::{GameServerBootData, TokenIssuer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let bridge = CentralBridge::new(Vec::new(), None, CentralTlsConfig::default());
+    bridge.set_boot_data(GameServerBootData {
+        secret_key2: "old secret".to_owned(),
+        token_expiry: 86400,
+        ..Default::default()
+    });
+
+    // the central server has rotated its secret, and new tokens are signed with the new one
+    let token = TokenIssuer::new("new secret", std::time::Duration::from_secs(86400)).generate(1, 2, "player");
+    let refreshes = AtomicUsize::new(0);
+
+    let refresh = || async {
+        refreshes.fetch_add(1, Ordering::Relaxed);
+
+        // simulates the response of the central server, now containing the new secret
+        bridge.set_boot_data(GameServerBootData {
+            secret_key2: "new secret".to_owned(),
+            token_expiry: 86400,
+            ..Default::default()
+        });
+
+        true
+    };
+
+    let result = bridge.validate_token(1, 2, &token, refresh).await;
+    assert_eq!(result.ok().as_deref(), Some("player"));
+    assert_eq!(refreshes.load(Ordering::Relaxed), 1);
+
+    // a token that is still invalid must not cause another refresh right away
+    let bad_token = TokenIssuer::new("some other secret", std::time::Duration::from_secs(86400)).generate(1, 2, "player");
+    let result = bridge.validate_token(1, 2, &bad_token, refresh).await;
+    assert!(result.is_err());
+    assert_eq!(refreshes.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_admin_key_generation() {
+    use globed_game_server::bridge::{CentralBridge, CentralTlsConfig};
+    use globed_shared::GameServerBootData;
+    use std::sync::atomic::Ordering;
+
+    let bridge = CentralBridge::new(Vec::new(), None, CentralTlsConfig::default());
+    let data = GameServerBootData {
+        admin_key: "first key".into(),
+        ..Default::default()
+    };
+
+    bridge.set_boot_data(data.clone());
+    let generation = bridge.admin_key_generation.load(Ordering::Relaxed);
+
+    // refreshing without a new key keeps everyone logged in
+    bridge.set_boot_data(data.clone());
+    assert_eq!(bridge.admin_key_generation.load(Ordering::Relaxed), generation);
+
+    bridge.set_boot_data(GameServerBootData {
+        admin_key: "second key".into(),
+        ..data
+    });
+    assert_ne!(bridge.admin_key_generation.load(Ordering::Relaxed), generation);
+}
+
+#[tokio::test]
+async fn test_expired_token_no_refresh() {
+    use globed_game_server::bridge::{CentralBridge, CentralTlsConfig};
+    use globed_shared::{token_issuer::TokenValidationFailure, GameServerBootData};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let bridge = CentralBridge::new(Vec::new(), None, CentralTlsConfig::default());
+    bridge.set_boot_data(GameServerBootData {
+        secret_key2: "secret".to_owned(),
+        token_expiry: 0,
+        ..Default::default()
+    });
+
+    let token = bridge.token_issuer.lock().generate(1, 2, "player");
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    // an expired token is rejected locally, without asking the central server
+    let refreshes = AtomicUsize::new(0);
+    let result = bridge
+        .validate_token(1, 2, &token, || async {
+            refreshes.fetch_add(1, Ordering::Relaxed);
+            true
+        })
+        .await;
+
+    assert!(matches!(result, Err(TokenValidationFailure::Expired)));
+    assert_eq!(refreshes.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn test_local_max_packet_size() {
+    use globed_game_server::bridge::{CentralBridge, CentralTlsConfig};
+    use globed_shared::GameServerBootData;
+
+    let bridge = CentralBridge::new(Vec::new(), None, CentralTlsConfig::default());
+    bridge.set_boot_data(GameServerBootData {
+        max_packet_size: 32768,
+        ..Default::default()
+    });
+    assert_eq!(bridge.max_packet_size(), 32768);
+
+    // the local override wins over the central server, including later refreshes
+    assert_eq!(bridge.set_local_max_packet_size(16384), 16384);
+    bridge.set_boot_data(GameServerBootData {
+        max_packet_size: 8192,
+        ..Default::default()
+    });
+    assert_eq!(bridge.max_packet_size(), 16384);
+
+    // too small values are raised to the minimum
+    assert_eq!(bridge.set_local_max_packet_size(100), 2048);
+    assert_eq!(bridge.max_packet_size(), 2048);
+}
+
+#[test]
+fn test_logger_shutdown_flush() {
+    use globed_shared::{log, Logger};
+
+    const THREADS: usize = 4;
+    const LINES: usize = 2000;
+
+    fn log_line(logger: &Logger, args: std::fmt::Arguments) {
+        log::Log::log(
+            logger,
+            &log::Record::builder().target("globed_tests").level(log::Level::Info).args(args).build(),
+        );
+    }
+
+    let dir = std::env::temp_dir().join(format!("globed_logger_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("globed_tests.log");
+
+    let logger = Logger::new("globed_tests", Some(path.clone()));
+
+    // other threads keep logging while the flush happens, and the logger is never dropped (which would flush the buffer),
+    // so every line has to make it to disk either through the flush itself or through the write-through afterwards
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let logger = &logger;
+            scope.spawn(move || {
+                for line in 0..LINES {
+                    log_line(logger, format_args!("thread {thread} line {line}"));
+                }
+            });
+        }
+
+        logger.flush_for_shutdown().unwrap();
+    });
+
+    log_line(&logger, format_args!("terminating"));
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(contents.lines().filter(|line| line.contains(" line ")).count(), THREADS * LINES);
+    assert!(contents.lines().last().is_some_and(|line| line.ends_with("terminating")));
+}
+
+#[tokio::test]
+async fn test_proxy_protocol() {
+    use globed_game_server::util::proxy_protocol::read_proxy_header;
+    use std::net::SocketAddr;
+
+    const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+    fn v2_header(ver_cmd: u8, family: u8, length: u16, data: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[ver_cmd, family]);
+        header.extend_from_slice(&length.to_be_bytes());
+        header.extend_from_slice(data);
+        header
+    }
+
+    let peer: SocketAddr = "10.0.0.1:4201".parse().unwrap();
+
+    // returns the parsed address and whatever is left in the stream after the header
+    async fn read(data: &[u8], peer: SocketAddr) -> (Option<SocketAddr>, Vec<u8>) {
+        let mut stream = data;
+        let result = read_proxy_header(&mut stream, peer).await.ok();
+        (result, stream.to_vec())
+    }
+
+    // v1
+    let (addr, rest) = read(b"PROXY TCP4 1.2.3.4 5.6.7.8 1234 4202\r\npayload", peer).await;
+    assert_eq!(addr, Some("1.2.3.4:1234".parse().unwrap()));
+    assert_eq!(rest, b"payload");
+
+    let (addr, _) = read(b"PROXY TCP6 ::ffff:1.2.3.4 ::1 1234 4202\r\n", peer).await;
+    assert_eq!(addr, Some("[::ffff:1.2.3.4]:1234".parse().unwrap()));
+
+    let (addr, rest) = read(b"PROXY UNKNOWN\r\npayload", peer).await;
+    assert_eq!(addr, Some(peer));
+    assert_eq!(rest, b"payload");
+
+    // truncated, missing fields, unsupported protocol, oversized
+    assert_eq!(read(b"PROXY TCP4 1.2.3.4 5.6.", peer).await.0, None);
+    assert_eq!(read(b"PROXY TCP4 1.2.3.4 5.6.7.8 1234\r\n", peer).await.0, None);
+    assert_eq!(read(b"PROXY UDP4 1.2.3.4 5.6.7.8 1234 4202\r\n", peer).await.0, None);
+
+    let mut oversized = b"PROXY TCP4 ".to_vec();
+    oversized.resize(200, b'1');
+    oversized.extend_from_slice(b"\r\n");
+    assert_eq!(read(&oversized, peer).await.0, None);
+
+    assert_eq!(read(b"GET / HTTP/1.1\r\n\r\n", peer).await.0, None);
+
+    // v2, ipv4: src addr, dst addr, src port, dst port
+    let inet = [1, 2, 3, 4, 5, 6, 7, 8, 0x04, 0xd2, 0x10, 0x6a];
+    let mut data = v2_header(0x21, 0x11, inet.len() as u16, &inet);
+    data.extend_from_slice(b"payload");
+    let (addr, rest) = read(&data, peer).await;
+    assert_eq!(addr, Some("1.2.3.4:1234".parse().unwrap()));
+    assert_eq!(rest, b"payload");
+
+    // v2, ipv6
+    let mut inet6 = [0u8; 36];
+    inet6[15] = 1;
+    inet6[32..34].copy_from_slice(&1234u16.to_be_bytes());
+    let (addr, _) = read(&v2_header(0x21, 0x21, inet6.len() as u16, &inet6), peer).await;
+    assert_eq!(addr, Some("[::1]:1234".parse().unwrap()));
+
+    // LOCAL still consumes the address block, but keeps the peer address
+    let mut data = v2_header(0x20, 0x11, inet.len() as u16, &inet);
+    data.extend_from_slice(b"payload");
+    let (addr, rest) = read(&data, peer).await;
+    assert_eq!(addr, Some(peer));
+    assert_eq!(rest, b"payload");
+
+    // AF_UNSPEC carries no address
+    assert_eq!(read(&v2_header(0x21, 0x00, 0, &[]), peer).await.0, Some(peer));
+
+    // unsupported address family (AF_UNIX), wrong version, unknown command
+    assert_eq!(read(&v2_header(0x21, 0x31, 216, &[0u8; 216]), peer).await.0, None);
+    assert_eq!(read(&v2_header(0x11, 0x11, inet.len() as u16, &inet), peer).await.0, None);
+    assert_eq!(read(&v2_header(0x22, 0x11, inet.len() as u16, &inet), peer).await.0, None);
+
+    // truncated header, address block shorter than the family requires, length past the end of the stream
+    assert_eq!(read(&v2_header(0x21, 0x11, 0, &[])[..14], peer).await.0, None);
+    assert_eq!(read(&v2_header(0x21, 0x11, 4, &inet[..4]), peer).await.0, None);
+    assert_eq!(read(&v2_header(0x21, 0x11, u16::MAX, &inet), peer).await.0, None);
+}
+
+#[test]
+fn test_compressed_encrypted_roundtrip() {
+    use globed_game_server::client::socket::{compress_payload, decompress_payload, decrypt_in_place, encode_packet_into};
+    use globed_shared::crypto_box::{aead::OsRng, ChaChaBox, SecretKey};
+
+    let server_key = SecretKey::generate(&mut OsRng);
+    let client_key = SecretKey::generate(&mut OsRng);
+    let server_box = ChaChaBox::new(&client_key.public_key(), &server_key);
+    let client_box = ChaChaBox::new(&server_key.public_key(), &client_key);
+
+    // profile lists are very repetitive, so they compress well
+    let packet = PlayerProfilesPacket {
+        players: (1..=100)
+            .map(|id| PlayerAccountData {
+                account_id: id,
+                user_id: id,
+                name: InlineString::new("some player"),
+                icons: PlayerIconData::default(),
+                special_user_data: SpecialUserData { roles: None },
+            })
+            .collect(),
+    };
+
+    let mut buf = ByteBuffer::new();
+    buf.write_value(&packet);
+    let raw = buf.as_bytes();
+
+    let compressed = compress_payload(raw).unwrap();
+    assert!(compressed.len() < raw.len());
+
+    let mut out = Vec::new();
+    assert!(encode_packet_into(
+        &mut out,
+        PlayerProfilesPacket::PACKET_ID,
+        PACKET_FLAG_COMPRESSED,
+        &compressed,
+        Some(&server_box),
+        true
+    )
+    .is_ok());
+
+    let length = u32::from_be_bytes(out[..4].try_into().unwrap()) as usize;
+    assert_eq!(length, out.len() - 4);
+
+    let message = &mut out[4..];
+    let header = ByteReader::from_bytes(message).read_packet_header().unwrap();
+    assert_eq!(header.packet_id, PlayerProfilesPacket::PACKET_ID);
+    assert!(header.encrypted() && header.compressed());
+
+    let Ok(data) = decrypt_in_place(&client_box, message) else {
+        panic!("failed to decrypt the packet");
+    };
+    assert_eq!(decompress_payload(data.as_bytes(), 1 << 20).ok().as_deref(), Some(raw));
+
+    // inflating past the limit must fail rather than allocate
+    assert!(decompress_payload(data.as_bytes(), 64).is_err());
+
+    // random data doesn't get any smaller, so it is sent as is
+    let noise: Vec<u8> = (0..4096).map(|_| globed_shared::rand::random()).collect();
+    assert!(compress_payload(&noise).is_none());
+}
+
+#[test]
+fn test_protocol_negotiation() {
+    use globed_shared::{negotiate_protocol, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+    assert_eq!(negotiate_protocol(PROTOCOL_VERSION), Some(PROTOCOL_VERSION));
+    assert_eq!(negotiate_protocol(MIN_PROTOCOL_VERSION), Some(MIN_PROTOCOL_VERSION));
+    assert_eq!(negotiate_protocol(MIN_PROTOCOL_VERSION - 1), None);
+    assert_eq!(negotiate_protocol(PROTOCOL_VERSION + 1), None);
+    assert_eq!(negotiate_protocol(0xffff), Some(PROTOCOL_VERSION));
+}
+
+#[test]
+fn test_handshake_capabilities() {
+    let mut buf = ByteBuffer::new();
+    buf.write_u16(6);
+    buf.write_bytes(&[0u8; 32]);
+
+    // older clients don't send any capabilities
+    let packet: CryptoHandshakeStartPacket = ByteReader::from_bytes(buf.as_bytes()).read_value().unwrap();
+    assert!(!packet.capabilities.compression());
+    assert!(!packet.capabilities.profiles_ratelimited());
+
+    buf.write_u8(HandshakeCapabilities::COMPRESSION);
+    let packet: CryptoHandshakeStartPacket = ByteReader::from_bytes(buf.as_bytes()).read_value().unwrap();
+    assert!(packet.capabilities.compression());
+    assert!(!packet.capabilities.profiles_ratelimited());
+    assert!(!packet.capabilities.voice_batching());
+}
+
+#[test]
+fn test_connection_phase_tracker() {
+    use globed_game_server::metrics::{ConnectionPhase, ConnectionPhaseTracker, ServerMetrics};
+
+    let metrics: &'static ServerMetrics = Box::leak(Box::default());
+
+    let first = ConnectionPhaseTracker::new(metrics);
+    let second = ConnectionPhaseTracker::new(metrics);
+    assert_eq!(metrics.connection_count(ConnectionPhase::Handshake), 2);
+
+    first.set(ConnectionPhase::Unauthenticated);
+    first.set(ConnectionPhase::Authenticated);
+    second.set(ConnectionPhase::Unauthenticated);
+    assert_eq!(metrics.connection_count(ConnectionPhase::Handshake), 0);
+    assert_eq!(metrics.connection_count(ConnectionPhase::Unauthenticated), 1);
+    assert_eq!(metrics.connection_count(ConnectionPhase::Authenticated), 1);
+
+    drop(first);
+    drop(second);
+    assert!(ConnectionPhase::ALL.iter().all(|phase| metrics.connection_count(*phase) == 0));
+}
+
+#[test]
+fn test_handshake_failure_limit() {
+    use globed_game_server::managers::PreauthLimiter;
+
+    let limiter = PreauthLimiter::default();
+    let address: std::net::IpAddr = "203.0.113.7".parse().unwrap();
+    let other: std::net::IpAddr = "203.0.113.8".parse().unwrap();
+
+    for _ in 0..3 {
+        assert!(limiter.try_handshake(address, 5));
+        limiter.record_handshake_failure(address);
+    }
+
+    // only the address that kept failing gets blocked
+    assert!(!limiter.try_handshake(address, 5));
+    assert!(limiter.try_handshake(other, 5));
+
+    // the limit comes from the central server, 0 disables it
+    let busy: std::net::IpAddr = "203.0.113.9".parse().unwrap();
+    assert!(limiter.try_handshake(busy, 2));
+    assert!(limiter.try_handshake(busy, 2));
+    assert!(!limiter.try_handshake(busy, 2));
+    assert!(limiter.try_handshake(busy, 0));
+}
+
+#[test]
+fn test_audit_log() {
+    use globed_game_server::audit::AuditLog;
+
+    let path = std::env::temp_dir().join(format!("globed_audit_test_{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let log = AuditLog::open(&path).unwrap();
+    log.record(1, "admin", "kick", Some(2), "reason: \"spam\"");
+    log.record(1, "admin", "maintenance", None, "enabled maintenance mode");
+    drop(log);
+
+    // reopening appends instead of truncating
+    AuditLog::open(&path).unwrap().record(3, "mod", "log_level", None, "set to debug");
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let entries: Vec<serde_json::Value> = contents.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0]["admin_id"], 1);
+    assert_eq!(entries[0]["target_id"], 2);
+    assert_eq!(entries[0]["details"], "reason: \"spam\"");
+    assert!(entries[1].get("target_id").is_none());
+    assert_eq!(entries[2]["action"], "log_level");
+    assert!(entries.iter().all(|entry| entry["timestamp"].as_str().is_some_and(|x| !x.is_empty())));
+}
+
+#[test]
+fn test_voice_recording_shutdown() {
+    use globed_game_server::voice_recorder::{VoiceRecorder, VoiceRecordingConfig};
+    use std::{sync::Arc, time::Duration};
+
+    let directory = std::env::temp_dir().join(format!("globed_recording_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let recorder = VoiceRecorder::start(VoiceRecordingConfig {
+        directory: directory.clone(),
+        levels: None,
+        retention: Duration::from_secs(3600),
+    });
+
+    let mut frames = ByteBuffer::new();
+    frames.write_value(&EncodedAudioFrame {
+        opus_frames: std::array::from_fn(|i| (i == 0).then(|| vec![0x78; 80])),
+    });
+
+    let packet = Arc::new(EncodedVoiceBroadcastPacket::new(&VoiceBroadcastPacket {
+        player_id: 1234,
+        data: FastEncodedAudioFrame {
+            data: frames.as_bytes().to_vec().into(),
+        },
+    }));
+
+    // two packets right after each other, then one after a pause
+    assert!(recorder.record(&packet, 1));
+    assert!(recorder.record(&packet, 1));
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(recorder.record(&packet, 1));
+    recorder.shutdown();
+
+    let file = std::fs::read_dir(directory.join("1234")).unwrap().next().unwrap().unwrap().path();
+    let contents = std::fs::read(file).unwrap();
+    let _ = std::fs::remove_dir_all(&directory);
+
+    // head, tags, the voice packets and an empty page that ends the stream, with the EOS flag set
+    let pages: Vec<usize> = (0..contents.len() - 3).filter(|&i| &contents[i..i + 4] == b"OggS").collect();
+    assert_eq!(pages.len(), 6);
+
+    let last = &contents[pages[5]..];
+    assert_eq!(last[5], 0x04);
+    assert_eq!(last.len(), 27);
+
+    // the reader fails on a wrong checksum, so this also checks the crc of every page
+    let mut reader = ogg::PacketReader::new(std::io::Cursor::new(contents));
+    let mut granules = Vec::new();
+    while let Some(packet) = reader.read_packet().expect("invalid ogg page") {
+        granules.push(packet.absgp_page());
+    }
+
+    // a 20ms frame is 960 samples at 48 kHz, and the pause is kept in the recording
+    assert_eq!(granules[..4], [0, 0, 960, 1920]);
+    let paused = granules[4] - 2880;
+    assert!((200 * 48..1000 * 48).contains(&paused), "pause of {paused} samples");
+}
+
+#[test]
+fn test_voice_frame_limit() {
+    // 20ms frames at 32 kbit/s, the client's usual settings
+    let frame = || Some(vec![0x78; 80]);
+
+    let encode = |frame_count: usize| {
+        let frames = EncodedAudioFrame {
+            opus_frames: std::array::from_fn(|i| if i < frame_count { frame() } else { None }),
+        };
+
+        let mut buf = ByteBuffer::new();
+        buf.write_value(&frames);
+
+        FastEncodedAudioFrame {
+            data: buf.as_bytes().to_vec().into(),
+        }
+    };
+
+    assert_eq!(encode(5).average_frame_size(5), Ok(80));
+    assert_eq!(encode(0).average_frame_size(5), Ok(0));
+
+    // more frames than allowed is told apart from malformed data
+    assert_eq!(encode(10).average_frame_size(5), Err(VoiceFrameError::TooManyFrames(10)));
+    assert_eq!(encode(10).average_frame_size(usize::MAX), Ok(80));
+
+    let mut truncated = encode(5);
+    truncated.data = truncated.data[..truncated.data.len() - 1].to_vec().into();
+    assert_eq!(truncated.average_frame_size(5), Err(VoiceFrameError::Malformed));
+}
+
+#[test]
+fn test_packet_checksum() {
+    let mut buf = ByteBuffer::new();
+    buf.write_value(&PacketHeader {
+        packet_id: PingPacket::PACKET_ID,
+        flags: PACKET_FLAG_CHECKSUM,
+    });
+    buf.write_u32(42);
+    buf.append_self_checksum();
+
+    let mut data = buf.into_vec();
+    let header = ByteReader::from_bytes(&data).read_packet_header().unwrap();
+    assert_eq!(header.verify_checksum(&data), Some(data.len() - 4));
+
+    // a single flipped bit anywhere gets caught
+    data[3] ^= 0x10;
+    assert_eq!(header.verify_checksum(&data), None);
+
+    let plain = PacketHeader::from_packet::<PingPacket>();
+    assert_eq!(plain.verify_checksum(&data), Some(data.len()));
+}
+
+#[test]
+fn test_packet_error_classes() {
+    use globed_game_server::{
+        client::{ErrorClass, PacketHandlingError},
+        metrics::ServerMetrics,
+    };
+
+    let metrics = ServerMetrics::default();
+    metrics.record_packet_error(&PacketHandlingError::Ratelimited);
+    metrics.record_packet_error(&PacketHandlingError::DecryptionError);
+    metrics.record_packet_error(&PacketHandlingError::PacketTooLong(1 << 20));
+
+    assert_eq!(metrics.packet_error_count(ErrorClass::Ratelimited), 1);
+    assert_eq!(metrics.packet_error_count(ErrorClass::Crypto), 1);
+    assert_eq!(metrics.packet_error_count(ErrorClass::Malformed), 1);
+    assert_eq!(metrics.packet_error_count(ErrorClass::Internal), 0);
+
+    // a client that sends too many frames is broken, not sending too fast
+    assert_eq!(PacketHandlingError::TooManyVoiceFrames(64).class(), ErrorClass::Malformed);
+
+    // only errors that leave the stream unreadable end the connection
+    assert!(PacketHandlingError::PacketTooLong(1 << 20).is_fatal());
+    assert!(!PacketHandlingError::DecryptionError.is_fatal());
+}
+
+#[test]
+fn test_packet_size_limits() {
+    assert_eq!(packet_size_limit(VoicePacket::PACKET_ID), Some(MAX_VOICE_PACKET_SIZE));
+    assert_eq!(packet_size_limit(LoginPacket::PACKET_ID), None);
+
+    // the largest player data the client can send still has to fit, even when encrypted and checksummed
+    let limit = packet_size_limit(PlayerDataPacket::PACKET_ID).unwrap();
+    assert!(limit >= PacketHeader::SIZE + PlayerData::ENCODED_SIZE + 24 + 16 + 4);
+    assert!(limit < MAX_VOICE_PACKET_SIZE);
+}
+
+#[test]
+fn test_packet_counters() {
+    use globed_game_server::metrics::PacketCounters;
+
+    let counters = PacketCounters::default();
+    for _ in 0..3 {
+        counters.record(VoicePacket::PACKET_ID);
+    }
+    counters.record(PlayerDataPacket::PACKET_ID);
+    counters.record(29000);
+    counters.record(12999);
+    counters.record(5);
+
+    // ids without a handler are all counted under 0
+    assert_eq!(
+        counters.get_all(),
+        vec![(0, 3), (PlayerDataPacket::PACKET_ID, 1), (VoicePacket::PACKET_ID, 3)]
+    );
+}
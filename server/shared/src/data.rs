@@ -1,21 +1,52 @@
 use super::*;
 use esp::FastString;
 use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Encodable, Decodable, Clone)]
 pub struct GameServerBootData {
+    /// must stay the first field, the game server reads it before decoding the rest
     pub protocol: u16,
     pub tps: u32,
     pub maintenance: bool,
     pub secret_key2: String,
     pub token_expiry: u64,
     pub status_print_interval: u64,
+    pub config_refresh_interval: u64,
     pub admin_key: FastString,
     pub whitelist: bool,
     pub admin_webhook_url: String,
+    pub join_leave_webhook: bool,
+    pub server_name: String,
+    pub motd: String,
     pub chat_burst_limit: u32,
     pub chat_burst_interval: u32,
+    pub chat_enabled: bool,
+    pub voice_throughput_limit: u32,
+    pub voice_proximity_radius: u32,
+    pub voice_silence_threshold: u32,
+    pub voice_min_bitrate: u32,
+    pub voice_max_bitrate: u32,
+    pub voice_max_frames: u32,
+    pub voice_enabled: bool,
+    pub max_packet_size: u32,
+    pub keepalive_interval: u32,
+    pub login_attempt_limit: u32,
+    pub login_attempt_ip_limit: u32,
+    pub login_attempt_interval: u32,
+    pub handshake_limit: u32,
+    pub user_data_cache_ttl: u32,
+    pub profile_request_limit: u32,
+    pub profile_request_interval: u32,
+    pub profile_request_max_players: u32,
+    pub profiles_enabled: bool,
+    pub max_players: u32,
+    pub login_queue_size: u32,
+    pub soft_max_players: u32,
+    pub max_accounts_per_ip: u32,
     pub roles: Vec<ServerRole>,
+    pub banned_users: Vec<BannedUser>,
+    pub banned_ips: Vec<String>,
 }
 
 impl Default for GameServerBootData {
@@ -32,16 +63,108 @@ impl Default for GameServerBootData {
             secret_key2: String::new(),
             token_expiry: 0,
             status_print_interval,
+            config_refresh_interval: 300,
             admin_key: generate_alphanum_string(ADMIN_KEY_LENGTH).into(),
             whitelist: false,
             admin_webhook_url: String::new(),
+            join_leave_webhook: false,
+            server_name: String::new(),
+            motd: String::new(),
             chat_burst_limit: 0,
             chat_burst_interval: 0,
+            chat_enabled: true,
+            voice_throughput_limit: 8,
+            voice_proximity_radius: 0,
+            voice_silence_threshold: 0,
+            voice_min_bitrate: 0,
+            voice_max_bitrate: 0,
+            voice_max_frames: 10,
+            voice_enabled: true,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            keepalive_interval: 30,
+            login_attempt_limit: 5,
+            login_attempt_ip_limit: 20,
+            login_attempt_interval: 60,
+            handshake_limit: 5,
+            user_data_cache_ttl: 30,
+            profile_request_limit: 10,
+            profile_request_interval: 5000,
+            profile_request_max_players: 100,
+            profiles_enabled: true,
+            max_players: 0,
+            login_queue_size: 0,
+            soft_max_players: 0,
+            max_accounts_per_ip: 0,
             roles: Vec::new(),
+            banned_users: Vec::new(),
+            banned_ips: Vec::new(),
         }
     }
 }
 
+impl GameServerBootData {
+    /// Returns the names of all the fields that differ between the two configurations.
+    pub fn changed_fields(&self, other: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+
+        macro_rules! compare {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if self.$field != other.$field {
+                        changed.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+
+        compare!(
+            protocol,
+            tps,
+            maintenance,
+            secret_key2,
+            token_expiry,
+            status_print_interval,
+            config_refresh_interval,
+            admin_key,
+            whitelist,
+            admin_webhook_url,
+            join_leave_webhook,
+            server_name,
+            motd,
+            chat_burst_limit,
+            chat_burst_interval,
+            chat_enabled,
+            voice_throughput_limit,
+            voice_proximity_radius,
+            voice_silence_threshold,
+            voice_min_bitrate,
+            voice_max_bitrate,
+            voice_max_frames,
+            voice_enabled,
+            max_packet_size,
+            keepalive_interval,
+            login_attempt_limit,
+            login_attempt_ip_limit,
+            login_attempt_interval,
+            handshake_limit,
+            user_data_cache_ttl,
+            profile_request_limit,
+            profile_request_interval,
+            profile_request_max_players,
+            profiles_enabled,
+            max_players,
+            login_queue_size,
+            soft_max_players,
+            max_accounts_per_ip,
+            roles,
+            banned_users,
+            banned_ips,
+        );
+
+        changed
+    }
+}
+
 #[derive(Encodable, Decodable, Serialize, Deserialize, DynamicSize, Clone, Default)]
 pub struct UserEntry {
     pub account_id: i32,
@@ -65,7 +188,15 @@ impl UserEntry {
     }
 }
 
-#[derive(Serialize, Deserialize, Encodable, Decodable, DynamicSize, Clone, Default)]
+/// Banned account, sent to game servers so they can reject it without asking the central server on every login.
+#[derive(Encodable, Decodable, Clone, PartialEq)]
+pub struct BannedUser {
+    pub account_id: i32,
+    pub reason: Option<String>,
+    pub expiry: Option<i64>, // seconds since unix epoch
+}
+
+#[derive(Serialize, Deserialize, Encodable, Decodable, DynamicSize, Clone, Default, PartialEq)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct ServerRole {
     pub id: String,
@@ -95,3 +226,43 @@ pub struct ServerRole {
     #[serde(default)]
     pub admin: bool,
 }
+
+impl BannedUser {
+    /// Message telling the user why they are banned and for how long.
+    pub fn rejection_message(&self) -> String {
+        let reason = self.reason.as_deref().filter(|x| !x.is_empty()).unwrap_or("No reason given");
+
+        match self.expiry {
+            Some(expiry) => format!("Banned from the server: {reason} (expires in {})", format_remaining_time(expiry)),
+            None => format!("Banned from the server: {reason} (permanent)"),
+        }
+    }
+}
+
+/// Formats the time left until `timestamp` (seconds since unix epoch) in a human readable way, for example `2 days, 5 hours`.
+#[allow(clippy::cast_possible_wrap)]
+pub fn format_remaining_time(timestamp: i64) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("clock went backwards").as_secs() as i64;
+    let remaining = (timestamp - now).max(0);
+
+    let units = [("day", 86_400), ("hour", 3600), ("minute", 60)];
+
+    let parts: Vec<_> = units
+        .iter()
+        .scan(remaining, |left, (name, secs)| {
+            let count = *left / secs;
+            *left %= secs;
+            Some((count, name))
+        })
+        .skip_while(|(count, _)| *count == 0)
+        .take(2)
+        .filter(|(count, _)| *count != 0)
+        .map(|(count, name)| format!("{count} {name}{}", if count == 1 { "" } else { "s" }))
+        .collect();
+
+    if parts.is_empty() {
+        "less than a minute".to_owned()
+    } else {
+        parts.join(", ")
+    }
+}
@@ -34,9 +34,13 @@ pub mod data;
 pub mod logger;
 pub mod token_issuer;
 
-pub const PROTOCOL_VERSION: u16 = 6;
+/// changes since the oldest supported version:
+/// * v8 - `RequestLeaderboardPacket` has a bool for whether the platformer leaderboard is requested
+pub const PROTOCOL_VERSION: u16 = 8;
+/// oldest protocol version that clients can still connect with
+pub const MIN_PROTOCOL_VERSION: u16 = 7;
 // used for communicating to the user the minimum required mod version for this protocol
-pub const MIN_CLIENT_VERSION: &str = "v1.4.0";
+pub const MIN_CLIENT_VERSION: &str = "v1.5.0";
 pub const SERVER_MAGIC: &[u8] = b"\xdd\xeeglobed\xda\xee";
 pub const SERVER_MAGIC_LEN: usize = SERVER_MAGIC.len();
 /// amount of chars in an admin key (32)
@@ -44,6 +48,8 @@ pub const ADMIN_KEY_LENGTH: usize = 32;
 /// maximum characters in a user's name (24). they can only be 15 chars max but we give headroom just in case
 pub const MAX_NAME_SIZE: usize = 24;
 pub const VIOLATION_REASON_LENGTH: usize = 128;
+/// default upper bound (in bytes) for a single packet sent by a client (64 KiB)
+pub const DEFAULT_MAX_PACKET_SIZE: u32 = 65536;
 
 pub const DEFAULT_CENTRAL_SERVER_PORT: u16 = 4201;
 pub const DEFAULT_GAME_SERVER_PORT: u16 = 4202;
@@ -61,14 +67,30 @@ pub fn get_log_level(env_var: &str) -> Option<LogLevelFilter> {
                 LogLevelFilter::Info
             })
         },
-        |level| match &*level.to_lowercase() {
-            "trace" => Some(LogLevelFilter::Trace),
-            "debug" => Some(LogLevelFilter::Debug),
-            "info" => Some(LogLevelFilter::Info),
-            "warn" => Some(LogLevelFilter::Warn),
-            "error" => Some(LogLevelFilter::Error),
-            "off" => Some(LogLevelFilter::Off),
-            _ => None,
-        },
+        |level| parse_log_level(&level),
     )
 }
+
+pub fn parse_log_level(level: &str) -> Option<LogLevelFilter> {
+    match &*level.to_lowercase() {
+        "trace" => Some(LogLevelFilter::Trace),
+        "debug" => Some(LogLevelFilter::Debug),
+        "info" => Some(LogLevelFilter::Info),
+        "warn" => Some(LogLevelFilter::Warn),
+        "error" => Some(LogLevelFilter::Error),
+        "off" => Some(LogLevelFilter::Off),
+        _ => None,
+    }
+}
+
+/// Picks the protocol version to use with a client on the given version, or `None` if it is not supported.
+/// Debug builds of the client send 0xffff, which always gets the current version.
+pub const fn negotiate_protocol(client: u16) -> Option<u16> {
+    if client == 0xffff {
+        Some(PROTOCOL_VERSION)
+    } else if client >= MIN_PROTOCOL_VERSION && client <= PROTOCOL_VERSION {
+        Some(client)
+    } else {
+        None
+    }
+}
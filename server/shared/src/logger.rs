@@ -2,45 +2,209 @@ use crate::SyncMutex;
 use std::{
     fs::File,
     io::{BufWriter, Write},
-    sync::OnceLock,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        OnceLock,
+    },
     time::SystemTime,
 };
 
 use colored::Colorize;
-use time::{format_description, OffsetDateTime};
+use time::{format_description, format_description::well_known::Rfc3339, OffsetDateTime};
 
 pub use log;
 pub use log::{debug, error, info, trace, warn, Level as LogLevel, LevelFilter as LogLevelFilter};
 
+struct LogFile {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    written: u64,
+}
+
 pub struct Logger {
     pub format_desc: Vec<format_description::FormatItem<'static>>,
     self_crate_name: &'static str,
-    file_writer: Option<SyncMutex<BufWriter<File>>>,
+    file_writer: Option<SyncMutex<LogFile>>,
+    json: AtomicBool,
+    max_file_size: AtomicU64,
+    max_old_files: AtomicUsize,
+    /// set once shutting down, after which every line is flushed right away
+    write_through: AtomicBool,
 }
 
 const TIME_FORMAT: &str = "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]";
+// used in the names of rotated log files, so they sort chronologically
+const ROTATED_TIME_FORMAT: &str = "[year]-[month]-[day]_[hour]-[minute]-[second]-[subsecond digits:3]";
 const LOG_BUFFER_CAPACITY: usize = 2048;
 
 impl Logger {
     #[allow(clippy::missing_panics_doc)]
     pub fn instance(self_crate_name: &'static str, write_to_file: bool) -> &'static Self {
         static INSTANCE: OnceLock<Logger> = OnceLock::new();
-        INSTANCE.get_or_init(|| Self {
+        INSTANCE.get_or_init(|| {
+            let path = write_to_file.then(|| std::env::current_dir().unwrap().join(format!("{self_crate_name}.log")));
+            Self::new(self_crate_name, path)
+        })
+    }
+
+    /// Creates a logger that isn't registered anywhere, writing to the log file at `path` if one is given.
+    /// Most code should use [`Logger::instance`] instead.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn new(self_crate_name: &'static str, path: Option<PathBuf>) -> Self {
+        Self {
             format_desc: format_description::parse_borrowed::<2>(TIME_FORMAT).unwrap(),
             self_crate_name,
-            file_writer: if write_to_file {
-                let file = File::create(std::env::current_dir().unwrap().join(format!("{self_crate_name}.log")));
-
-                if let Ok(file) = file {
-                    Some(SyncMutex::new(BufWriter::with_capacity(LOG_BUFFER_CAPACITY, file)))
-                } else {
-                    eprintln!("failed to open log file for writing: {}", file.unwrap_err());
+            file_writer: path.and_then(|path| match File::create(&path) {
+                Ok(file) => Some(SyncMutex::new(LogFile {
+                    writer: BufWriter::with_capacity(LOG_BUFFER_CAPACITY, file),
+                    path,
+                    written: 0,
+                })),
+                Err(e) => {
+                    eprintln!("failed to open log file for writing: {e}");
                     None
                 }
-            } else {
-                None
-            },
-        })
+            }),
+            json: AtomicBool::new(false),
+            max_file_size: AtomicU64::new(0),
+            max_old_files: AtomicUsize::new(0),
+            write_through: AtomicBool::new(false),
+        }
+    }
+
+    /// Enables log file rotation. Once the log file grows past `max_size` bytes, it gets renamed to include the current time
+    /// and a new file is started. Only the `max_old_files` most recent rotated files are kept. `max_size` of 0 disables rotation.
+    pub fn set_rotation(&self, max_size: u64, max_old_files: usize) {
+        self.max_file_size.store(max_size, Ordering::Relaxed);
+        self.max_old_files.store(max_old_files, Ordering::Relaxed);
+    }
+
+    fn write_to_file(&self, line: &str) {
+        let Some(file) = self.file_writer.as_ref() else {
+            return;
+        };
+
+        // rotation happens under the same lock as writes and flushes, so nothing can get lost or written into a closed file
+        let mut file = file.lock();
+
+        if let Err(e) = writeln!(file.writer, "{line}") {
+            eprintln!("Failed to write to the logfile: {e}");
+            return;
+        }
+
+        file.written += line.len() as u64 + 1;
+
+        if self.write_through.load(Ordering::Relaxed) {
+            if let Err(e) = file.writer.flush() {
+                eprintln!("Failed to flush the logfile: {e}");
+            }
+        }
+
+        let max_size = self.max_file_size.load(Ordering::Relaxed);
+        if max_size != 0 && file.written >= max_size {
+            if let Err(e) = self.rotate(&mut file) {
+                eprintln!("Failed to rotate the logfile: {e}");
+            }
+        }
+    }
+
+    fn rotate(&self, file: &mut LogFile) -> std::io::Result<()> {
+        file.writer.flush()?;
+
+        let now: OffsetDateTime = SystemTime::now().into();
+        let format = format_description::parse_borrowed::<2>(ROTATED_TIME_FORMAT).unwrap();
+        let timestamp = now.format(&format).unwrap_or_default();
+
+        let rotated_path = file.path.with_file_name(format!("{}-{timestamp}.log", self.self_crate_name));
+        std::fs::rename(&file.path, rotated_path)?;
+
+        file.writer = BufWriter::with_capacity(LOG_BUFFER_CAPACITY, File::create(&file.path)?);
+        file.written = 0;
+
+        self.remove_old_files(&file.path);
+
+        Ok(())
+    }
+
+    fn remove_old_files(&self, current_path: &Path) {
+        let Some(dir) = current_path.parent() else {
+            return;
+        };
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        let prefix = format!("{}-", self.self_crate_name);
+        let mut rotated: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".log"))
+            })
+            .collect();
+
+        let max_old_files = self.max_old_files.load(Ordering::Relaxed);
+        if rotated.len() <= max_old_files {
+            return;
+        }
+
+        // names contain the timestamp, so the oldest files come first
+        rotated.sort();
+
+        for path in &rotated[..rotated.len() - max_old_files] {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Failed to remove old logfile {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Flushes everything written so far to the log file, and makes every later line get written straight to disk,
+    /// so that lines logged by other threads while the process is exiting are not lost in the buffer.
+    ///
+    /// This blocks on file I/O, so async code should call it through `spawn_blocking`.
+    pub fn flush_for_shutdown(&self) -> std::io::Result<()> {
+        let Some(file) = self.file_writer.as_ref() else {
+            return Ok(());
+        };
+
+        // under the lock, so no line can end up in the buffer after the flush without being flushed itself
+        let mut file = file.lock();
+        self.write_through.store(true, Ordering::Relaxed);
+        file.writer.flush()
+    }
+
+    /// When enabled, every record is written as a single line JSON object instead of the human-readable format,
+    /// both to the console and to the log file. Key-value pairs attached to the record (like `account_id`) are included as fields.
+    pub fn set_json(&self, enabled: bool) {
+        self.json.store(enabled, Ordering::Relaxed);
+    }
+
+    fn format_json(now: OffsetDateTime, record: &log::Record) -> String {
+        struct FieldCollector<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+        impl<'kvs> log::kv::VisitSource<'kvs> for FieldCollector<'_> {
+            fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+                let value = value
+                    .to_i64()
+                    .map_or_else(|| serde_json::Value::String(value.to_string()), serde_json::Value::from);
+
+                self.0.insert(key.to_string(), value);
+                Ok(())
+            }
+        }
+
+        let mut fields = serde_json::Map::new();
+        fields.insert("timestamp".to_owned(), now.format(&Rfc3339).unwrap_or_default().into());
+        fields.insert("level".to_owned(), record.level().as_str().into());
+        fields.insert("target".to_owned(), record.target().into());
+        fields.insert("message".to_owned(), record.args().to_string().into());
+
+        let _ = record.key_values().visit(&mut FieldCollector(&mut fields));
+
+        serde_json::Value::Object(fields).to_string()
     }
 }
 
@@ -59,13 +223,24 @@ impl log::Log for Logger {
         }
 
         let now: OffsetDateTime = SystemTime::now().into();
-        let formatted_time = now.format(&self.format_desc).unwrap();
 
-        if let Some(file) = self.file_writer.as_ref() {
-            let mut file = file.lock();
-            if let Err(e) = writeln!(file, "[{formatted_time}] [{}] - {}", record.level(), record.args()) {
-                eprintln!("Failed to write to the logfile: {e}");
+        if self.json.load(Ordering::Relaxed) {
+            let line = Self::format_json(now, record);
+            self.write_to_file(&line);
+
+            if record.level() == LogLevel::Error {
+                eprintln!("{line}");
+            } else {
+                println!("{line}");
             }
+
+            return;
+        }
+
+        let formatted_time = now.format(&self.format_desc).unwrap();
+
+        if self.file_writer.is_some() {
+            self.write_to_file(&format!("[{formatted_time}] [{}] - {}", record.level(), record.args()));
         }
 
         let (level, args) = match record.level() {
@@ -84,6 +259,6 @@ impl log::Log for Logger {
     }
 
     fn flush(&self) {
-        self.file_writer.as_ref().map(|w| w.lock().flush());
+        self.file_writer.as_ref().map(|w| w.lock().writer.flush());
     }
 }
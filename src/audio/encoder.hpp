@@ -46,6 +46,9 @@ public:
     // sets the amount of channels that will be used and recreates the encoder
     Result<> setChannels(int channels);
 
+    // sets the bitrate for the encoder in bits per second, or OPUS_AUTO to let opus pick it
+    Result<> setBitrate(int bitrate);
+
 private:
     // EXPERIMENTAL ZONE
     //
@@ -56,9 +59,6 @@ private:
     // resets the internal state of the encoder
     Result<> resetState();
 
-    // sets the bitrate for the encoder
-    Result<> setBitrate(int bitrate);
-
     // sets the encoder complexity (1-10)
     Result<> setComplexity(int complexity);
 
@@ -220,6 +220,12 @@ void GlobedAudioManager::setRecordBufferCapacity(size_t frames) {
     recordFrame.setCapacity(frames);
 }
 
+void GlobedAudioManager::setBitrateLimits(uint32_t minBitrate, uint32_t maxBitrate) {
+    // aim for the maximum, the server leaves some headroom above it for variable bitrate
+    uint32_t bitrate = maxBitrate != 0 ? maxBitrate : minBitrate;
+    encoderBitrate = static_cast<int>(bitrate * 1000);
+}
+
 Result<> GlobedAudioManager::startRecordingInternal(bool passive) {
     if (!permission::getPermissionStatus(Permission::RecordAudio)) {
         return Err("Recording failed, please grant microphone permission in Globed settings");
@@ -552,6 +558,12 @@ Result<> GlobedAudioManager::audioThreadWork() {
             float pcmbuf[VOICE_TARGET_FRAMESIZE];
             recordQueue.copyTo(pcmbuf, VOICE_TARGET_FRAMESIZE);
 
+            int bitrate = encoderBitrate;
+            if (bitrate != appliedEncoderBitrate) {
+                GLOBED_UNWRAP(encoder.setBitrate(bitrate == 0 ? OPUS_AUTO : bitrate));
+                appliedEncoderBitrate = bitrate;
+            }
+
             GLOBED_UNWRAP_INTO(encoder.encode(pcmbuf), auto opusFrame);
             GLOBED_UNWRAP(recordFrame.pushOpusFrame(opusFrame));
         }
@@ -74,6 +74,10 @@ public:
     // set the amount of record frames in a buffer (used by the lowerAudioLatency setting)
     void setRecordBufferCapacity(size_t frames);
 
+    // set the bitrate range (in kbit/s) the server allows for voice, 0 means unrestricted.
+    // the encoder picks up the change before encoding the next frame.
+    void setBitrateLimits(uint32_t minBitrate, uint32_t maxBitrate);
+
     // start recording the voice and call the callback once a full frame is ready.
     // if `stopRecording()` is called at any point, the callback will be called with the remaining data.
     // in that case it may have less than the full 10 frames.
@@ -149,6 +153,8 @@ private:
     void internalStopRecording();
 
     AudioEncoder encoder;
+    asp::AtomicI32 encoderBitrate = 0; // in bits per second, 0 if opus can pick it
+    int appliedEncoderBitrate = 0; // only used on the audio thread
 
     /* misc */
     FMOD::System* cachedSystem = nullptr;
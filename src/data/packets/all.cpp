@@ -16,6 +16,7 @@ std::shared_ptr<Packet> matchPacket(packetid_t packetId) {
         PACKET(KeepaliveTCPResponsePacket);
         PACKET(ClaimThreadFailedPacket);
         PACKET(LoginRecoveryFailecPacket);
+        PACKET(LoginQueuedPacket);
 
         PACKET(ServerNoticePacket);
         PACKET(ServerBannedPacket);
@@ -24,9 +24,11 @@ class CryptoHandshakeStartPacket : public Packet {
 
     uint16_t protocol;
     CryptoPublicKey key;
+    // optional features we support, none for now
+    uint8_t capabilities = 0;
 };
 
-GLOBED_SERIALIZABLE_STRUCT(CryptoHandshakeStartPacket, (protocol, key));
+GLOBED_SERIALIZABLE_STRUCT(CryptoHandshakeStartPacket, (protocol, key, capabilities));
 
 // 10002 - KeepalivePacket
 class KeepalivePacket : public Packet {
@@ -66,6 +68,7 @@ class LoginPacket : public Packet {
     PlayerIconData icons;
     uint16_t fragmentationLimit;
     std::string platform;
+    bool spectator = false;
 };
 
 GLOBED_SERIALIZABLE_STRUCT(LoginPacket, (
@@ -75,7 +78,8 @@ GLOBED_SERIALIZABLE_STRUCT(LoginPacket, (
     token,
     icons,
     fragmentationLimit,
-    platform
+    platform,
+    spectator
 ));
 
 // 10005 - ClaimThreadPacket
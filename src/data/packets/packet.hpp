@@ -62,10 +62,18 @@ public:
 };
 
 struct PacketHeader {
-    static constexpr size_t SIZE = sizeof(packetid_t) + sizeof(bool);
+    static constexpr size_t SIZE = sizeof(packetid_t) + sizeof(uint8_t);
+
+    static constexpr uint8_t FLAG_ENCRYPTED = 1 << 0;
+    static constexpr uint8_t FLAG_COMPRESSED = 1 << 1;
+    static constexpr uint8_t FLAG_CHECKSUM = 1 << 2;
 
     packetid_t id;
-    bool encrypted;
+    uint8_t flags;
+
+    bool encrypted() const {
+        return (flags & FLAG_ENCRYPTED) != 0;
+    }
 };
 
-GLOBED_SERIALIZABLE_STRUCT(PacketHeader, (id, encrypted));
+GLOBED_SERIALIZABLE_STRUCT(PacketHeader, (id, flags));
@@ -11,8 +11,9 @@ class PingResponsePacket : public Packet {
     PingResponsePacket() {}
 
     uint32_t id, playerCount;
+    uint64_t uptime;
 };
-GLOBED_SERIALIZABLE_STRUCT(PingResponsePacket, (id, playerCount));
+GLOBED_SERIALIZABLE_STRUCT(PingResponsePacket, (id, playerCount, uptime));
 
 // 20001 - CryptoHandshakeResponsePacket
 class CryptoHandshakeResponsePacket : public Packet {
@@ -21,8 +22,10 @@ class CryptoHandshakeResponsePacket : public Packet {
     CryptoHandshakeResponsePacket() {}
 
     CryptoPublicKey data;
+    uint16_t protocol;
+    uint8_t capabilities;
 };
-GLOBED_SERIALIZABLE_STRUCT(CryptoHandshakeResponsePacket, (data));
+GLOBED_SERIALIZABLE_STRUCT(CryptoHandshakeResponsePacket, (data, protocol, capabilities));
 
 // 20002 - KeepaliveResponsePacket
 class KeepaliveResponsePacket : public Packet {
@@ -31,8 +34,9 @@ class KeepaliveResponsePacket : public Packet {
     KeepaliveResponsePacket() {}
 
     uint32_t playerCount;
+    uint64_t timestamp;
 };
-GLOBED_SERIALIZABLE_STRUCT(KeepaliveResponsePacket, (playerCount));
+GLOBED_SERIALIZABLE_STRUCT(KeepaliveResponsePacket, (playerCount, timestamp));
 
 // 20003 - ServerDisconnectPacket
 class ServerDisconnectPacket : public Packet {
@@ -41,8 +45,9 @@ class ServerDisconnectPacket : public Packet {
     ServerDisconnectPacket() {}
 
     std::string message;
+    uint8_t reason;
 };
-GLOBED_SERIALIZABLE_STRUCT(ServerDisconnectPacket, (message));
+GLOBED_SERIALIZABLE_STRUCT(ServerDisconnectPacket, (message, reason));
 
 // 20004 - LoggedInPacket
 class LoggedInPacket : public Packet {
@@ -54,8 +59,11 @@ class LoggedInPacket : public Packet {
     SpecialUserData specialUserData;
     std::vector<GameServerRole> allRoles;
     uint32_t secretKey;
+    uint16_t protocol;
+    uint32_t keepaliveInterval;
+    uint32_t voiceMinBitrate, voiceMaxBitrate;
 };
-GLOBED_SERIALIZABLE_STRUCT(LoggedInPacket, (tps, specialUserData, allRoles, secretKey));
+GLOBED_SERIALIZABLE_STRUCT(LoggedInPacket, (tps, specialUserData, allRoles, secretKey, protocol, keepaliveInterval, voiceMinBitrate, voiceMaxBitrate));
 
 // 20005 - LoginFailedPacket
 class LoginFailedPacket : public Packet {
@@ -64,8 +72,9 @@ class LoginFailedPacket : public Packet {
     LoginFailedPacket() {}
 
     std::string message;
+    uint8_t reason;
 };
-GLOBED_SERIALIZABLE_STRUCT(LoginFailedPacket, (message));
+GLOBED_SERIALIZABLE_STRUCT(LoginFailedPacket, (message, reason));
 
 // 20006 - ProtocolMismatchPacket
 class ProtocolMismatchPacket : public Packet {
@@ -75,8 +84,9 @@ class ProtocolMismatchPacket : public Packet {
 
     uint16_t serverProtocol;
     std::string minClientVersion;
+    uint16_t minProtocol;
 };
-GLOBED_SERIALIZABLE_STRUCT(ProtocolMismatchPacket, (serverProtocol, minClientVersion));
+GLOBED_SERIALIZABLE_STRUCT(ProtocolMismatchPacket, (serverProtocol, minClientVersion, minProtocol));
 
 // 20007 - KeepaliveTCPResponsePacket
 class KeepaliveTCPResponsePacket : public Packet {
@@ -102,6 +112,17 @@ class LoginRecoveryFailecPacket : public Packet {
 };
 GLOBED_SERIALIZABLE_STRUCT(LoginRecoveryFailecPacket, ());
 
+// 20011 - LoginQueuedPacket
+class LoginQueuedPacket : public Packet {
+    GLOBED_PACKET(20011, LoginQueuedPacket, false, false)
+
+    LoginQueuedPacket() {}
+
+    uint32_t position;
+    uint32_t keepaliveInterval;
+};
+GLOBED_SERIALIZABLE_STRUCT(LoginQueuedPacket, (position, keepaliveInterval));
+
 // 20100 - ServerNoticePacket
 class ServerNoticePacket : public Packet {
     GLOBED_PACKET(20100, ServerNoticePacket, false, false)
@@ -215,7 +215,7 @@ Result<PollResult> GameSocket::poll(int timeoutMs) {
 Result<> GameSocket::encodePacket(Packet& packet, ByteBuffer& buffer) {
     PacketHeader header = {
         .id = packet.getPacketId(),
-        .encrypted = packet.getEncrypted(),
+        .flags = packet.getEncrypted() ? PacketHeader::FLAG_ENCRYPTED : uint8_t(0),
     };
 
     bool tcp = packet.getUseTcp();
@@ -268,11 +268,14 @@ Result<std::shared_ptr<Packet>> GameSocket::decodePacket(ByteBuffer& buffer) {
 
     GLOBED_REQUIRE_SAFE(packet.get() != nullptr, std::string("invalid server-side packet: ") + std::to_string(header.id))
 
-    if (packet->getEncrypted() && !header.encrypted) {
+    // we never ask for compression in the handshake, so the server must not send compressed packets
+    GLOBED_REQUIRE_SAFE((header.flags & PacketHeader::FLAG_COMPRESSED) == 0, "server sent a compressed packet without it being negotiated")
+
+    if (packet->getEncrypted() && !header.encrypted()) {
         GLOBED_REQUIRE_SAFE(false, "server sent a cleartext packet when expected an encrypted one")
     }
 
-    if (header.encrypted) {
+    if (header.encrypted()) {
         GLOBED_REQUIRE_SAFE(cryptoBox.get() != nullptr, "attempted to decrypt a packet when no cryptobox is initialized")
         bytevector& bufvec = buffer.data();
 
@@ -19,6 +19,7 @@
 #include <managers/settings.hpp>
 #include <managers/room.hpp>
 #include <managers/role.hpp>
+#include <audio/manager.hpp>
 #include <util/cocos.hpp>
 #include <util/format.hpp>
 #include <util/time.hpp>
@@ -29,7 +30,7 @@ using namespace asp::sync;
 using namespace geode::prelude;
 using ConnectionState = NetworkManager::ConnectionState;
 
-static constexpr uint16_t PROTOCOL_VERSION = 6;
+static constexpr uint16_t PROTOCOL_VERSION = 8;
 
 // yes, really
 struct AtomicConnectionState {
@@ -205,6 +206,9 @@ protected:
     AtomicBool cancellingRecovery;
     AtomicU32 secretKey;
     AtomicU32 serverTps;
+    // set while the server is full and we are waiting in its login queue
+    AtomicBool loginQueued;
+    AtomicU32 queueKeepaliveInterval;
 
     Impl() {
         // initialize winsock
@@ -259,6 +263,7 @@ protected:
 
         this->standalone = standalone;
         this->wasFromRecovery = fromRecovery;
+        this->loginQueued = false;
 
         lastReceivedPacket = util::time::now();
         lastSentKeepalive = util::time::now();
@@ -453,6 +458,12 @@ protected:
             this->onProtocolMismatch(std::move(packet));
         });
 
+        addInternalListener<LoginQueuedPacket>([this](auto packet) {
+            loginQueued = true;
+            queueKeepaliveInterval = packet->keepaliveInterval;
+            ErrorQueues::get().notice(fmt::format("The server is full, you are <cy>#{}</c> in the queue. You will be connected once there is a free slot.", packet->position));
+        });
+
         addInternalListener<ClaimThreadFailedPacket>([this](auto packet) {
             this->disconnectWithMessage("failed to claim udp thread");
         });
@@ -610,7 +621,15 @@ protected:
     }
 
     void onLoggedIn(std::shared_ptr<LoggedInPacket> packet) {
+        // the handshake is not encrypted, so make sure nobody downgraded the protocol in the meantime
+        if (!ignoreProtocolMismatch && packet->protocol != PROTOCOL_VERSION) {
+            log::warn("server negotiated protocol v{}, but we requested v{}", packet->protocol, PROTOCOL_VERSION);
+            this->disconnectWithMessage("protocol version mismatch after logging in");
+            return;
+        }
+
         log::info("Successfully logged into the server!");
+        loginQueued = false;
         serverTps = packet->tps;
         secretKey = packet->secretKey;
         state = ConnectionState::Established;
@@ -625,6 +644,10 @@ protected:
 
         GameServerManager::get().setActive(connectedServerId);
 
+#ifdef GLOBED_VOICE_SUPPORT
+        GlobedAudioManager::get().setBitrateLimits(packet->voiceMinBitrate, packet->voiceMaxBitrate);
+#endif
+
         // these are not thread-safe, so delay it
         Loader::get()->queueInMainThread([specialUserData = std::move(packet->specialUserData), allRoles = std::move(packet->allRoles)] {
             auto& pcm = ProfileCacheManager::get();
@@ -898,7 +921,7 @@ protected:
             return;
         }
         // Detect if authentication is taking too long
-        else if (state == ConnectionState::Authenticating && !recovering && (util::time::now() - lastReceivedPacket) > util::time::seconds(5)) {
+        else if (state == ConnectionState::Authenticating && !recovering && !loginQueued && (util::time::now() - lastReceivedPacket) > util::time::seconds(5)) {
             this->disconnect(true);
 
             ErrorQueues::get().error(fmt::format("Failed to connect to the server.\n\nReason: <cy>server took too long to respond to the handshake</c>"));
@@ -907,6 +930,13 @@ protected:
 
         if (this->established()) {
             this->maybeSendKeepalive();
+        } else if (state == ConnectionState::Authenticating && loginQueued) {
+            // the server drops queued logins that stop sending keepalives
+            auto interval = util::time::seconds(std::max<uint32_t>(queueKeepaliveInterval.load(), 1));
+            if (util::time::now() - lastTcpExchange > interval) {
+                this->send(KeepaliveTCPPacket::create());
+                lastTcpExchange = util::time::now();
+            }
         }
 
         // poll for any incoming packets